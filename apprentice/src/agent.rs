@@ -1,14 +1,16 @@
 use crate::config::Config;
 use crate::prompts::Prompts;
-use apprentice_lib::rag;
-use crate::tools::{Help, Shell};
-use apprentice_lib::llm::{get_llm_chat, LLMChat, Message, Role, ToolCall};
+use crate::tools::{Fetch, Help, ReadFile, Shell};
+use apprentice_lib::llm::{get_llm_chat, LLMChat, Message, Role, StopReason, ToolCall, Usage};
 use apprentice_lib::tools::ToolChoice;
 use crate::error::AppError;
 use crate::term::Term;
+use crate::session::Session;
+use crate::util::{provider_error_hint, set_interrupt_handler, take_interrupted, Redactor};
 use apprentice_lib::Error;
-use apprentice_lib::request::get_reqwest_client;
+use apprentice_lib::request::{CancellationToken, ReqwestClient};
 use rustyline::error::ReadlineError;
+use std::time::Duration;
 
 /// Agent.
 pub struct Agent {
@@ -16,75 +18,159 @@ pub struct Agent {
     term: Term,
     shell: Shell,
     help: Help,
+    read_file: ReadFile,
+    fetch: Fetch,
     chat: Box<dyn LLMChat>,
-    embedding: Box<dyn rag::Embedding>,
+    usage: Usage,
 }
 
 impl Agent {
 
     /// Create new agent.
-    pub fn new(config: Config, prompts: Prompts) -> Result<Self, AppError> {
+    pub fn new(config: Config, prompts: Prompts, session: Option<Session>) -> Result<Self, AppError> {
         let term = Term::new(&config)?;
 
         term.print_logo();
 
-        let shell = Shell::new();
-        let help = Help::new(config.goal);
+        let redactor = if config.settings.redact_secrets { Some(Redactor::new(&config.settings.redact_patterns)?) } else { None };
+        let shell = Shell::new(config.dry_run, config.settings.auto_approve.clone(), config.command_timeout.map(Duration::from_secs), config.settings.max_tool_output_bytes, redactor.clone());
+        let help = Help::new(config.goal.help_commands(), !config.json, config.settings.max_tool_output_bytes, redactor);
+        let read_file = ReadFile::new(config.allow_any_path);
+        let fetch = Fetch::new();
 
         let tools = vec![
             shell.get_tool_spec(),
-            help.get_tool_spec()
+            help.get_tool_spec(),
+            read_file.get_tool_spec(),
+            fetch.get_tool_spec(),
         ];
 
-        term.loading_progress("Initializing embeddings model...");
+        term.loading_progress("Intitializing chat with llm...");
 
-        let embedding = rag::get_embedding(rag::Type::HuggingFace)?;
+        // Ctrl+C cancels the inference, and ends the session.
+        let cancellation = CancellationToken::new();
+        set_interrupt_handler(cancellation.clone())?;
 
-        term.loading_progress("Intitializing chat with llm...");
+        let reqwest_client = Box::new(ReqwestClient::builder().verbose(config.verbose).cancellation(cancellation).build());
+        for field in config.model_params.unsupported_fields(config.model_params.provider) {
+            term.loading_progress(&format!("Parameter {} is not supported by {} and will be ignored.", field, config.model_params.provider));
+        }
 
-        let reqwest_client = get_reqwest_client()?;
         let mut chat = get_llm_chat(config.model_params.clone(), reqwest_client, tools)?;
         chat.set_system_prompt(prompts.get(0)?.into());
 
+        if let Some(session) = session {
+            Self::restore_session(&term, chat.as_mut(), &config, session);
+        }
+
         term.loading_progress("Apprentice is ready.");
 
         Ok(Agent {
             shell,
             help,
+            read_file,
+            fetch,
             config,
             term,
             chat,
-            embedding,
+            usage: Usage::default(),
         })
     }
 
+    fn restore_session(term: &Term, chat: &mut dyn LLMChat, config: &Config, session: Session) {
+        let provider = config.model_params.provider.to_string();
+
+        if session.version != env!("CARGO_PKG_VERSION") {
+            term.loading_progress(&format!("Conversation was saved by apprentice ver. {}, restoring it anyway.", session.version));
+        }
+
+        if session.provider != provider {
+            term.loading_progress(&format!(
+                "Conversation was saved with model provider {}, it can't be restored with {}. Starting a new conversation.",
+                session.provider, provider
+            ));
+            return;
+        }
+
+        if session.model != config.model_params.name {
+            term.loading_progress(&format!("Conversation was saved with model {}, continuing with {}.", session.model, config.model_params.name));
+        }
+
+        chat.import_history(session.history);
+        term.loading_progress("Conversation restored.");
+    }
+
+    fn save_session(&self) -> Result<(), AppError> {
+        if let Some(path) = &self.config.save {
+            let session = Session {
+                version: env!("CARGO_PKG_VERSION").to_owned(),
+                goal: self.config.goal.to_string(),
+                provider: self.config.model_params.provider.to_string(),
+                model: self.config.model_params.name.clone(),
+                history: self.chat.export_history(),
+            };
+            session.save(path)?;
+        }
+        Ok(())
+    }
+
     /// Run agent.
+    /// Aborting the session is not an error.
     pub fn run(&mut self) -> Result<(), AppError> {
+        let ret = match self.chat_loop() {
+            Err(AppError::Aborted) => {
+                self.term.loading_progress("Session aborted.");
+                Ok(())
+            },
+            ret => ret,
+        };
+        let saved = self.save_session();
+        let history_saved = self.term.save_history();
+        ret.and(saved).and(history_saved)
+    }
+
+    fn chat_loop(&mut self) -> Result<(), AppError> {
         self.term.loading_progress("For help use ?, to exit use Ctrl+C");
 
-        let mut next_message = if let Some(first_message) = &self.config.message {
+        let mut next_messages = if let Some(first_message) = &self.config.message {
             let user_message = Message::text(Role::User, first_message.clone());
 
-            let response = self.chat.get_inference(&[user_message], ToolChoice::Auto)
-                .map_err(AppError::LibError);
+            let response = self.get_inference(&[user_message]);
 
-            if let Some(msg) = self.process_response(response)? {
-                msg
+            if let Some(messages) = self.process_response(response)? {
+                messages
             } else {
                 return Ok(());
             }
         } else if let Some(msg) = self.get_user_message()? {
-            msg
+            vec![msg]
         } else {
             return Ok(());
         };
 
+        // Model turns in a row without user input.
+        let mut turns = 0;
         loop {
-            let response = self.chat.get_inference(&[next_message], ToolChoice::Auto)
-            .map_err(AppError::LibError);
+            if matches!(next_messages.first(), Some(Message::ToolResult(_))) {
+                turns += 1;
+                if self.config.max_turns.is_some_and(|max_turns| turns > max_turns) {
+                    self.term.loading_progress(&format!("Apprentice exceeded the limit of {} turns without user input.", turns - 1));
+                    turns = 0;
+                    // Tool results are still sent to keep the history consistent.
+                    if let Some(msg) = self.get_user_message()? {
+                        next_messages.push(msg);
+                    } else {
+                        break;
+                    }
+                }
+            } else {
+                turns = 0;
+            }
+
+            let response = self.get_inference(&next_messages);
 
-            next_message = if let Some(message) = self.process_response(response)? {
-                message
+            next_messages = if let Some(messages) = self.process_response(response)? {
+                messages
             } else {
                 break;
             }
@@ -93,6 +179,15 @@ impl Agent {
         Ok(())
     }
 
+    fn get_inference(&mut self, messages: &[Message]) -> Result<Vec<Message>, AppError> {
+        let _spinner = self.term.start_spinner();
+        self.chat.get_inference(messages, ToolChoice::Auto)
+            .map_err(|err| match err {
+                Error::Cancelled => AppError::Aborted,
+                err => AppError::LibError(err),
+            })
+    }
+
     fn get_user_message(&mut self) -> Result<Option<Message>, AppError> {
         loop {
             let user_input = self.term.user_input();
@@ -113,78 +208,108 @@ impl Agent {
         }
     }
 
-    fn process_response(&mut self, response: Result<Vec<Message>, AppError>) -> Result<Option<Message>, AppError> {
+    fn process_response(&mut self, response: Result<Vec<Message>, AppError>) -> Result<Option<Vec<Message>>, AppError> {
         if let Ok(results) = response {
-            if results.len() > 1 || results.is_empty() {
-                let mut tool_msg = None;
-                for message in results.iter() {
-                    match message {
-                        Message::Text(text) => { 
-                            self.term.apprentice_print(&text.message);
-                        },
-                        Message::ToolCall(tool_call) => {
-                            if tool_msg.replace(tool_call).is_some() {
-                                return Err(AppError::ApplicationError("Unexpected LLM response: parallel tool call is requested."))
-                            }
-                        },
-                        Message::ToolResult(_) => {
-                            return Err(AppError::ApplicationError("Unexpected \"tool result\" message from LLM."))
-                        }
+            let mut tool_calls = vec![];
+            for message in results.iter() {
+                match message {
+                    Message::Text(_) => { 
+                        self.print_message(message);
+                    },
+                    Message::ToolCall(tool_call) => {
+                        tool_calls.push(tool_call);
+                    },
+                    Message::ToolResult(_) => {
+                        return Err(AppError::ApplicationError("Unexpected \"tool result\" message from LLM."))
                     }
                 }
+            }
+
+            if self.chat.stop_reason() == Some(StopReason::MaxTokens) {
+                self.term.print_notice("(response truncated — increase --max-tokens)");
+            }
 
-                if let Some(tool_call) = tool_msg {
-                    self.process_tool_call(tool_call)
-                } else {
-                    self.get_user_message()
+            if self.config.show_usage {
+                if let Some(usage) = self.chat.usage() {
+                    self.usage += usage;
+                    self.term.print_usage(self.usage);
                 }
-                
+            }
+
+            if tool_calls.is_empty() {
+                Ok(self.get_user_message()?.map(|msg| vec![msg]))
             } else {
-                let message = &results[0];
-                match message {
-                    Message::Text(text) => { 
-                        self.term.apprentice_print(&text.message);
-                        self.get_user_message()
+                let mut tool_results = Vec::with_capacity(tool_calls.len());
+                let mut interrupted = false;
+                for (i, tool_call) in tool_calls.iter().enumerate() {
+                    match self.process_tool_call(tool_call) {
+                        Ok(result) => tool_results.push(result),
+                        Err(AppError::Aborted) => {
+                            // Every call of the model turn is answered to keep the saved conversation valid.
+                            tool_results.extend(tool_calls[i..].iter().map(|call| call.to_result("Session aborted by the user.".to_owned())));
+                            self.chat.add_messages(&tool_results);
+                            return Err(AppError::Aborted);
+                        },
+                        Err(err) => return Err(err),
                     }
-                    Message::ToolCall(tool_call) => {
-                        self.process_tool_call(tool_call)
+                    if take_interrupted() {
+                        tool_results.extend(tool_calls[i + 1..].iter().map(|call| call.to_result("Skipped, the user interrupted the previous command.".to_owned())));
+                        interrupted = true;
+                        break;
                     }
-                    Message::ToolResult(_) => {
-                        Err(AppError::ApplicationError("Unexpected message type from the LLM."))
+                }
+                if interrupted {
+                    self.term.print_notice("(command interrupted)");
+                    // Tool results are still sent to keep the history consistent.
+                    if let Some(msg) = self.get_user_message()? {
+                        tool_results.push(msg);
+                    } else {
+                        return Ok(None);
                     }
                 }
+                Ok(Some(tool_results))
             }
-        } else if let Err(AppError::LibError(llmerr)) = response {
-            if let Error::LLMErrorMessage(msg) = llmerr {
-                self.term.apprentice_print(&format!("{}", AppError::LibError(Error::LLMErrorMessage(msg))));
-            } else if let Error::LLMCallError(msg) = llmerr {
-                self.term.apprentice_print(&format!("{}", AppError::LibError(Error::LLMCallError(msg))));
-            }
-            self.get_user_message()
+        } else if let Err(AppError::LibError(err)) = response {
+            // Every library error is shown, so the user knows why there is no answer.
+            let hint = match &err {
+                Error::ProviderError { code, kind, .. } => provider_error_hint(code.as_deref(), kind.as_deref()),
+                _ => "",
+            };
+            self.term.print_error(&format!("{}{}", AppError::LibError(err), hint));
+            Ok(self.get_user_message()?.map(|msg| vec![msg]))
         } else {
             Err(response.err().unwrap())
         }
     }
 
-    fn process_tool_call(&mut self, tool_call: &ToolCall) -> Result<Option<Message>, AppError> {
+    fn print_message(&self, message: &Message) {
+        if self.term.is_json() {
+            self.term.print_json(message);
+        } else if let Message::Text(text) = message {
+            self.term.apprentice_print(&text.message);
+        }
+    }
+
+    fn process_tool_call(&mut self, tool_call: &ToolCall) -> Result<Message, AppError> {
+        self.print_message(&Message::ToolCall(tool_call.clone()));
+
         let tool_result = if tool_call.name == "SHELL" {
-            match self.shell.call_tool(&tool_call.params, &mut self.term) {
-                Ok(result) => result,
-                Err(err) => return Err(err),
-            }
+            self.shell.call_tool(&tool_call.params, &mut self.term)?
         } else if tool_call.name == "HELP" {
-            match self.help.call_tool(&tool_call.params) {
-                Ok(result) => result,
-                Err(err) => return Err(err),
-            }
+            self.help.call_tool(&tool_call.params)?
+        } else if tool_call.name == "READ_FILE" {
+            self.read_file.call_tool(&tool_call.params)?
+        } else if tool_call.name == "FETCH" {
+            self.fetch.call_tool(&tool_call.params, &mut self.term)?
         } else {
             format!("Unknown tool \"{}\" was requested.", tool_call.name)
         };
 
-        Ok(Some(Message::tool_result(
-            tool_call.call_id.clone(), 
-            tool_call.name.clone(), 
-            tool_result)))
+        let result = tool_call.to_result(tool_result);
+
+        self.print_message(&result);
+
+        Ok(result)
     }
 
     fn process_user_input_errors(&self, err: AppError) -> Result<bool, AppError> {
@@ -0,0 +1,178 @@
+//! Tab-completion of CLI subcommands.
+
+use rustyline::completion::Completer;
+use rustyline::Context;
+use crate::config::Goal;
+
+const GCP_COMMANDS: &[&str] = &[
+    "gcloud auth list",
+    "gcloud auth login",
+    "gcloud compute disks list",
+    "gcloud compute firewall-rules list",
+    "gcloud compute instances create",
+    "gcloud compute instances delete",
+    "gcloud compute instances describe",
+    "gcloud compute instances list",
+    "gcloud compute instances ssh",
+    "gcloud compute instances start",
+    "gcloud compute instances stop",
+    "gcloud compute networks list",
+    "gcloud config list",
+    "gcloud config set",
+    "gcloud container clusters create",
+    "gcloud container clusters get-credentials",
+    "gcloud container clusters list",
+    "gcloud functions deploy",
+    "gcloud functions list",
+    "gcloud iam service-accounts list",
+    "gcloud projects describe",
+    "gcloud projects list",
+    "gcloud run deploy",
+    "gcloud run services list",
+    "gcloud sql instances describe",
+    "gcloud sql instances list",
+    "gcloud storage buckets list",
+    "gcloud storage cp",
+    "gcloud storage ls",
+    "bq ls",
+    "bq mk",
+    "bq query",
+    "bq rm",
+    "bq show",
+    "gsutil cp",
+    "gsutil ls",
+    "gsutil mb",
+    "gsutil rm",
+];
+
+const AWS_COMMANDS: &[&str] = &[
+    "aws cloudformation describe-stacks",
+    "aws cloudformation list-stacks",
+    "aws configure list",
+    "aws ec2 describe-instances",
+    "aws ec2 describe-security-groups",
+    "aws ec2 describe-vpcs",
+    "aws ec2 run-instances",
+    "aws ec2 start-instances",
+    "aws ec2 stop-instances",
+    "aws ec2 terminate-instances",
+    "aws eks list-clusters",
+    "aws eks update-kubeconfig",
+    "aws iam get-user",
+    "aws iam list-roles",
+    "aws iam list-users",
+    "aws lambda invoke",
+    "aws lambda list-functions",
+    "aws rds describe-db-instances",
+    "aws s3 cp",
+    "aws s3 ls",
+    "aws s3 mb",
+    "aws s3 rb",
+    "aws s3 rm",
+    "aws s3 sync",
+    "aws sts get-caller-identity",
+];
+
+const AZURE_COMMANDS: &[&str] = &[
+    "az account list",
+    "az account set",
+    "az account show",
+    "az aks get-credentials",
+    "az aks list",
+    "az group create",
+    "az group delete",
+    "az group list",
+    "az login",
+    "az network vnet list",
+    "az storage account list",
+    "az vm create",
+    "az vm delete",
+    "az vm list",
+    "az vm show",
+    "az vm start",
+    "az vm stop",
+    "az webapp list",
+];
+
+/// Completes the words of the known commands, e.g. `gcloud comp` to `gcloud compute`.
+pub struct CommandCompleter {
+    commands: Vec<Vec<String>>,
+}
+
+impl CommandCompleter {
+
+    /// Create completer for the goal commands, custom goal completes only the help prefixes.
+    /// Disabled completer doesn't return candidates.
+    pub fn new(goal: &Goal, enabled: bool) -> Self {
+        let commands: Vec<String> = if !enabled {
+            vec![]
+        } else {
+            match goal {
+                Goal::Gcp => GCP_COMMANDS.iter().map(|c| c.to_string()).collect(),
+                Goal::Aws => AWS_COMMANDS.iter().map(|c| c.to_string()).collect(),
+                Goal::Azure => AZURE_COMMANDS.iter().map(|c| c.to_string()).collect(),
+                Goal::Custom { help_prefixes, .. } => help_prefixes.clone(),
+            }
+        };
+
+        CommandCompleter {
+            commands: commands.iter().map(|c| c.split_whitespace().map(str::to_owned).collect()).collect(),
+        }
+    }
+
+    /// Return start of the word under the cursor and the candidates to replace it.
+    /// Commands may follow free text, completion starts from the last command name.
+    fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+        let partial = &line[start..];
+
+        let words: Vec<&str> = line[..start].split_whitespace().collect();
+        let typed = words.iter()
+            .rposition(|word| self.commands.iter().any(|command| command[0] == *word))
+            .map(|i| &words[i..])
+            .unwrap_or(&[]);
+
+        let mut candidates: Vec<String> = vec![];
+        for command in &self.commands {
+            if command.len() > typed.len() && command.iter().zip(typed).all(|(a, b)| a == b) {
+                let next = &command[typed.len()];
+                if next.starts_with(partial) && !candidates.contains(next) {
+                    candidates.push(next.clone());
+                }
+            }
+        }
+
+        (start, candidates)
+    }
+}
+
+impl Completer for CommandCompleter {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        let completer = CommandCompleter::new(&Goal::Gcp, true);
+
+        assert_eq!(completer.candidates("gcloud comp", 11), (7, vec!["compute".to_owned()]));
+        assert_eq!(completer.candidates("list vms with gcloud compute instances l", 40), (39, vec!["list".to_owned()]));
+        assert_eq!(completer.candidates("gs", 2), (0, vec!["gsutil".to_owned()]));
+        assert_eq!(completer.candidates("bq ", 3).1, vec!["ls", "mk", "query", "rm", "show"]);
+        assert_eq!(completer.candidates("gcloud xyz ", 11).1, Vec::<String>::new());
+
+        let completer = CommandCompleter::new(&Goal::Aws, true);
+        assert_eq!(completer.candidates("aws s3 s", 8), (7, vec!["sync".to_owned()]));
+
+        let completer = CommandCompleter::new(&Goal::Aws, false);
+        assert_eq!(completer.candidates("aws s3 s", 8), (7, vec![]));
+    }
+}
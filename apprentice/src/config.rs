@@ -1,9 +1,12 @@
-use apprentice_lib::Config as ModelParams;
+use std::fmt::Display;
+use std::path::PathBuf;
+use dirs::home_dir;
+use apprentice_lib::{AuthMode, Config as ModelParams, ModelProvider};
 
-use crate::{error::AppError, options::Options, util::api_url_for_provider};
+use crate::{error::AppError, options::Options, tools::DEFAULT_HELP_SUFFIX, util::{api_url_for_provider, aws_credentials_from_env}};
 
 /// Goal the agent will pursue
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum Goal {
     /// Google cloud platform
     Gcp,
@@ -11,21 +14,66 @@ pub enum Goal {
     Aws,
     /// Microsoft Azure
     Azure,
+    /// Any other command line tools
+    Custom {
+        /// CLI tools the agent works with, e.g. "Kubernetes CLI kubectl"
+        description: String,
+        /// Commands the HELP tool may request help pages for, e.g. "kubectl"
+        help_prefixes: Vec<String>,
+    },
 }
 
 impl TryFrom<&str> for Goal {
     type Error = AppError;
 
+    /// Custom goal is specified as `custom` or `custom:<description>`.
     fn try_from(val: &str) -> Result<Self, Self::Error> {
         match val {
             "gcp" => Ok(Goal::Gcp),
             "aws" => Ok(Goal::Aws),
             "azure" => Ok(Goal::Azure),
-            _ => Err(AppError::ConfigParseError("unknown goal")),
+            "custom" => Ok(Goal::Custom { description: String::new(), help_prefixes: vec![] }),
+            _ => match val.strip_prefix("custom:") {
+                Some(description) => Ok(Goal::Custom { description: description.trim().to_owned(), help_prefixes: vec![] }),
+                None => Err(AppError::ConfigParseError("unknown goal")),
+            }
         }
     }
 }
 
+impl Goal {
+    /// Commands the HELP tool may request help pages for, paired with the suffix that requests the help page.
+    pub fn help_commands(&self) -> Vec<(String, String)> {
+        let commands: Vec<(&str, &str)> = match self {
+            Goal::Gcp => vec![("gcloud", DEFAULT_HELP_SUFFIX), ("bq", DEFAULT_HELP_SUFFIX), ("gsutil", DEFAULT_HELP_SUFFIX)],
+            Goal::Aws => vec![("aws", " help")],
+            Goal::Azure => vec![("az", DEFAULT_HELP_SUFFIX)],
+            Goal::Custom { help_prefixes, .. } => help_prefixes.iter().map(|prefix| (prefix.as_str(), DEFAULT_HELP_SUFFIX)).collect(),
+        };
+
+        commands.into_iter().map(|(prefix, suffix)| (prefix.to_owned(), suffix.to_owned())).collect()
+    }
+}
+
+impl Display for Goal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let goal = match self {
+            Goal::Gcp => "gcp",
+            Goal::Aws => "aws",
+            Goal::Azure => "azure",
+            Goal::Custom { description, .. } if description.is_empty() => "custom",
+            Goal::Custom { description, .. } => return write!(f, "custom:{}", description),
+        };
+        f.write_str(goal)
+    }
+}
+
+/// Default maximum number of input history entries.
+const DEFAULT_HISTORY_SIZE: usize = 1000;
+
+/// Default maximum number of bytes of shell command stdout and stderr each sent to the model.
+const DEFAULT_MAX_TOOL_OUTPUT_BYTES: usize = 16 * 1024;
+
 /// Application settings.
 #[derive(Clone, Debug)]
 pub struct Settings {
@@ -35,6 +83,26 @@ pub struct Settings {
     pub apprentice_color: (Option<[u8;3]>, Option<[u8;3]>),
     /// Tool stdout and stderr output color.
     pub tool_color: (Option<[u8;3]>, Option<[u8;3]>),
+    /// Do not use colors and styles in the terminal output.
+    pub no_color: bool,
+    /// Use colors and styles even if the output is not a terminal.
+    pub force_color: bool,
+    /// Logo color.
+    pub logo_color: (Option<[u8;3]>, Option<[u8;3]>),
+    /// Help text color.
+    pub help_color: (Option<[u8;3]>, Option<[u8;3]>),
+    /// Shell command prefixes that are executed without confirmation.
+    pub auto_approve: Vec<String>,
+    /// File to keep the input history in, history is not persisted if not set.
+    pub history_file: Option<PathBuf>,
+    /// Maximum number of input history entries.
+    pub history_size: usize,
+    /// Maximum number of bytes of shell command stdout and stderr each sent to the model.
+    pub max_tool_output_bytes: usize,
+    /// Mask secrets in shell command output before sending it to the model.
+    pub redact_secrets: bool,
+    /// Regular expressions of secrets to mask in addition to the built-in ones.
+    pub redact_patterns: Vec<String>,
 }
 
 /// App config
@@ -50,6 +118,161 @@ pub struct Config {
     pub settings: Settings,
     /// Custom instructions to add to system prompt.
     pub prompt: Option<String>,
+    /// Template that replaces the built-in instructions of the system prompt.
+    pub prompt_template: Option<String>,
+    /// Do not add the OS, current directory, and shell to the system prompt.
+    pub minimal_prompt: bool,
+    /// Do not execute shell commands, only show them.
+    pub dry_run: bool,
+    /// Allow READ_FILE tool to read files outside of the current directory.
+    pub allow_any_path: bool,
+    /// Print LLM API requests and responses to stderr.
+    pub verbose: bool,
+    /// File to save the conversation to on exit.
+    pub save: Option<String>,
+    /// File to restore the conversation from.
+    pub resume: Option<String>,
+    /// Print messages as JSON lines.
+    pub json: bool,
+    /// Shell command timeout in seconds.
+    pub command_timeout: Option<u64>,
+    /// Print token usage after each response.
+    pub show_usage: bool,
+    /// Maximum number of model turns in a row without user input.
+    pub max_turns: Option<u32>,
+    /// Print the resolved configuration and exit.
+    pub print_config: bool,
+}
+
+fn format_opt<T: Display>(val: &Option<T>) -> String {
+    val.as_ref().map(|v| v.to_string()).unwrap_or_else(|| "-".to_owned())
+}
+
+fn format_color(color: &(Option<[u8;3]>, Option<[u8;3]>)) -> String {
+    let parts: Vec<String> = [("fg", color.0), ("bg", color.1)].iter()
+        .filter_map(|(name, rgb)| rgb.map(|[r, g, b]| format!("{}({},{},{})", name, r, g, b)))
+        .collect();
+    if parts.is_empty() { "-".to_owned() } else { parts.join(";") }
+}
+
+// Expand the leading `~/` to the home directory, as the shell does.
+fn expand_home(path: String) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+impl Config {
+    /// Check the provider-specific requirements, so the errors are reported before any LLM call.
+    pub fn validate(&self) -> Result<(), AppError> {
+        let mp = &self.model_params;
+
+        match mp.provider {
+            ModelProvider::Anthropic => {
+                if mp.api_version.is_none() {
+                    return Err(AppError::InvalidArgError("api-version is mandatory for anthropic, e.g. --api-version=2023-06-01"));
+                }
+                if mp.max_tokens.is_none() {
+                    return Err(AppError::InvalidArgError("max-tokens is mandatory for anthropic, e.g. --max-tokens=4096"));
+                }
+            },
+            ModelProvider::GCP => {
+                if !mp.api_url.contains(&mp.name) {
+                    return Err(AppError::InvalidArgError("api-url must contain the model name for gcp, e.g. .../v1beta/models/<model>:generateContent"));
+                }
+            },
+            ModelProvider::Bedrock => {
+                if mp.max_tokens.is_none() {
+                    return Err(AppError::InvalidArgError("max-tokens is mandatory for bedrock, e.g. --max-tokens=4096"));
+                }
+                if mp.aws_credentials.is_none() {
+                    return Err(AppError::InvalidArgError("AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY environment variables are mandatory for bedrock"));
+                }
+            },
+            ModelProvider::OpenAI | ModelProvider::DeepSeek | ModelProvider::Groq | ModelProvider::OpenRouter | ModelProvider::Cohere | ModelProvider::Echo => {},
+        }
+
+        // Gateways may serve any model, so the model name is checked with the provider's own API only.
+        // Groq and OpenRouter host third-party models, e.g. `deepseek-r1-distill-llama-70b`.
+        let hosts_other_models = matches!(mp.provider, ModelProvider::Groq | ModelProvider::OpenRouter);
+        if !hosts_other_models && mp.api_url == api_url_for_provider(mp.provider, &mp.name, None) {
+            let name = mp.name.as_str();
+            let expected = if name.starts_with("gpt-") {
+                Some((ModelProvider::OpenAI, "model looks like an OpenAI model, use --model-provider=openai"))
+            } else if name.starts_with("claude-") {
+                Some((ModelProvider::Anthropic, "model looks like an Anthropic model, use --model-provider=anthropic"))
+            } else if name.starts_with("gemini-") {
+                Some((ModelProvider::GCP, "model looks like a Gemini model, use --model-provider=gcp"))
+            } else if name.starts_with("deepseek-") {
+                Some((ModelProvider::DeepSeek, "model looks like a DeepSeek model, use --model-provider=deepseek"))
+            } else {
+                None
+            };
+
+            if let Some((provider, err)) = expected {
+                if provider != mp.provider {
+                    return Err(AppError::InvalidArgError(err));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resolved configuration as key/value pairs, API key is redacted.
+    pub fn resolved(&self) -> Vec<(&'static str, String)> {
+        let mp = &self.model_params;
+        let st = &self.settings;
+        vec![
+            ("goal", self.goal.to_string()),
+            ("help_prefixes", self.goal.help_commands().into_iter().map(|(prefix, _)| prefix).collect::<Vec<_>>().join(", ")),
+            ("model_provider", mp.provider.to_string()),
+            ("model", mp.name.clone()),
+            ("api_key", if mp.api_key.is_empty() { "-".to_owned() } else { "<redacted>".to_owned() }),
+            ("auth_mode", match mp.auth_mode { AuthMode::ApiKey => "api_key", AuthMode::Bearer => "bearer" }.to_owned()),
+            ("api_url", mp.api_url.clone()),
+            ("api_version", format_opt(&mp.api_version)),
+            ("max_tokens", format_opt(&mp.max_tokens)),
+            ("n", format_opt(&mp.n)),
+            ("temperature", format_opt(&mp.temperature)),
+            ("top_p", format_opt(&mp.top_p)),
+            ("top_k", format_opt(&mp.top_k)),
+            ("frequency_penalty", format_opt(&mp.frequency_penalty)),
+            ("presence_penalty", format_opt(&mp.presence_penalty)),
+            ("stop_sequences", format_opt(&mp.stop_sequences.as_ref().map(|seqs| seqs.join(", ")))),
+            ("reasoning_effort", format_opt(&mp.reasoning_effort)),
+            ("thinking_budget", format_opt(&mp.thinking_budget)),
+            ("prompt_caching", mp.prompt_caching.to_string()),
+            ("safety_settings", mp.safety_settings.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(", ")),
+            ("prompt", format_opt(&self.prompt)),
+            ("prompt_template", format_opt(&self.prompt_template)),
+            ("minimal_prompt", self.minimal_prompt.to_string()),
+            ("message", format_opt(&self.message)),
+            ("dry_run", self.dry_run.to_string()),
+            ("allow_any_path", self.allow_any_path.to_string()),
+            ("verbose", self.verbose.to_string()),
+            ("save", format_opt(&self.save)),
+            ("resume", format_opt(&self.resume)),
+            ("json", self.json.to_string()),
+            ("command_timeout", format_opt(&self.command_timeout)),
+            ("show_usage", self.show_usage.to_string()),
+            ("max_turns", format_opt(&self.max_turns)),
+            ("user_color", format_color(&st.user_color)),
+            ("apprentice_color", format_color(&st.apprentice_color)),
+            ("tool_color", format_color(&st.tool_color)),
+            ("logo_color", format_color(&st.logo_color)),
+            ("help_color", format_color(&st.help_color)),
+            ("no_color", st.no_color.to_string()),
+            ("force_color", st.force_color.to_string()),
+            ("auto_approve", st.auto_approve.join(", ")),
+            ("history_file", format_opt(&st.history_file.as_ref().map(|path| path.display()))),
+            ("history_size", st.history_size.to_string()),
+            ("max_tool_output_bytes", st.max_tool_output_bytes.to_string()),
+            ("redact_secrets", st.redact_secrets.to_string()),
+            ("redact_patterns", st.redact_patterns.join(", ")),
+        ]
+    }
 }
 
 impl TryFrom<Options> for Config {
@@ -58,12 +281,19 @@ impl TryFrom<Options> for Config {
     fn try_from(options: Options) -> Result<Self, AppError> {
         let model = options.model.unwrap();
         let provider = options.model_provider.unwrap().as_str().try_into()?;
-        let default_url = api_url_for_provider(provider, &model);
+
+        // Bedrock is authenticated with the AWS credentials, and the URL depends on the region.
+        let aws_credentials = if provider == ModelProvider::Bedrock { aws_credentials_from_env(|name| std::env::var(name).ok()) } else { None };
+        let api_base = options.api_base.or_else(|| aws_credentials.as_ref()
+            .map(|credentials| format!("https://bedrock-runtime.{}.amazonaws.com", credentials.region)));
+
+        let default_url = api_url_for_provider(provider, &model, api_base.as_deref());
 
         let model_params = ModelParams {
             provider,
             name: model.clone(),
-            api_key: options.api_key.unwrap(),
+            api_key: options.api_key.unwrap_or_default(),
+            auth_mode: options.auth_mode.as_deref().map(AuthMode::try_from).transpose()?.unwrap_or(AuthMode::ApiKey),
             api_url: options.api_url.unwrap_or(default_url),
             api_version: options.api_version,
             max_tokens: options.max_tokens,
@@ -73,22 +303,69 @@ impl TryFrom<Options> for Config {
             top_k: options.top_k,
             frequency_penalty: options.frequency_penalty,
             presence_penalty: options.presence_penalty,
-            stop_sequence: options.stop_sequence,
+            stop_sequences: options.stop_sequences,
+            reasoning_effort: options.reasoning_effort,
+            thinking_budget: options.thinking_budget,
+            prompt_caching: options.prompt_caching,
+            safety_settings: options.safety_settings,
+            logprobs: None,
+            aws_credentials,
+            extra_headers: vec![],
+            omit_params: vec![],
+            non_streaming_max_tokens: None,
         };
 
         let settings = Settings {
             user_color: options.user_color,
             apprentice_color: options.apprentice_color,
             tool_color: options.tool_color,
+            no_color: options.no_color,
+            force_color: options.force_color,
+            logo_color: options.logo_color,
+            help_color: options.help_color,
+            auto_approve: options.auto_approve,
+            history_file: if options.no_history {
+                None
+            } else {
+                options.history_file.map(expand_home).or_else(|| home_dir().map(|dir| dir.join(".apprentice_history")))
+            },
+            history_size: options.history_size.unwrap_or(DEFAULT_HISTORY_SIZE),
+            max_tool_output_bytes: options.max_tool_output.unwrap_or(DEFAULT_MAX_TOOL_OUTPUT_BYTES),
+            redact_secrets: options.redact_secrets,
+            redact_patterns: options.redact_patterns,
         };
 
-        Ok(Config {
-            goal: options.goal.unwrap().as_str().try_into()?,
+        let mut goal = options.goal.unwrap().as_str().try_into()?;
+        if let Goal::Custom { help_prefixes, .. } = &mut goal {
+            *help_prefixes = options.help_prefixes;
+        }
+
+        let config = Config {
+            goal,
             model_params,
             message: options.message,
             settings,
             prompt: options.prompt,
-        })
+            prompt_template: options.prompt_template,
+            minimal_prompt: options.minimal_prompt,
+            dry_run: options.dry_run,
+            allow_any_path: options.allow_any_path,
+            verbose: options.verbose,
+            save: options.save,
+            resume: options.resume,
+            json: options.json,
+            command_timeout: options.command_timeout,
+            show_usage: options.show_usage,
+            max_turns: options.max_turns,
+            print_config: options.print_config,
+        };
+
+        // Invalid configuration is still printed, the error is reported after it.
+        if !config.print_config {
+            config.validate()?;
+        }
+
+        Ok(config)
     }
 }
 
@@ -98,6 +375,43 @@ mod test {
 
     use super::*;
 
+    #[test]
+    fn test_validate() {
+        let mut options = Options::new();
+        options.goal = Some("gcp".into());
+        options.model_provider = Some("anthropic".into());
+        options.model = Some("claude-3-5-sonnet-20241022".into());
+        options.api_key = Some("apk".into());
+        options.max_tokens = Some(1024);
+
+        let mut printed = options.clone();
+        printed.print_config = true;
+        let config = Config::try_from(printed).expect("validation is deferred when printing the config");
+        assert!(config.validate().is_err());
+
+        let err = Config::try_from(options.clone()).expect_err("missing api version");
+        assert!(matches!(err, AppError::InvalidArgError(msg) if msg.starts_with("api-version")));
+
+        options.api_version = Some("2023-06-01".into());
+        assert!(Config::try_from(options.clone()).is_ok());
+
+        options.model_provider = Some("gcp".into());
+        let err = Config::try_from(options.clone()).expect_err("model of other provider");
+        assert!(matches!(err, AppError::InvalidArgError(msg) if msg.contains("--model-provider=anthropic")));
+
+        options.model = Some("gemini-2.0-flash".into());
+        options.api_url = Some("https://generativelanguage.googleapis.com/v1beta/models/gemini-1.5-pro:generateContent".into());
+        let err = Config::try_from(options.clone()).expect_err("url without model");
+        assert!(matches!(err, AppError::InvalidArgError(msg) if msg.starts_with("api-url")));
+
+        options.api_url = None;
+        assert!(Config::try_from(options.clone()).is_ok());
+
+        options.model_provider = Some("groq".into());
+        options.model = Some("deepseek-r1-distill-llama-70b".into());
+        assert!(Config::try_from(options.clone()).is_ok());
+    }
+
     #[test]
     fn test_config_try_from() {
         let mut options = Options {
@@ -105,7 +419,9 @@ mod test {
             model_provider: Some("anthropic".into()),
             model: Some("mdl".into()),
             api_key: Some("apk".into()),
+            auth_mode: Some("bearer".into()),
             api_url: Some("apr".into()),
+            api_base: None,
             api_version: Some("apv".into()),
             max_tokens: Some(1024),
             n: Some(34),
@@ -114,12 +430,40 @@ mod test {
             top_k: Some(7),
             frequency_penalty: Some(0.222),
             presence_penalty: Some(0.111),
-            stop_sequence: Some("ssq".into()),
+            stop_sequences: Some(vec!["ssq".into()]),
+            reasoning_effort: Some("low".into()),
+            thinking_budget: Some(2048),
+            prompt_caching: true,
+            safety_settings: vec![("HARM_CATEGORY_HARASSMENT".into(), "BLOCK_NONE".into())],
             message: Some("msg".into()),
             user_color: (Some([255,0,123]), Some([0,123,255])),
             apprentice_color: (Some([255,0,124]), Some([0,124,255])),
             tool_color: (Some([255,0,125]), Some([0,125,255])),
+            no_color: true,
+            force_color: true,
+            logo_color: (Some([255,0,126]), None),
+            help_color: (None, Some([0,127,255])),
             prompt: Some("prm".into()),
+            prompt_template: Some("tpl".into()),
+            minimal_prompt: true,
+            dry_run: true,
+            auto_approve: vec!["bq show".into()],
+            history_file: Some("hst".into()),
+            history_size: Some(50),
+            no_history: false,
+            allow_any_path: true,
+            verbose: true,
+            save: Some("sav".into()),
+            resume: Some("res".into()),
+            json: true,
+            command_timeout: Some(30),
+            max_tool_output: Some(4096),
+            redact_secrets: true,
+            redact_patterns: vec!["token=(\\S+)".into()],
+            show_usage: true,
+            max_turns: Some(10),
+            print_config: true,
+            help_prefixes: vec!["kubectl".into()],
         };
 
         let config = Config::try_from(options.clone()).expect("create from options");
@@ -127,9 +471,30 @@ mod test {
         assert!(matches!(config.goal, Goal::Aws));
         assert_eq!(config.message, Some("msg".into()));
         assert_eq!(config.prompt, Some("prm".into()));
+        assert_eq!(config.prompt_template, Some("tpl".into()));
+        assert!(config.minimal_prompt);
+        assert!(config.dry_run);
+        assert!(config.allow_any_path);
+        assert!(config.verbose);
+        assert_eq!(config.save, Some("sav".into()));
+        assert_eq!(config.resume, Some("res".into()));
+        assert!(config.json);
+        assert_eq!(config.command_timeout, Some(30));
+        assert!(config.show_usage);
+        assert_eq!(config.max_turns, Some(10));
+        assert!(config.print_config);
+
+        let resolved = config.resolved();
+        assert!(resolved.contains(&("api_key", "<redacted>".to_owned())));
+        assert!(resolved.contains(&("model_provider", "anthropic".to_owned())));
+        assert!(resolved.contains(&("max_turns", "10".to_owned())));
+        assert!(resolved.contains(&("tool_color", "fg(255,0,125);bg(0,125,255)".to_owned())));
+        assert_eq!(config.goal.to_string(), "aws");
+        assert_eq!(config.goal.help_commands(), vec![("aws".to_owned(), " help".to_owned())]);
         assert!(matches!(config.model_params.provider, ModelProvider::Anthropic));
         assert_eq!(config.model_params.name, "mdl".to_owned());
         assert_eq!(config.model_params.api_key, "apk".to_owned());
+        assert_eq!(config.model_params.auth_mode, AuthMode::Bearer);
         assert_eq!(config.model_params.api_url, "apr".to_owned());
         assert_eq!(config.model_params.api_version, Some("apv".into()));
         assert_eq!(config.model_params.max_tokens, Some(1024));
@@ -139,11 +504,41 @@ mod test {
         assert_eq!(config.model_params.top_k, Some(7));
         assert_eq!(config.model_params.frequency_penalty, Some(0.222));
         assert_eq!(config.model_params.presence_penalty, Some(0.111));
-        assert_eq!(config.model_params.stop_sequence, Some("ssq".into()));
+        assert_eq!(config.model_params.stop_sequences, Some(vec!["ssq".into()]));
+        assert_eq!(config.model_params.reasoning_effort, Some("low".into()));
+        assert_eq!(config.model_params.thinking_budget, Some(2048));
+        assert!(config.model_params.prompt_caching);
+        assert_eq!(config.model_params.safety_settings, vec![("HARM_CATEGORY_HARASSMENT".to_owned(), "BLOCK_NONE".to_owned())]);
 
         assert_eq!(config.settings.user_color, (Some([255,0,123]), Some([0,123,255])));
         assert_eq!(config.settings.apprentice_color, (Some([255,0,124]), Some([0,124,255])));
         assert_eq!(config.settings.tool_color, (Some([255,0,125]), Some([0,125,255])));
+        assert!(config.settings.no_color);
+        assert!(config.settings.force_color);
+        assert_eq!(config.settings.logo_color, (Some([255,0,126]), None));
+        assert_eq!(config.settings.help_color, (None, Some([0,127,255])));
+        assert_eq!(config.settings.auto_approve, vec!["bq show".to_owned()]);
+        assert_eq!(config.settings.history_file, Some(PathBuf::from("hst")));
+        assert_eq!(config.settings.history_size, 50);
+        assert_eq!(config.settings.max_tool_output_bytes, 4096);
+        assert!(config.settings.redact_secrets);
+        assert_eq!(config.settings.redact_patterns, vec!["token=(\\S+)".to_owned()]);
+
+        if let Some(home) = home_dir() {
+            let mut options = options.clone();
+            options.history_file = Some("~/.apprentice_history".into());
+            let config = Config::try_from(options).expect("create from options");
+            assert_eq!(config.settings.history_file, Some(home.join(".apprentice_history")));
+        }
+
+        options.no_history = true;
+        options.history_size = None;
+        options.max_tool_output = None;
+
+        let config = Config::try_from(options.clone()).expect("create from options");
+        assert_eq!(config.settings.history_file, None);
+        assert_eq!(config.settings.history_size, DEFAULT_HISTORY_SIZE);
+        assert_eq!(config.settings.max_tool_output_bytes, DEFAULT_MAX_TOOL_OUTPUT_BYTES);
 
         options.api_url = None;
 
@@ -159,5 +554,38 @@ mod test {
 
         let config = Config::try_from(options.clone()).expect("create from options");
         assert_eq!(config.model_params.api_url, "https://api.openai.com/v1/chat/completions");
+
+        options.api_base = Some("https://gateway.example.com/".into());
+
+        let config = Config::try_from(options.clone()).expect("create from options");
+        assert_eq!(config.model_params.api_url, "https://gateway.example.com/v1/chat/completions");
+
+        options.model_provider = Some("gcp".into());
+
+        let config = Config::try_from(options.clone()).expect("create from options");
+        assert_eq!(config.model_params.api_url, "https://gateway.example.com/v1beta/models/mdl:generateContent");
+
+        options.api_url = Some("apr".into());
+        options.model_provider = Some("openai".into());
+
+        let config = Config::try_from(options.clone()).expect("create from options");
+        assert_eq!(config.model_params.api_url, "apr");
+
+        options.api_url = None;
+        options.api_base = None;
+
+        options.goal = Some("custom: Kubernetes CLI kubectl".into());
+
+        let config = Config::try_from(options.clone()).expect("create from options");
+        if let Goal::Custom { description, help_prefixes } = &config.goal {
+            assert_eq!(description, "Kubernetes CLI kubectl");
+            assert_eq!(help_prefixes, &vec!["kubectl".to_owned()]);
+            assert_eq!(config.goal.help_commands(), vec![("kubectl".to_owned(), " --help".to_owned())]);
+        } else {
+            panic!("type mismatch");
+        }
+        assert_eq!(config.goal.to_string(), "custom:Kubernetes CLI kubectl");
+        assert!(matches!(Goal::try_from("custom"), Ok(Goal::Custom { description, .. }) if description.is_empty()));
+        assert!(Goal::try_from("gcpx").is_err());
     }
 }
\ No newline at end of file
@@ -43,4 +43,8 @@ pub enum AppError {
     /// General error.
     #[error("{0}")]
     Error(String),
+
+    /// User chose to abort the session, e.g. at the shell command confirmation.
+    #[error("Session aborted by the user")]
+    Aborted,
 }
\ No newline at end of file
@@ -1,8 +1,12 @@
+#![allow(clippy::type_complexity)]
+
 mod agent;
+mod completion;
 mod config;
 mod error;
 mod options;
 mod prompts;
+mod session;
 mod style;
 mod term;
 mod toml_parser;
@@ -12,15 +16,36 @@ mod tools;
 use agent::Agent;
 use error::AppError;
 use options::Options;
-use config::Config;
+use config::{Config, Goal};
 use prompts::Prompts;
+use session::Session;
 
 fn run_agent() -> Result<(), AppError> {
     let options = Options::load(std::env::args())?;
-    let config: Config = options.try_into()?;
-    let prompts = Prompts::new(&config.prompt, config.goal);
+    let mut config: Config = options.try_into()?;
+
+    if config.print_config {
+        for (key, value) in config.resolved() {
+            println!("{} = {}", key, value);
+        }
+        return config.validate();
+    }
+
+    let session = if let Some(path) = &config.resume {
+        let session = Session::load(path)?;
+        let mut goal: Goal = session.goal.as_str().try_into()?;
+        if let (Goal::Custom { help_prefixes, .. }, Goal::Custom { help_prefixes: configured, .. }) = (&mut goal, &config.goal) {
+            *help_prefixes = configured.clone();
+        }
+        config.goal = goal;
+        Some(session)
+    } else {
+        None
+    };
+
+    let prompts = Prompts::new(&config.prompt, &config.prompt_template, &config.goal, !config.minimal_prompt);
 
-    Agent::new(config, prompts)?.run()
+    Agent::new(config, prompts, session)?.run()
 }
 
 fn main() {
@@ -2,6 +2,7 @@
 
 use anstyle::Style;
 use clap::Arg;
+use clap::ArgAction;
 use clap::ArgMatches;
 use clap::Command;
 use std::ffi::OsString;
@@ -22,8 +23,12 @@ pub struct Options {
     pub model: Option<String>,
     /// API key.
     pub api_key: Option<String>,
+    /// How the API key is passed to the provider.
+    pub auth_mode: Option<String>,
     /// Model API URL.
     pub api_url: Option<String>,
+    /// Base URL the provider's API path is appended to, used when `api_url` is not set.
+    pub api_base: Option<String>,
     /// Model API version.
     pub api_version: Option<String>,
     /// Maximum number of tokens that will be generated.
@@ -40,8 +45,16 @@ pub struct Options {
     pub frequency_penalty: Option<f64>,
     /// Penalize new tokens based on whether they appear in the text so far.
     pub presence_penalty: Option<f64>,
-    /// Sequence at which model will stop generating.
-    pub stop_sequence: Option<String>,
+    /// Sequences at which model will stop generating.
+    pub stop_sequences: Option<Vec<String>>,
+    /// Reasoning effort for reasoning models.
+    pub reasoning_effort: Option<String>,
+    /// Maximum number of tokens the model can use for thinking.
+    pub thinking_budget: Option<i64>,
+    /// Mark the system prompt and tool definitions as cacheable.
+    pub prompt_caching: bool,
+    /// Safety filter thresholds by harm category.
+    pub safety_settings: Vec<(String, String)>,
     /// User message.
     pub message: Option<String>,
     /// User message color and prompt background.
@@ -50,8 +63,56 @@ pub struct Options {
     pub apprentice_color: (Option<[u8;3]>, Option<[u8;3]>),
     /// Apprentice message color and prompt background.
     pub tool_color: (Option<[u8;3]>, Option<[u8;3]>),
+    /// Do not use colors and styles in the terminal output.
+    pub no_color: bool,
+    /// Use colors and styles even if the output is not a terminal.
+    pub force_color: bool,
+    /// Logo text and background color.
+    pub logo_color: (Option<[u8;3]>, Option<[u8;3]>),
+    /// Help text and background color.
+    pub help_color: (Option<[u8;3]>, Option<[u8;3]>),
     /// Custom instructions to add to system prompt.
     pub prompt: Option<String>,
+    /// Template that replaces the built-in instructions of the system prompt.
+    pub prompt_template: Option<String>,
+    /// Do not add the OS, current directory, and shell to the system prompt.
+    pub minimal_prompt: bool,
+    /// Do not execute shell commands, only show them.
+    pub dry_run: bool,
+    /// Shell command prefixes that are executed without confirmation.
+    pub auto_approve: Vec<String>,
+    /// File to keep the input history in.
+    pub history_file: Option<String>,
+    /// Maximum number of input history entries.
+    pub history_size: Option<usize>,
+    /// Do not load or save the input history.
+    pub no_history: bool,
+    /// Allow READ_FILE tool to read files outside of the current directory.
+    pub allow_any_path: bool,
+    /// Print LLM API requests and responses to stderr.
+    pub verbose: bool,
+    /// File to save the conversation to on exit.
+    pub save: Option<String>,
+    /// File to restore the conversation from.
+    pub resume: Option<String>,
+    /// Print messages as JSON lines.
+    pub json: bool,
+    /// Shell command timeout in seconds.
+    pub command_timeout: Option<u64>,
+    /// Maximum number of bytes of shell command stdout and stderr each sent to the model.
+    pub max_tool_output: Option<usize>,
+    /// Mask secrets in shell command output before sending it to the model.
+    pub redact_secrets: bool,
+    /// Regular expressions of secrets to mask in addition to the built-in ones.
+    pub redact_patterns: Vec<String>,
+    /// Print token usage after each response.
+    pub show_usage: bool,
+    /// Maximum number of model turns in a row without user input.
+    pub max_turns: Option<u32>,
+    /// Print the resolved configuration and exit.
+    pub print_config: bool,
+    /// Commands the HELP tool may request help pages for with the custom goal.
+    pub help_prefixes: Vec<String>,
 }
 
 
@@ -90,7 +151,9 @@ impl Options {
             model_provider: None,
             model: None,
             api_key: None,
+            auth_mode: None,
             api_url: None,
+            api_base: None,
             api_version: None,
             max_tokens: None,
             n: None,
@@ -99,12 +162,40 @@ impl Options {
             top_k: None,
             frequency_penalty: None,
             presence_penalty: None,
-            stop_sequence: None,
+            stop_sequences: None,
+            reasoning_effort: None,
+            thinking_budget: None,
+            prompt_caching: false,
+            safety_settings: vec![],
             message: None,
             user_color: (None, None),
             apprentice_color: (None, None),
             tool_color: (None, None),
+            no_color: false,
+            force_color: false,
+            logo_color: (None, None),
+            help_color: (None, None),
             prompt: None,
+            prompt_template: None,
+            minimal_prompt: false,
+            dry_run: false,
+            auto_approve: vec![],
+            history_file: None,
+            history_size: None,
+            no_history: false,
+            allow_any_path: false,
+            verbose: false,
+            save: None,
+            resume: None,
+            json: false,
+            command_timeout: None,
+            max_tool_output: None,
+            redact_secrets: false,
+            redact_patterns: vec![],
+            show_usage: false,
+            max_turns: None,
+            print_config: false,
+            help_prefixes: vec![],
         }
     }
     
@@ -118,7 +209,7 @@ impl Options {
             .arg(
                 Arg::new("goal")
                 .long("goal")
-                .help("One of: gcp, aws, azure")
+                .help("One of: gcp, aws, azure, or custom:<description of the CLI tools>")
                 .short('g')
                 .env("APPRENTICE_GOAL")
                 .required(false)
@@ -132,7 +223,7 @@ impl Options {
             ).arg(
                 Arg::new("model-provider")
                 .long("model-provider")
-                .help("Model provider, one of: openai, anthropic, gcp, azure, custom")
+                .help("Model provider, one of: openai, anthropic, gcp, deepseek, groq, bedrock, cohere, openrouter, echo, azure, custom")
                 .short('p')
                 .env("APPRENTICE_MODEL_PROVIDER")
                 .required(false)
@@ -143,6 +234,12 @@ impl Options {
                 .short('k')
                 .env("APPRENTICE_API_KEY")
                 .required(false)
+            ).arg(
+                Arg::new("auth-mode")
+                .long("auth-mode")
+                .help("How the API key is passed: api_key, or bearer to send it as an OAuth access token (gcp only)")
+                .env("APPRENTICE_AUTH_MODE")
+                .required(false)
             ).arg(
                 Arg::new("api-url")
                 .long("api-url")
@@ -157,6 +254,12 @@ impl Options {
                 .short('c')
                 .env("APPRENTICE_CONFIG")
                 .required(false)
+            ).arg(
+                Arg::new("context")
+                .long("context")
+                .help("Config file context to use instead of the default_context")
+                .env("APPRENTICE_CONTEXT")
+                .required(false)
             ).arg(
                 Arg::new("message")
                 .long("message")
@@ -215,15 +318,57 @@ impl Options {
             ).arg(
                 Arg::new("stop-sequence")
                 .long("stop-sequence")
-                .help("Sequence at which model will stop generating")
+                .help("Sequence at which model will stop generating, can be repeated or comma-separated")
                 .env("APPRENTICE_STOP_SEQUENCE")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .required(false)
+            ).arg(
+                Arg::new("reasoning-effort")
+                .long("reasoning-effort")
+                .help("Reasoning effort for reasoning models, e.g. low, medium, high (openai only)")
+                .env("APPRENTICE_REASONING_EFFORT")
+                .required(false)
+            ).arg(
+                Arg::new("thinking-budget")
+                .long("thinking-budget")
+                .help("Maximum number of tokens the model can use for thinking (anthropic and gcp)")
+                .env("APPRENTICE_THINKING_BUDGET")
+                .required(false)
+            ).arg(
+                Arg::new("prompt-caching")
+                .long("prompt-caching")
+                .help("Mark the system prompt and tool definitions as cacheable (anthropic only)")
+                .env("APPRENTICE_PROMPT_CACHING")
+                .action(ArgAction::SetTrue)
+                .required(false)
+            ).arg(
+                Arg::new("safety-setting")
+                .long("safety-setting")
+                .help("Safety filter threshold for a harm category, can be repeated (gcp only, e.g. 'HARM_CATEGORY_DANGEROUS_CONTENT=BLOCK_ONLY_HIGH')")
+                .env("APPRENTICE_SAFETY_SETTINGS")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
                 .required(false)
             ).arg(
                 Arg::new("prompt")
                 .long("prompt")
-                .help("Custom instructions to use in the system prompt.")
+                .help("Custom instructions to use in the system prompt. Placeholders {goal}, {os}, and {cwd} are replaced with their values.")
                 .env("APPRENTICE_PROMPT")
                 .required(false)
+            ).arg(
+                Arg::new("prompt-template")
+                .long("prompt-template")
+                .help("Instructions that replace the built-in system prompt. Placeholders {cli}, {goal}, {os}, and {cwd} are replaced with their values.")
+                .env("APPRENTICE_PROMPT_TEMPLATE")
+                .required(false)
+            ).arg(
+                Arg::new("minimal-prompt")
+                .long("minimal-prompt")
+                .help("Do not add the OS, current directory, and shell to the system prompt")
+                .env("APPRENTICE_MINIMAL_PROMPT")
+                .action(ArgAction::SetTrue)
+                .required(false)
             ).arg(
                 Arg::new("apprentice-color")
                 .long("apprentice-color")
@@ -242,6 +387,152 @@ impl Options {
                 .help("Tool stdout and stderr and prompt background colors, rgb (e.g. 'fg(255,0,123);bg(0,123,255)').")
                 .env("APPRENTICE_TOOL_COLOR")
                 .required(false)
+            ).arg(
+                Arg::new("no-color")
+                .long("no-color")
+                .help("Do not use colors and styles in the terminal output, also enabled by the NO_COLOR environment variable")
+                .env("APPRENTICE_NO_COLOR")
+                .action(ArgAction::SetTrue)
+                .required(false)
+            ).arg(
+                Arg::new("force-color")
+                .long("force-color")
+                .help("Use colors and styles even if the output is not a terminal")
+                .env("APPRENTICE_FORCE_COLOR")
+                .action(ArgAction::SetTrue)
+                .required(false)
+            ).arg(
+                Arg::new("logo-color")
+                .long("logo-color")
+                .help("Logo text and background colors, rgb (e.g. 'fg(255,0,123);bg(0,123,255)').")
+                .env("APPRENTICE_LOGO_COLOR")
+                .required(false)
+            ).arg(
+                Arg::new("help-color")
+                .long("help-color")
+                .help("Help text and background colors, rgb (e.g. 'fg(255,0,123);bg(0,123,255)').")
+                .env("APPRENTICE_HELP_COLOR")
+                .required(false)
+            ).arg(
+                Arg::new("dry-run")
+                .long("dry-run")
+                .help("Do not execute shell commands, only show what would be executed")
+                .env("APPRENTICE_DRY_RUN")
+                .action(ArgAction::SetTrue)
+                .required(false)
+            ).arg(
+                Arg::new("auto-approve")
+                .long("auto-approve")
+                .help("Shell command prefix that is executed without confirmation, can be repeated (e.g. 'gcloud compute instances list')")
+                .env("APPRENTICE_AUTO_APPROVE")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .required(false)
+            ).arg(
+                Arg::new("help-prefix")
+                .long("help-prefix")
+                .help("Command the HELP tool may request help pages for with the custom goal, can be repeated (e.g. 'kubectl')")
+                .env("APPRENTICE_HELP_PREFIXES")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .required(false)
+            ).arg(
+                Arg::new("history-file")
+                .long("history-file")
+                .help("File to keep the input history in (default: ~/.apprentice_history)")
+                .env("APPRENTICE_HISTORY_FILE")
+                .required(false)
+            ).arg(
+                Arg::new("history-size")
+                .long("history-size")
+                .help("Maximum number of input history entries (default: 1000)")
+                .env("APPRENTICE_HISTORY_SIZE")
+                .required(false)
+            ).arg(
+                Arg::new("no-history")
+                .long("no-history")
+                .help("Do not load or save the input history")
+                .env("APPRENTICE_NO_HISTORY")
+                .action(ArgAction::SetTrue)
+                .required(false)
+            ).arg(
+                Arg::new("allow-any-path")
+                .long("allow-any-path")
+                .help("Allow the file-read tool to access files outside of the current directory")
+                .env("APPRENTICE_ALLOW_ANY_PATH")
+                .action(ArgAction::SetTrue)
+                .required(false)
+            ).arg(
+                Arg::new("verbose")
+                .long("verbose")
+                .help("Print LLM API request payloads and responses to stderr (API keys are redacted)")
+                .short('v')
+                .env("APPRENTICE_VERBOSE")
+                .action(ArgAction::SetTrue)
+                .required(false)
+            ).arg(
+                Arg::new("save")
+                .long("save")
+                .help("Save the conversation to the file on exit")
+                .env("APPRENTICE_SAVE")
+                .required(false)
+            ).arg(
+                Arg::new("resume")
+                .long("resume")
+                .help("Restore the conversation saved with --save")
+                .env("APPRENTICE_RESUME")
+                .required(false)
+            ).arg(
+                Arg::new("json")
+                .long("json")
+                .help("Print apprentice messages, tool calls, and tool results as JSON lines without styling")
+                .env("APPRENTICE_JSON")
+                .action(ArgAction::SetTrue)
+                .required(false)
+            ).arg(
+                Arg::new("show-usage")
+                .long("show-usage")
+                .help("Print the running token usage after each response")
+                .env("APPRENTICE_SHOW_USAGE")
+                .action(ArgAction::SetTrue)
+                .required(false)
+            ).arg(
+                Arg::new("command-timeout")
+                .long("command-timeout")
+                .help("Kill shell commands that run longer than the number of seconds")
+                .env("APPRENTICE_COMMAND_TIMEOUT")
+                .required(false)
+            ).arg(
+                Arg::new("max-tool-output")
+                .long("max-tool-output")
+                .help("Truncate shell command stdout and stderr sent to the model to the number of bytes each (default: 16384)")
+                .env("APPRENTICE_MAX_TOOL_OUTPUT")
+                .required(false)
+            ).arg(
+                Arg::new("redact-secrets")
+                .long("redact-secrets")
+                .help("Mask secrets like AWS keys, bearer tokens and passwords in shell command output before sending it to the model")
+                .env("APPRENTICE_REDACT_SECRETS")
+                .action(ArgAction::SetTrue)
+                .required(false)
+            ).arg(
+                Arg::new("redact-pattern")
+                .long("redact-pattern")
+                .help("Regular expression of a secret to mask with --redact-secrets, can be repeated; if it has a capture group, only the group is masked (e.g. 'client_secret=(\\S+)')")
+                .action(ArgAction::Append)
+                .required(false)
+            ).arg(
+                Arg::new("max-turns")
+                .long("max-turns")
+                .help("Ask for user input after the number of model turns in a row without it")
+                .env("APPRENTICE_MAX_TURNS")
+                .required(false)
+            ).arg(
+                Arg::new("print-config")
+                .long("print-config")
+                .help("Print the resolved configuration and exit")
+                .action(ArgAction::SetTrue)
+                .required(false)
             )
             .after_help(format!("{bold_underline}Example:{bold_underline:#} {bold}
 
@@ -279,7 +570,8 @@ The configuration options can be overridden with the command line arguments or e
         if options.model_provider.is_none() {
             return Err(AppError::MissingArgError("model provider is not specified."));
         }
-        if options.api_key.is_none() {
+        // Bedrock uses the AWS credentials, echo runs offline.
+        if options.api_key.is_none() && !matches!(options.model_provider.as_deref(), Some("bedrock" | "echo")) {
             return Err(AppError::MissingArgError("API key is not specified."));
         }
         if let Some(n) = options.n {
@@ -300,11 +592,14 @@ The configuration options can be overridden with the command line arguments or e
         let mut options = Options::new();
 
         let config_path = m.get_one("config").map(|s: &String| s.as_ref());
+        let context = m.get_one("context").map(|s: &String| s.as_str());
 
         if let Some(content) = Self::load_config_file(config_path)
             .map_err(|err| AppError::Error(format!("Error loading config file: {}", err)))?
         {
-            parse_toml_config(&content, &mut options)?;
+            parse_toml_config(&content, context, &mut options)?;
+        } else if let Some(context) = context {
+            return Err(AppError::Error(format!("Context \"{}\" is specified, but config file is not found.", context)));
         }
 
         if let Some(x) = m.get_one::<String>("goal") {
@@ -319,6 +614,9 @@ The configuration options can be overridden with the command line arguments or e
         if let Some(x) = m.get_one::<String>("api-key") {
             options.api_key.replace(x.clone());
         }
+        if let Some(x) = m.get_one::<String>("auth-mode") {
+            options.auth_mode.replace(x.clone());
+        }
         if let Some(x) = m.get_one::<String>("api-url") {
             options.api_url.replace(x.clone());
         }
@@ -350,24 +648,153 @@ The configuration options can be overridden with the command line arguments or e
             }
         }
 
+        if let Some(x) = m.get_one::<String>("thinking-budget") {
+            if let Ok(val) = x.parse::<i64>() {
+                if val <= 0 { return Err(AppError::InvalidArgError("thinking-budget must be greater than zero")) };
+                options.thinking_budget.replace(val);
+            } else {
+                return Err(AppError::InvalidArgError("thinking-budget must be integer"));
+            }
+        }
+
+        if let Some(x) = m.get_one::<String>("reasoning-effort") {
+            options.reasoning_effort.replace(x.clone());
+        }
+
+        if let Some(x) = m.get_one::<String>("command-timeout") {
+            if let Ok(val) = x.parse::<u64>() {
+                if val == 0 { return Err(AppError::InvalidArgError("command-timeout must be greater than zero")) };
+                options.command_timeout.replace(val);
+            } else {
+                return Err(AppError::InvalidArgError("command-timeout must be non-negative integer"));
+            }
+        }
+
+        if let Some(x) = m.get_one::<String>("max-tool-output") {
+            if let Ok(val) = x.parse::<usize>() {
+                if val == 0 { return Err(AppError::InvalidArgError("max-tool-output must be greater than zero")) };
+                options.max_tool_output.replace(val);
+            } else {
+                return Err(AppError::InvalidArgError("max-tool-output must be non-negative integer"));
+            }
+        }
+
+        if let Some(x) = m.get_one::<String>("max-turns") {
+            if let Ok(val) = x.parse::<u32>() {
+                if val == 0 { return Err(AppError::InvalidArgError("max-turns must be greater than zero")) };
+                options.max_turns.replace(val);
+            } else {
+                return Err(AppError::InvalidArgError("max-turns must be non-negative integer"));
+            }
+        }
+
         check_and_set_float_arg!("temperature", m, options.temperature);
         check_and_set_float_arg!("top-p", m, options.top_p);
         check_and_set_float_arg!("frequency-penalty", m, options.frequency_penalty);
         check_and_set_float_arg!("presence-penalty", m, options.presence_penalty);
 
-        if let Some(x) = m.get_one::<String>("stop-sequence") {
-            options.stop_sequence.replace(x.clone());
+        if let Some(x) = m.get_many::<String>("stop-sequence") {
+            options.stop_sequences.replace(x.cloned().collect());
         }
 
         if let Some(x) = m.get_one::<String>("prompt") {
             options.prompt.replace(x.clone());
         }
 
+        if let Some(x) = m.get_one::<String>("prompt-template") {
+            options.prompt_template.replace(x.clone());
+        }
+
         check_and_set_color_arg!("apprentice-color", m, options.apprentice_color);
         check_and_set_color_arg!("user-color", m, options.user_color);
         check_and_set_color_arg!("tool-color", m, options.tool_color);
+        check_and_set_color_arg!("logo-color", m, options.logo_color);
+        check_and_set_color_arg!("help-color", m, options.help_color);
 
         options.message = m.get_one::<String>("message").cloned();
+        options.save = m.get_one::<String>("save").cloned();
+        options.resume = m.get_one::<String>("resume").cloned();
+
+        if m.get_flag("prompt-caching") {
+            options.prompt_caching = true;
+        }
+
+        if m.get_flag("minimal-prompt") {
+            options.minimal_prompt = true;
+        }
+
+        if m.get_flag("dry-run") {
+            options.dry_run = true;
+        }
+
+        if m.get_flag("allow-any-path") {
+            options.allow_any_path = true;
+        }
+
+        if m.get_flag("verbose") {
+            options.verbose = true;
+        }
+
+        if m.get_flag("json") {
+            options.json = true;
+        }
+
+        if m.get_flag("show-usage") {
+            options.show_usage = true;
+        }
+
+        if m.get_flag("print-config") {
+            options.print_config = true;
+        }
+
+        if let Some(settings) = m.get_many::<String>("safety-setting") {
+            options.safety_settings = settings
+                .map(|s| s.split_once('=')
+                    .map(|(category, threshold)| (category.trim().to_owned(), threshold.trim().to_owned()))
+                    .ok_or(AppError::InvalidArgError("safety-setting must have format CATEGORY=THRESHOLD")))
+                .collect::<Result<_, _>>()?;
+        }
+
+        if let Some(prefixes) = m.get_many::<String>("auto-approve") {
+            options.auto_approve = prefixes.cloned().collect();
+        }
+
+        if let Some(x) = m.get_one::<String>("history-size") {
+            if let Ok(val) = x.parse::<usize>() {
+                if val == 0 { return Err(AppError::InvalidArgError("history-size must be greater than zero")) };
+                options.history_size.replace(val);
+            } else {
+                return Err(AppError::InvalidArgError("history-size must be non-negative integer"));
+            }
+        }
+
+        if let Some(x) = m.get_one::<String>("history-file") {
+            options.history_file.replace(x.clone());
+        }
+
+        if m.get_flag("no-color") {
+            options.no_color = true;
+        }
+
+        if m.get_flag("force-color") {
+            options.force_color = true;
+        }
+
+        if m.get_flag("no-history") {
+            options.no_history = true;
+        }
+
+        if m.get_flag("redact-secrets") {
+            options.redact_secrets = true;
+        }
+
+        if let Some(patterns) = m.get_many::<String>("redact-pattern") {
+            options.redact_patterns = patterns.cloned().collect();
+        }
+
+        if let Some(prefixes) = m.get_many::<String>("help-prefix") {
+            options.help_prefixes = prefixes.cloned().collect();
+        }
 
         Self::validate_mandatory_options(&options)?;
 
@@ -400,11 +827,42 @@ mod tests {
             OsString::from("--top-k=123"),
             OsString::from("--frequency-penalty=1.234"),
             OsString::from("--presence-penalty=2.345"),
-            OsString::from("--stop-sequence=<stop-sequence>"),
+            OsString::from("--stop-sequence=<stop-sequence>,<stop-sequence2>"),
+            OsString::from("--reasoning-effort=low"),
+            OsString::from("--thinking-budget=2048"),
+            OsString::from("--prompt-caching"),
+            OsString::from("--safety-setting=HARM_CATEGORY_HARASSMENT=BLOCK_NONE"),
             OsString::from("--prompt=<prompt>"),
+            OsString::from("--prompt-template=<prompt-template>"),
             OsString::from("--apprentice-color=fg(255,0,124);bg(0,124,255)"),
             OsString::from("--user-color='fg(255,0,125);bg(0,125,255)'"),
             OsString::from("--tool-color=\"fg(255,0,123);bg(0,123,255)\""),
+            OsString::from("--dry-run"),
+            OsString::from("--auto-approve=gcloud compute instances list"),
+            OsString::from("--auto-approve=bq show"),
+            OsString::from("--allow-any-path"),
+            OsString::from("-v"),
+            OsString::from("--save=<save>"),
+            OsString::from("--resume=<resume>"),
+            OsString::from("--json"),
+            OsString::from("--command-timeout=30"),
+            OsString::from("--max-tool-output=4096"),
+            OsString::from("--redact-secrets"),
+            OsString::from("--redact-pattern=client_secret=(\\S+)"),
+            OsString::from("--redact-pattern=ghp_[0-9a-zA-Z]{36,}"),
+            OsString::from("--auth-mode=bearer"),
+            OsString::from("--minimal-prompt"),
+            OsString::from("--help-prefix=kubectl,helm"),
+            OsString::from("--history-file=<history-file>"),
+            OsString::from("--history-size=50"),
+            OsString::from("--no-history"),
+            OsString::from("--show-usage"),
+            OsString::from("--max-turns=10"),
+            OsString::from("--print-config"),
+            OsString::from("--no-color"),
+            OsString::from("--force-color"),
+            OsString::from("--logo-color=fg(1,2,3)"),
+            OsString::from("--help-color=fg(4,5,6);bg(7,8,9)"),
         ];
 
         let options = Options::load(args.clone()).expect("load options");
@@ -413,6 +871,7 @@ mod tests {
         assert_eq!(options.model_provider, Some("<model-provider>".into()));
         assert_eq!(options.model, Some("<model>".into()));
         assert_eq!(options.api_key, Some("<api-key>".into()));
+        assert_eq!(options.auth_mode, Some("bearer".into()));
         assert_eq!(options.api_url, Some("<api-url>".into()));
         assert_eq!(options.api_version, Some("<api-version>".into()));
         assert_eq!(options.max_tokens, Some(789));
@@ -422,12 +881,40 @@ mod tests {
         assert_eq!(options.top_k, Some(123));
         assert_eq!(options.frequency_penalty, Some(1.234));
         assert_eq!(options.presence_penalty, Some(2.345));
-        assert_eq!(options.stop_sequence, Some("<stop-sequence>".into()));
+        assert_eq!(options.stop_sequences, Some(vec!["<stop-sequence>".into(), "<stop-sequence2>".into()]));
+        assert_eq!(options.reasoning_effort, Some("low".into()));
+        assert_eq!(options.thinking_budget, Some(2048));
+        assert!(options.prompt_caching);
+        assert_eq!(options.safety_settings, vec![("HARM_CATEGORY_HARASSMENT".to_owned(), "BLOCK_NONE".to_owned())]);
         assert_eq!(options.message, Some("<message>".into()));
         assert_eq!(options.apprentice_color, (Some([255,0,124]), Some([0,124,255])));
         assert_eq!(options.user_color, (Some([255,0,125]), Some([0,125,255])));
         assert_eq!(options.tool_color, (Some([255,0,123]), Some([0,123,255])));
         assert_eq!(options.prompt, Some("<prompt>".into()));
+        assert_eq!(options.prompt_template, Some("<prompt-template>".into()));
+        assert!(options.minimal_prompt);
+        assert!(options.dry_run);
+        assert!(options.allow_any_path);
+        assert!(options.verbose);
+        assert_eq!(options.save, Some("<save>".into()));
+        assert_eq!(options.resume, Some("<resume>".into()));
+        assert!(options.json);
+        assert_eq!(options.command_timeout, Some(30));
+        assert_eq!(options.max_tool_output, Some(4096));
+        assert!(options.redact_secrets);
+        assert_eq!(options.redact_patterns, vec!["client_secret=(\\S+)".to_owned(), "ghp_[0-9a-zA-Z]{36,}".to_owned()]);
+        assert_eq!(options.max_turns, Some(10));
+        assert!(options.print_config);
+        assert!(options.show_usage);
+        assert_eq!(options.auto_approve, vec!["gcloud compute instances list".to_owned(), "bq show".to_owned()]);
+        assert_eq!(options.help_prefixes, vec!["kubectl".to_owned(), "helm".to_owned()]);
+        assert_eq!(options.history_file, Some("<history-file>".into()));
+        assert_eq!(options.history_size, Some(50));
+        assert!(options.no_history);
+        assert!(options.no_color);
+        assert!(options.force_color);
+        assert_eq!(options.logo_color, (Some([1,2,3]), None));
+        assert_eq!(options.help_color, (Some([4,5,6]), Some([7,8,9])));
 
         let mut args2 = args.clone();
         args2.remove(1);
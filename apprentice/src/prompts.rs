@@ -1,8 +1,7 @@
 use crate::{config::Goal, error::AppError};
 
-const PROMPTS: [&str;3] = [
-"You are an assistant called \"Apprentice\" that helps translate a user request into a valid call to the ",
-".
+const PROMPTS: [&str;2] = [
+"You are an assistant called \"Apprentice\" that helps translate a user request into a valid call to the {cli}.
 You are in dialogue with the user. 
 After each response from the user, you think and ALWAYS do one of the following actions:
 1. Produce the resulting command (use the SHELL tool).
@@ -19,6 +18,11 @@ You reflect in the resulting command ALL that the user specifid in the request a
 Below is your actual dialogue with the user."
 ];
 
+const ENVIRONMENT_PROMPT: &str = "The user works in the following environment:
+- Operating system: {os}
+- Current directory: {cwd}
+- ";
+
 /// System prompts.
 pub struct Prompts {
     prompts: Vec<String>,
@@ -27,30 +31,86 @@ pub struct Prompts {
 impl Prompts {
 
     /// Create a new instance.
-    pub fn new(sys_add: &Option<String>, goal: Goal) -> Self {
-        let mut sys = PROMPTS[0].to_owned();
-
-        sys += match goal {
+    /// Placeholders `{goal}`, `{os}`, and `{cwd}` in the user instructions are replaced with their values.
+    /// If `template` is set, it replaces the built-in instructions, and is rendered with the same placeholders and `{cli}`.
+    /// If `environment` is set, the OS, current directory, and shell are added to the prompt.
+    pub fn new(sys_add: &Option<String>, template: &Option<String>, goal: &Goal, environment: bool) -> Self {
+        let cli = match goal {
             Goal::Gcp => "Google Cloud CLI tools gcloud, bq, gsutil",
             Goal::Aws => "AWS CLI aws",
             Goal::Azure => "Azure CLI az",
+            Goal::Custom { description, .. } if description.is_empty() => "command line tools available in the user's shell",
+            Goal::Custom { description, .. } => description,
         };
 
-        sys += PROMPTS[1];
+        let goal = goal.to_string();
+        let cwd = std::env::current_dir().map(|dir| dir.display().to_string()).unwrap_or_default();
+        let vars = [
+            ("cli", cli),
+            ("goal", goal.as_str()),
+            ("os", std::env::consts::OS),
+            ("cwd", cwd.as_str()),
+        ];
+
+
+        let mut sys = Self::render(template.as_deref().unwrap_or(PROMPTS[0]), &vars);
+
+        if environment {
+            sys += &Self::render(ENVIRONMENT_PROMPT, &vars);
+            sys += &Self::shell();
+            sys += "\n\n";
+        }
 
         if let Some(instr) = sys_add {
             sys += "In addition, consider using the following information from the user:\n-----\n";
-            sys += instr;
+            sys += &Self::render(instr, &vars);
             sys += "\n-----";
         }
 
-        sys += PROMPTS[2];
+        sys += PROMPTS[1];
 
         Prompts {
             prompts: vec![sys],
         }
     }
 
+    // Commands are run with `sh -c` or `cmd /C`, the user's login shell is mentioned for context.
+    fn shell() -> String {
+        let shell = if cfg!(target_os = "windows") { "cmd" } else { "sh" };
+        match std::env::var("SHELL") {
+            Ok(login_shell) if !login_shell.is_empty() => format!("Commands are executed by: {shell} (the user's login shell is {login_shell})"),
+            _ => format!("Commands are executed by: {shell}"),
+        }
+    }
+
+    /// Replace `{name}` tokens in the template with the values of the variables.
+    /// Unknown tokens are left intact.
+    pub fn render(template: &str, vars: &[(&str, &str)]) -> String {
+        let mut ret = String::with_capacity(template.len());
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            ret += &rest[..start];
+            rest = &rest[start..];
+
+            let value = rest.find('}').and_then(|end| {
+                let name = &rest[1..end];
+                vars.iter().find(|(var, _)| *var == name).map(|(_, value)| (*value, end))
+            });
+
+            if let Some((value, end)) = value {
+                ret += value;
+                rest = &rest[end + 1..];
+            } else {
+                ret.push('{');
+                rest = &rest[1..];
+            }
+        }
+
+        ret += rest;
+        ret
+    }
+
     /// Get prompt by id.
     pub fn get(&self, id: usize) -> Result<&str, AppError> {
         let len = if self.prompts.is_empty() {PROMPTS.len()} else {self.prompts.len()};
@@ -61,4 +121,46 @@ impl Prompts {
 
         Ok(&self.prompts[id])
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let vars = [("goal", "gcp"), ("os", "linux")];
+
+        assert_eq!(Prompts::render("Goal {goal} on {os}, {goal}.", &vars), "Goal gcp on linux, gcp.");
+        assert_eq!(Prompts::render("{unknown} {goal", &vars), "{unknown} {goal");
+        assert_eq!(Prompts::render("json: {\"a\": {goal}}", &vars), "json: {\"a\": gcp}");
+        assert_eq!(Prompts::render("", &vars), "");
+
+        let prompts = Prompts::new(&Some("Use {goal} on {os}.".into()), &None, &Goal::Aws, false);
+        let sys = prompts.get(0).expect("system prompt");
+        assert!(sys.contains("valid call to the AWS CLI aws."));
+        assert!(sys.contains(&format!("Use aws on {}.", std::env::consts::OS)));
+        assert!(!sys.contains("Operating system:"));
+
+        let prompts = Prompts::new(&Some("Be brief.".into()), &Some("You translate requests into {cli} commands.\n".into()), &Goal::Aws, false);
+        let sys = prompts.get(0).expect("system prompt");
+        assert!(sys.starts_with("You translate requests into AWS CLI aws commands.\n"));
+        assert!(!sys.contains("Apprentice"));
+        assert!(sys.contains("Be brief."));
+    }
+
+    #[test]
+    fn test_environment() {
+        let prompts = Prompts::new(&None, &None, &Goal::Gcp, true);
+        let sys = prompts.get(0).expect("system prompt");
+        let cwd = std::env::current_dir().expect("current dir");
+
+        assert!(sys.contains(&format!("- Operating system: {}\n", std::env::consts::OS)));
+        assert!(sys.contains(&format!("- Current directory: {}\n", cwd.display())));
+        assert!(sys.contains("- Commands are executed by: "));
+
+        let goal = Goal::Custom { description: "Kubernetes CLI kubectl".into(), help_prefixes: vec![] };
+        let prompts = Prompts::new(&None, &None, &goal, false);
+        assert!(prompts.get(0).expect("system prompt").contains("valid call to the Kubernetes CLI kubectl."));
+    }
+}
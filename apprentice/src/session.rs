@@ -0,0 +1,72 @@
+//! Saving and restoring conversations.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::error::AppError;
+
+/// Saved conversation.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Session {
+    /// Apprentice version that created the file.
+    pub version: String,
+    /// Agent goal.
+    pub goal: String,
+    /// Model provider.
+    pub provider: String,
+    /// Model name.
+    pub model: String,
+    /// Chat history in the provider-specific format.
+    pub history: Vec<Value>,
+}
+
+impl Session {
+
+    /// Load session from file.
+    pub fn load(path: &str) -> Result<Self, AppError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| AppError::Error(format!("Error loading session file {}: {}", path, err)))?;
+
+        serde_json::from_str(&content)
+            .map_err(|err| AppError::Error(format!("Error parsing session file {}: {}", path, err)))
+    }
+
+    /// Save session to file.
+    pub fn save(&self, path: &str) -> Result<(), AppError> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|err| AppError::Error(format!("Error serializing session: {}", err)))?;
+
+        std::fs::write(path, content)
+            .map_err(|err| AppError::Error(format!("Error saving session file {}: {}", path, err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_load() {
+        let path = std::env::temp_dir().join(format!("apprentice_session_test_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let session = Session {
+            version: "0.1.1".into(),
+            goal: "gcp".into(),
+            provider: "openai".into(),
+            model: "gpt-4".into(),
+            history: vec![serde_json::json!({"role": "user", "content": "hi"})],
+        };
+
+        session.save(path).expect("save session");
+        let loaded = Session::load(path).expect("load session");
+        std::fs::remove_file(path).expect("remove session file");
+
+        assert_eq!(loaded.version, session.version);
+        assert_eq!(loaded.goal, session.goal);
+        assert_eq!(loaded.provider, session.provider);
+        assert_eq!(loaded.model, session.model);
+        assert_eq!(loaded.history, session.history);
+
+        assert!(Session::load("/nonexistent/session.json").is_err());
+    }
+}
@@ -24,6 +24,14 @@ pub struct Styles {
     pub tool_prompt_arrow: Style,
     /// Tool output style.
     pub tool_text: Style,
+    /// Logo style.
+    pub logo_text: Style,
+    /// Help text style.
+    pub help_text: Style,
+    /// Token usage status line style.
+    pub usage_text: Style,
+    /// Notice style, e.g. for a truncated response.
+    pub notice_text: Style,
 }
 
 impl Styles {
@@ -65,6 +73,17 @@ impl Styles {
         let tool_prompt_arrow = Style::new().bold().fg_color(Some(bg_tool_color));
         let tool_text = Style::new().fg_color(Some(fg_tool_color));
 
+        // Logo and help fall back to the apprentice message style, each color can be set separately.
+        let (fg_logo_color, bg_logo_color) = config.settings.logo_color;
+        let logo_text = Style::new()
+            .fg_color(Some(fg_logo_color.map(|[r,g,b]| Color::Rgb(RgbColor(r,g,b))).unwrap_or(fg_apprentice_color)))
+            .bg_color(bg_logo_color.map(|[r,g,b]| Color::Rgb(RgbColor(r,g,b))));
+
+        let (fg_help_color, bg_help_color) = config.settings.help_color;
+        let help_text = Style::new()
+            .fg_color(Some(fg_help_color.map(|[r,g,b]| Color::Rgb(RgbColor(r,g,b))).unwrap_or(fg_apprentice_color)))
+            .bg_color(bg_help_color.map(|[r,g,b]| Color::Rgb(RgbColor(r,g,b))));
+
         Self {
             user_prompt,
             user_prompt_arrow,
@@ -75,6 +94,10 @@ impl Styles {
             tool_prompt,
             tool_prompt_arrow,
             tool_text,
+            logo_text,
+            help_text,
+            usage_text: Style::new().dimmed(),
+            notice_text: Style::new().dimmed(),
         }
     }
 }
\ No newline at end of file
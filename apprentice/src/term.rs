@@ -1,7 +1,14 @@
 use std::borrow::Cow;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
 
-use crate::{config::Config, style::Styles, error::AppError};
-use rustyline::{config::BellStyle, highlight::{CmdKind, Highlighter}, history::MemHistory, Completer, CompletionType, EditMode, Editor, Helper, Hinter, Validator};
+use crate::{completion::CommandCompleter, config::Config, style::Styles, error::AppError, util::group_digits};
+use apprentice_lib::llm::{Message, Usage};
+use rustyline::{config::BellStyle, highlight::{CmdKind, Highlighter}, history::FileHistory, Completer, CompletionType, EditMode, Editor, Helper, Hinter, Validator};
 
 const LOGO: &str = r"
     ___    ___   ___   ___   ____ _  __ ______ ____ _____ ____
@@ -14,13 +21,18 @@ Apprentice can ask clarifying questions, use tools, for example,
 execute a shell command (each time it will ask for user confirmation), etc.
 It is not recommended to trust the application blindly.";
 
+const SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+
 /// Terminal stuff.
 pub struct Term {
     user_prompt: String, 
     apprentice_prompt: String, 
     styles: Styles,
     dumb: bool,
-    editor: Editor<RlineHelper, MemHistory>,
+    json: bool,
+    editor: Editor<RlineHelper, FileHistory>,
+    history_file: Option<PathBuf>,
 }
 
 impl Term {
@@ -35,10 +47,16 @@ impl Term {
             .check_cursor_position(true)
             .completion_type(CompletionType::List)
             .edit_mode(EditMode::Emacs)
+            .max_history_size(config.settings.history_size)?
             .build();
 
+        let dumb_term = Ok("dumb") == std::env::var("TERM").as_deref();
+        let no_color = config.settings.no_color || std::env::var("NO_COLOR").is_ok_and(|val| !val.is_empty());
+        // Escape codes would garble the output redirected to a file or a pipe.
+        let redirected = !config.settings.force_color && !std::io::stdout().is_terminal();
+
         let (user_prompt, apprentice_prompt, dumb) = 
-        if Ok("dumb") == std::env::var("TERM").as_deref() {
+        if config.json || dumb_term || no_color || redirected {
             (
                 "USER> ".to_owned(),
                 "APPRENTICE> ".to_owned(),
@@ -52,21 +70,39 @@ impl Term {
             )
         };
 
-        let mut editor: Editor<RlineHelper, MemHistory> = Editor::with_config(rline_config)?;
+        let mut editor: Editor<RlineHelper, FileHistory> = Editor::with_config(rline_config)?;
         let h = RlineHelper {
-            colored_prompt: String::new()
+            colored_prompt: String::new(),
+            completer: CommandCompleter::new(&config.goal, !config.json && !dumb_term),
         };
         editor.set_helper(Some(h));
 
+        if let Some(path) = &config.settings.history_file {
+            // The file doesn't exist on the first run.
+            if path.exists() {
+                editor.load_history(path)?;
+            }
+        }
+
         Ok(Term {
             user_prompt,
             apprentice_prompt, 
             styles,
             dumb,
+            json: config.json,
             editor,
+            history_file: config.settings.history_file.clone(),
         })
     }
 
+    /// Save input history to the history file if it is set.
+    pub fn save_history(&mut self) -> Result<(), AppError> {
+        if let Some(path) = &self.history_file {
+            self.editor.save_history(path)?;
+        }
+        Ok(())
+    }
+
     /// Get input from user.
     pub fn user_input(&mut self) -> Result<String, AppError> {
         if self.dumb {
@@ -88,19 +124,75 @@ impl Term {
         }
     }
 
+    /// Output is in JSON lines format.
+    pub fn is_json(&self) -> bool {
+        self.json
+    }
+
+    /// Print message as a single JSON line.
+    pub fn print_json(&self, message: &Message) {
+        match serde_json::to_string(message) {
+            Ok(line) => println!("{}", line),
+            Err(err) => eprintln!("Failed to serialize message: {}", err),
+        }
+    }
+
+    /// Print error message.
+    pub fn print_error(&self, s: &str) {
+        if self.json {
+            println!("{}", serde_json::json!({"type": "error", "message": s}));
+        } else {
+            self.apprentice_print(s);
+        }
+    }
+
+    /// Print token usage status line.
+    pub fn print_usage(&self, usage: Usage) {
+        if self.json {
+            println!("{}", serde_json::json!({"type": "usage", "input_tokens": usage.input_tokens, "output_tokens": usage.output_tokens}));
+            return;
+        }
+        let line = format!("tokens: {} in / {} out", group_digits(usage.input_tokens), group_digits(usage.output_tokens));
+        if self.dumb {
+            println!("{}", line);
+        } else {
+            println!("{}{}{:#}", self.styles.usage_text, line, self.styles.usage_text);
+        }
+    }
+
+    /// Print notice about the last response.
+    pub fn print_notice(&self, s: &str) {
+        if self.json {
+            println!("{}", serde_json::json!({"type": "notice", "message": s}));
+        } else if self.dumb {
+            println!("{}", s);
+        } else {
+            println!("{}{}{:#}", self.styles.notice_text, s, self.styles.notice_text);
+        }
+    }
+
     /// Print logo and instructions.
     pub fn print_logo(&self) {
+        if self.json {
+            return;
+        }
         if self.dumb {
             println!("{}\n (ver. {})\n", LOGO, env!("CARGO_PKG_VERSION"));
         } else {
-            println!("{}{}\n (ver. {}){:#}\n", self.styles.apprentice_text, LOGO,  env!("CARGO_PKG_VERSION"), self.styles.apprentice_text);
+            println!("{}{}\n (ver. {}){:#}\n", self.styles.logo_text, LOGO,  env!("CARGO_PKG_VERSION"), self.styles.logo_text);
         }
     }
 
-    /// Print command suggested for execution.
-    pub fn print_tool_message(&self, tool: &str, message: &str) {
+    /// Print command suggested for execution, and the explanation of what it does if provided.
+    pub fn print_tool_message(&self, tool: &str, message: &str, explanation: Option<&str>) {
+        if self.json {
+            return;
+        }
         if self.dumb {
             println!("{}> {}", tool, message);
+            if let Some(explanation) = explanation {
+                println!("{}> # {}", tool, explanation);
+            }
         } else {
             println!("{} {} {:#}{} {:#}{}{}{:#}", 
                 self.styles.tool_prompt, 
@@ -112,6 +204,18 @@ impl Term {
                 message,
                 self.styles.tool_text
             );
+            if let Some(explanation) = explanation {
+                println!("{} {} {:#}{} {:#}{}# {}{:#}", 
+                    self.styles.tool_prompt, 
+                    tool, 
+                    self.styles.tool_prompt, 
+                    self.styles.tool_prompt_arrow, 
+                    self.styles.tool_prompt_arrow, 
+                    self.styles.apprentice_text,
+                    explanation,
+                    self.styles.apprentice_text
+                );
+            }
         }
     }
 
@@ -137,33 +241,87 @@ impl Term {
 
     /// Begin formatting with tool ouput style.
     pub fn begin_tool_format(&self) {
-        print!("{}", self.styles.tool_text);
+        if !self.dumb { print!("{}", self.styles.tool_text); }
     }
 
     /// End formatting with tool ouput style.
     pub fn end_tool_format(&self) {
-        print!("{:#}", self.styles.tool_text);
+        if !self.dumb { print!("{:#}", self.styles.tool_text); }
     }
 
     /// Print help information.
     pub fn print_help(&self) {
-        if !self.dumb { print!("{}", self.styles.apprentice_text); }
+        if self.json {
+            eprintln!("{}", HELP);
+            return;
+        }
+        if !self.dumb { print!("{}", self.styles.help_text); }
         print!("{}", HELP);
-        if !self.dumb { println!("{:#}", self.styles.apprentice_text); } else { println!(); }
+        if !self.dumb { println!("{:#}", self.styles.help_text); } else { println!(); }
+    }
+
+    /// Start spinner shown while waiting for the model response.
+    /// The spinner is stopped and cleared when the returned value is dropped.
+    /// Nothing is shown in dumb terminal.
+    pub fn start_spinner(&self) -> Spinner {
+        if self.dumb {
+            return Spinner { stop: Arc::new(AtomicBool::new(true)), handle: None };
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let style = self.styles.usage_text;
+
+        let handle = std::thread::spawn(move || {
+            let mut stdout = std::io::stdout();
+            for frame in SPINNER_FRAMES.iter().cycle() {
+                if stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+                let _ = write!(stdout, "\r{}{} thinking...{:#}", style, frame, style);
+                let _ = stdout.flush();
+                std::thread::sleep(SPINNER_INTERVAL);
+            }
+            // Clear the spinner line.
+            let _ = write!(stdout, "\r\x1b[2K");
+            let _ = stdout.flush();
+        });
+
+        Spinner { stop, handle: Some(handle) }
     }
 
     /// Loading messages.
     pub fn loading_progress(&self, message: &str) {
+        if self.json {
+            eprintln!("{}", message);
+            return;
+        }
         if !self.dumb { print!("{}", self.styles.apprentice_text); }
         print!("{}", message);
         if !self.dumb { println!("{:#}", self.styles.apprentice_text); } else { println!(); }
     }
 }
 
+/// Running spinner, see [`Term::start_spinner`].
+pub struct Spinner {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
 
 #[derive(Helper, Validator, Hinter, Completer)]
 struct RlineHelper {
     colored_prompt: String,
+    #[rustyline(Completer)]
+    completer: CommandCompleter,
 }
 
 impl Highlighter for RlineHelper {
@@ -4,11 +4,41 @@ use crate::options::Options;
 use crate::error::AppError;
 use crate::util::parse_colors;
 
-fn get_str_val<'a>(val: &'a Value, err: &'static str) -> Result<&'a str, AppError> {
-    if !val.is_str() {
-        return Err(AppError::ConfigParseError(err));
+/// Keys recognized in a context section.
+const CONTEXT_KEYS: &[&str] = &[
+    "goal", "model", "model_provider", "api_key", "auth_mode", "api_url", "api_base", "api_version",
+    "max_tokens", "n", "temperature", "top_p", "top_k", "frequency_penalty", "presence_penalty",
+    "stop_sequence", "reasoning_effort", "thinking_budget", "prompt_caching", "safety_settings",
+    "prompt", "prompt_template", "help_prefixes", "minimal_prompt",
+];
+
+/// Sections that are not contexts.
+const RESERVED_SECTIONS: &[&str] = &["settings", "defaults"];
+
+/// Replace `${VAR}` tokens with the values returned by `lookup`.
+/// Unterminated `${` is kept as is.
+fn expand_vars(s: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String, AppError> {
+    let mut ret = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + 2 + len];
+        let value = lookup(name)
+            .ok_or_else(|| AppError::Error(format!("Failed to parse config file: environment variable {} is not set", name)))?;
+        ret.push_str(&rest[..start]);
+        ret.push_str(&value);
+        rest = &rest[start + 2 + len + 1..];
     }
-    val.as_str().ok_or(AppError::Unknown)
+    ret.push_str(rest);
+    Ok(ret)
+}
+
+/// String value with `${VAR}` tokens replaced by environment variables.
+fn get_str_val(val: &Value, err: &'static str) -> Result<String, AppError> {
+    let s = val.as_str().ok_or(AppError::ConfigParseError(err))?;
+    expand_vars(s, |name| std::env::var(name).ok())
 }
 
 fn get_int_val(val: &Value, err: &'static str) -> Result<i64, AppError> {
@@ -25,83 +55,205 @@ fn get_float_val(val: &Value, err: &'static str) -> Result<f64, AppError> {
     val.as_float().ok_or(AppError::Unknown)
 }
 
+fn get_bool_val(val: &Value, err: &'static str) -> Result<bool, AppError> {
+    if !val.is_bool() {
+        return Err(AppError::ConfigParseError(err));
+    }
+    val.as_bool().ok_or(AppError::Unknown)
+}
+
+fn get_str_array_val(val: &Value, err: &'static str) -> Result<Vec<String>, AppError> {
+    let arr = val.as_array().ok_or(AppError::ConfigParseError(err))?;
+    arr.iter()
+        .map(|v| get_str_val(v, err))
+        .collect()
+}
+
+fn get_str_table_val(val: &Value, err: &'static str) -> Result<Vec<(String, String)>, AppError> {
+    let table = val.as_table().ok_or(AppError::ConfigParseError(err))?;
+    table.iter()
+        .map(|(k, v)| get_str_val(v, err).map(|s| (k.clone(), s)))
+        .collect()
+}
+
 fn get_color_val(val: &Value, err: &'static str) -> Result<(Option<[u8;3]>, Option<[u8;3]>), AppError> {
     let s = get_str_val(val, err)?;
-    parse_colors(s).map_err(|_| AppError::ConfigParseError(err))
+    parse_colors(&s).map_err(|_| AppError::ConfigParseError(err))
 }
 
-pub fn parse_toml_config(content: &str, options: &mut Options) -> Result<(), AppError> {
+/// Names of the context sections.
+fn context_names(toml_config: &Table) -> Vec<&str> {
+    toml_config.iter()
+        .filter(|(name, val)| val.is_table() && !RESERVED_SECTIONS.contains(&name.as_str()))
+        .map(|(name, _)| name.as_str())
+        .collect()
+}
 
-    let toml_config: Table = toml::from_str(content)?;
+/// Warnings about the keys of the context section that are not recognized, e.g. misspelled.
+fn context_warnings(context_name: &str, ct: &Table) -> Vec<String> {
+    let unknown: Vec<&str> = ct.keys()
+        .map(|key| key.as_str())
+        .filter(|key| !CONTEXT_KEYS.contains(key))
+        .collect();
 
-    if let Some(default_context) = toml_config.get("default_context") {
+    if unknown.len() == ct.len() {
+        vec![format!("context \"{}\" has no recognized keys.", context_name)]
+    } else {
+        unknown.iter()
+            .map(|key| format!("unknown key \"{}\" in context \"{}\" is ignored.", key, context_name))
+            .collect()
+    }
+}
 
-        let context_name = get_str_val(default_context, "default_context must be a string value")?;
+/// Set the options from the keys of the context section.
+fn apply_context(ct: &Table, options: &mut Options) -> Result<(), AppError> {
+    if let Some(val) = ct.get("goal") {
+        options.goal.replace(get_str_val(val, "goal must be a string value")?);
+    }
 
-        let context_value = toml_config.get(context_name)
-            .ok_or(AppError::ConfigParseError("configuration for the default context is not specified"))?;
+    if let Some(val) = ct.get("model") {
+        options.model.replace(get_str_val(val,"model must be a string value")?);
+    }
 
-        let ct = context_value.as_table().ok_or(AppError::Unknown)?;
+    if let Some(val) = ct.get("model_provider") {
+        options.model_provider.replace(get_str_val(val, "model_provider must be a string value")?);
+    }
 
-        if let Some(val) = ct.get("goal") {
-            options.goal.replace(get_str_val(val, "goal must be a string value")?.to_owned());
-        }
+    if let Some(val) = ct.get("api_key") {
+        options.api_key.replace(get_str_val(val,"api_key must be a string value")?);
+    }
 
-        if let Some(val) = ct.get("model") {
-            options.model.replace(get_str_val(val,"model must be a string value")?.to_owned());
-        }
+    if let Some(val) = ct.get("auth_mode") {
+        options.auth_mode.replace(get_str_val(val, "auth_mode must be a string value")?);
+    }
+    
+    if let Some(val) = ct.get("api_url") {
+        options.api_url.replace(get_str_val(val,"api_url must be a string value")?);
+    }
+    
+    if let Some(val) = ct.get("api_base") {
+        options.api_base.replace(get_str_val(val,"api_base must be a string value")?);
+    }
 
-        if let Some(val) = ct.get("model_provider") {
-            options.model_provider.replace(get_str_val(val, "model_provider must be a string value")?.to_owned());
-        }
+    if let Some(val) = ct.get("api_version") {
+        options.api_version.replace(get_str_val(val,"api_version must be a string value")?);
+    }
 
-        if let Some(val) = ct.get("api_key") {
-            options.api_key.replace(get_str_val(val,"api_key must be a string value")?.to_owned());
-        }
-        
-        if let Some(val) = ct.get("api_url") {
-            options.api_url.replace(get_str_val(val,"api_url must be a string value")?.to_owned());
-        }
-        
-        if let Some(val) = ct.get("api_version") {
-            options.api_version.replace(get_str_val(val,"api_version must be a string value")?.to_owned());
-        }
+    if let Some(val) = ct.get("max_tokens") {
+        options.max_tokens.replace(get_int_val(val,"max_tokens must be an integer value")?);
+    }
 
-        if let Some(val) = ct.get("max_tokens") {
-            options.max_tokens.replace(get_int_val(val,"max_tokens must be an integer value")?);
-        }
+    if let Some(val) = ct.get("n") {
+        options.n.replace(get_int_val(val,"n must be an integer value")?);
+    }
 
-        if let Some(val) = ct.get("n") {
-            options.n.replace(get_int_val(val,"n must be an integer value")?);
-        }
+    if let Some(val) = ct.get("temperature") {
+        options.temperature.replace(get_float_val(val,"temperature must be a float value")?);
+    }
 
-        if let Some(val) = ct.get("temperature") {
-            options.temperature.replace(get_float_val(val,"temperature must be a float value")?);
-        }
+    if let Some(val) = ct.get("top_p") {
+        options.top_p.replace(get_float_val(val,"top_p must be a float value")?);
+    }
 
-        if let Some(val) = ct.get("top_p") {
-            options.top_p.replace(get_float_val(val,"top_p must be a float value")?);
-        }
+    if let Some(val) = ct.get("top_k") {
+        options.top_k.replace(get_int_val(val,"top_k must be an integer value")?);
+    }
 
-        if let Some(val) = ct.get("top_k") {
-            options.top_k.replace(get_int_val(val,"top_k must be an integer value")?);
-        }
+    if let Some(val) = ct.get("frequency_penalty") {
+        options.frequency_penalty.replace(get_float_val(val,"frequency_penalty must be a float value")?);
+    }
 
-        if let Some(val) = ct.get("frequency_penalty") {
-            options.frequency_penalty.replace(get_float_val(val,"frequency_penalty must be a float value")?);
-        }
+    if let Some(val) = ct.get("presence_penalty") {
+        options.presence_penalty.replace(get_float_val(val,"presence_penalty must be a float value")?);
+    }
 
-        if let Some(val) = ct.get("presence_penalty") {
-            options.presence_penalty.replace(get_float_val(val,"presence_penalty must be a float value")?);
-        }
+    if let Some(val) = ct.get("stop_sequence") {
+        let sequences = if val.is_str() {
+            vec![get_str_val(val, "stop_sequence must be a string or an array of string values")?]
+        } else {
+            get_str_array_val(val, "stop_sequence must be a string or an array of string values")?
+        };
+        options.stop_sequences.replace(sequences);
+    }
+
+    if let Some(val) = ct.get("reasoning_effort") {
+        options.reasoning_effort.replace(get_str_val(val, "reasoning_effort must be a string value")?);
+    }
+
+    if let Some(val) = ct.get("thinking_budget") {
+        options.thinking_budget.replace(get_int_val(val, "thinking_budget must be an integer value")?);
+    }
+
+    if let Some(val) = ct.get("prompt_caching") {
+        options.prompt_caching = get_bool_val(val, "prompt_caching must be a boolean value")?;
+    }
+
+    if let Some(val) = ct.get("safety_settings") {
+        options.safety_settings = get_str_table_val(val, "safety_settings must be a table of string values")?;
+    }
+
+    if let Some(val) = ct.get("prompt") {
+        options.prompt.replace(get_str_val(val, "prompt must be a string value")?);
+    }
+
+    if let Some(val) = ct.get("prompt_template") {
+        options.prompt_template.replace(get_str_val(val, "prompt_template must be a string value")?);
+    }
+
+    if let Some(val) = ct.get("help_prefixes") {
+        options.help_prefixes = get_str_array_val(val, "help_prefixes must be an array of string values")?;
+    }
+
+    if let Some(val) = ct.get("minimal_prompt") {
+        options.minimal_prompt = get_bool_val(val, "minimal_prompt must be a boolean value")?;
+    }
 
-        if let Some(val) = ct.get("stop_sequence") {
-            options.stop_sequence.replace(get_str_val(val,"stop_sequence must be a string value")?.to_owned());
+    Ok(())
+}
+
+/// Parse config file content into the options.
+/// `context` selects the context section instead of `default_context`.
+pub fn parse_toml_config(content: &str, context: Option<&str>, options: &mut Options) -> Result<(), AppError> {
+
+    let toml_config: Table = toml::from_str(content)?;
+
+    // Defaults are applied first and overridden by the selected context.
+    if let Some(defaults) = toml_config.get("defaults") {
+        let ct = defaults.as_table()
+            .ok_or(AppError::ConfigParseError("defaults must be a table"))?;
+
+        for warning in context_warnings("defaults", ct) {
+            eprintln!("Warning: config file {}", warning);
         }
 
-        if let Some(val) = ct.get("prompt") {
-            options.prompt.replace(get_str_val(val, "prompt must be a string value")?.to_owned());
+        apply_context(ct, options)?;
+    }
+
+    let (context_name, context_kind) = if let Some(context) = context {
+        (Some(context), "context")
+    } else if let Some(default_context) = toml_config.get("default_context") {
+        (Some(default_context.as_str().ok_or(AppError::ConfigParseError("default_context must be a string value"))?), "default context")
+    } else {
+        (None, "")
+    };
+
+    if let Some(context_name) = context_name {
+
+        let context_value = toml_config.get(context_name)
+            .filter(|val| val.is_table() && !RESERVED_SECTIONS.contains(&context_name))
+            .ok_or_else(|| AppError::Error(format!(
+                "Failed to parse config file: {} \"{}\" is not found, available contexts: {}",
+                context_kind,
+                context_name,
+                context_names(&toml_config).join(", "))))?;
+
+        let ct = context_value.as_table().ok_or(AppError::Unknown)?;
+
+        for warning in context_warnings(context_name, ct) {
+            eprintln!("Warning: config file {}", warning);
         }
+
+        apply_context(ct, options)?;
     }
 
     if let Some(settings_section) = toml_config.get("settings") {
@@ -115,6 +267,50 @@ pub fn parse_toml_config(content: &str, options: &mut Options) -> Result<(), App
             if let Some(tool_color) = settings.get("tool_color") {
                 options.tool_color = get_color_val(tool_color, "tool_color value must have valid format, e.g. 'fg(255,0,123);bg(0,123,255)'.")?;
             }
+            if let Some(show_usage) = settings.get("show_usage") {
+                options.show_usage = get_bool_val(show_usage, "show_usage must be a boolean value")?;
+            }
+            if let Some(no_color) = settings.get("no_color") {
+                options.no_color = get_bool_val(no_color, "no_color must be a boolean value")?;
+            }
+            if let Some(force_color) = settings.get("force_color") {
+                options.force_color = get_bool_val(force_color, "force_color must be a boolean value")?;
+            }
+            if let Some(logo_color) = settings.get("logo_color") {
+                options.logo_color = get_color_val(logo_color, "logo_color value must have valid format, e.g. 'fg(255,0,123);bg(0,123,255)'.")?;
+            }
+            if let Some(help_color) = settings.get("help_color") {
+                options.help_color = get_color_val(help_color, "help_color value must have valid format, e.g. 'fg(255,0,123);bg(0,123,255)'.")?;
+            }
+            if let Some(auto_approve) = settings.get("auto_approve") {
+                options.auto_approve = get_str_array_val(auto_approve, "auto_approve must be an array of string values")?;
+            }
+            if let Some(history_file) = settings.get("history_file") {
+                options.history_file.replace(get_str_val(history_file, "history_file must be a string value")?);
+            }
+            if let Some(history_size) = settings.get("history_size") {
+                let size = get_int_val(history_size, "history_size must be a positive integer value")?;
+                if size <= 0 {
+                    return Err(AppError::ConfigParseError("history_size must be a positive integer value"));
+                }
+                options.history_size.replace(size as usize);
+            }
+            if let Some(max_tool_output_bytes) = settings.get("max_tool_output_bytes") {
+                let size = get_int_val(max_tool_output_bytes, "max_tool_output_bytes must be a positive integer value")?;
+                if size <= 0 {
+                    return Err(AppError::ConfigParseError("max_tool_output_bytes must be a positive integer value"));
+                }
+                options.max_tool_output.replace(size as usize);
+            }
+            if let Some(no_history) = settings.get("no_history") {
+                options.no_history = get_bool_val(no_history, "no_history must be a boolean value")?;
+            }
+            if let Some(redact_secrets) = settings.get("redact_secrets") {
+                options.redact_secrets = get_bool_val(redact_secrets, "redact_secrets must be a boolean value")?;
+            }
+            if let Some(redact_patterns) = settings.get("redact_patterns") {
+                options.redact_patterns = get_str_array_val(redact_patterns, "redact_patterns must be an array of string values")?;
+            }
         }
     }
 
@@ -132,10 +328,11 @@ default_context = \"google_cloud\"
 
 # Context contains a set of configuration parameters for the agent
 [google_cloud]
-goal = \"gcp\"                # The cloud provider agent will work with, one of gcp, aws, or azure
+goal = \"gcp\"                # The cloud provider agent will work with, one of gcp, aws, azure, or custom:<description>
 model_provider = \"openai\"   # Model provider, one of: openai, anthropic, gcp
 model = \"gpt-4\"             # Model name
 api_url = \"https://api.openai.com/v1/chat/completions\"  # Model API URL
+api_base = \"https://gateway.example.com\"
 api_key = \"<your-api-key>\"  # Model API key
 api_version = \"v1.1\"        # Other parameters (depending on provider some of the parameters may be required)
 max_tokens = 8192
@@ -146,7 +343,14 @@ top_k = 10
 frequency_penalty = 2.0
 presence_penalty = 3.0
 stop_sequence = \"seq\"
+reasoning_effort = \"high\"
+thinking_budget = 1024
+prompt_caching = true
+safety_settings = { HARM_CATEGORY_HARASSMENT = \"BLOCK_NONE\" }
 prompt = \"sample_prompt\"
+prompt_template = \"sample_template\"
+minimal_prompt = true
+help_prefixes = [\"kubectl\"]
 
 # Second context
 [google_cloud_gemini]
@@ -155,22 +359,37 @@ model_provider = \"gcp\"
 model = \"gemini-1.5-pro-002\"
 api_url = \"https://generativelanguage.googleapis.com/v1beta/models/gemini-1.5-pro-002:generateContent\"
 api_key = \"<your-api-key>\"      
+auth_mode = \"bearer\"
 prompt = \"sample_prompt\"  
         
 [settings]
 user_color = \"fg(1,2,3);bg(4,5,6)\"
 apprentice_color = \"fg(7,8,9);bg(10,11,12)\"
 tool_color = \"fg(13,14,15);bg(16,17,18)\"
+logo_color = \"fg(19,20,21)\"
+no_color = true
+show_usage = true
+force_color = true
+help_color = \"fg(22,23,24);bg(25,26,27)\"
+auto_approve = [\"gcloud compute instances list\", \"bq show\"]
+history_file = \"/tmp/history\"
+history_size = 200
+max_tool_output_bytes = 8192
+no_history = true
+redact_secrets = true
+redact_patterns = [\"client_secret=(\\\\S+)\"]
 ";
 
         let mut options = Options::new();
-        assert!(parse_toml_config(SAMPLE_CONTENT, &mut options).is_ok());
+        assert!(parse_toml_config(SAMPLE_CONTENT, None, &mut options).is_ok());
 
         assert_eq!(options.goal, Some("gcp".into()));
         assert_eq!(options.model_provider, Some("openai".into()));
         assert_eq!(options.model, Some("gpt-4".into()));
         assert_eq!(options.api_key, Some("<your-api-key>".into()));
+        assert_eq!(options.auth_mode, None);
         assert_eq!(options.api_url, Some("https://api.openai.com/v1/chat/completions".into()));
+        assert_eq!(options.api_base, Some("https://gateway.example.com".into()));
         assert_eq!(options.api_version, Some("v1.1".into()));
         assert_eq!(options.max_tokens, Some(8192));
         assert_eq!(options.n, Some(4));
@@ -179,11 +398,122 @@ tool_color = \"fg(13,14,15);bg(16,17,18)\"
         assert_eq!(options.top_k, Some(10));
         assert_eq!(options.frequency_penalty, Some(2.0));
         assert_eq!(options.presence_penalty, Some(3.0));
-        assert_eq!(options.stop_sequence, Some("seq".into()));
+        assert_eq!(options.stop_sequences, Some(vec!["seq".into()]));
+
+        let mut options2 = Options::new();
+        assert!(parse_toml_config("default_context = \"ctx\"\n[ctx]\nstop_sequence = [\"a\", \"b\"]\n", None, &mut options2).is_ok());
+        assert_eq!(options2.stop_sequences, Some(vec!["a".into(), "b".into()]));
+        assert_eq!(options.reasoning_effort, Some("high".into()));
+        assert_eq!(options.thinking_budget, Some(1024));
+        assert!(options.prompt_caching);
+        assert_eq!(options.safety_settings, vec![("HARM_CATEGORY_HARASSMENT".to_owned(), "BLOCK_NONE".to_owned())]);
         assert_eq!(options.message, None);
         assert_eq!(options.apprentice_color, (Some([7,8,9]), Some([10,11,12])));
         assert_eq!(options.user_color, (Some([1,2,3]), Some([4,5,6])));
         assert_eq!(options.tool_color, (Some([13,14,15]), Some([16,17,18])));
+        assert_eq!(options.logo_color, (Some([19,20,21]), None));
+        assert!(options.no_color);
+        assert!(options.show_usage);
+        assert!(options.force_color);
+        assert_eq!(options.help_color, (Some([22,23,24]), Some([25,26,27])));
         assert_eq!(options.prompt, Some("sample_prompt".into()));
+        assert_eq!(options.prompt_template, Some("sample_template".into()));
+        assert!(options.minimal_prompt);
+        assert_eq!(options.help_prefixes, vec!["kubectl".to_owned()]);
+        assert_eq!(options.auto_approve, vec!["gcloud compute instances list".to_owned(), "bq show".to_owned()]);
+        assert_eq!(options.history_file, Some("/tmp/history".into()));
+        assert_eq!(options.history_size, Some(200));
+        assert_eq!(options.max_tool_output, Some(8192));
+        assert!(options.no_history);
+        assert!(options.redact_secrets);
+        assert_eq!(options.redact_patterns, vec!["client_secret=(\\S+)".to_owned()]);
+    }
+
+    #[test]
+    fn test_expand_vars() {
+        let lookup = |name: &str| (name == "KEY").then(|| "<secret>".to_owned());
+
+        assert_eq!(expand_vars("${KEY}", lookup).expect("expanded"), "<secret>");
+        assert_eq!(expand_vars("Bearer ${KEY}, ${KEY}!", lookup).expect("expanded"), "Bearer <secret>, <secret>!");
+        assert_eq!(expand_vars("no vars $KEY {KEY}", lookup).expect("expanded"), "no vars $KEY {KEY}");
+        assert_eq!(expand_vars("unterminated ${KEY", lookup).expect("expanded"), "unterminated ${KEY");
+
+        let err = expand_vars("${MISSING}", lookup).expect_err("variable is not set");
+        assert_eq!(err.to_string(), "Failed to parse config file: environment variable MISSING is not set");
+    }
+
+    #[test]
+    fn test_defaults() {
+        const CONTENT: &str = "
+default_context = \"gpt\"
+
+[defaults]
+model_provider = \"openai\"
+api_key = \"<shared-key>\"
+model = \"gpt-4\"
+max_tokens = 1024
+
+[gpt]
+goal = \"gcp\"
+max_tokens = 2048
+
+[claude]
+goal = \"aws\"
+model_provider = \"anthropic\"
+model = \"claude-3-5-sonnet-20241022\"
+";
+        let mut options = Options::new();
+        parse_toml_config(CONTENT, None, &mut options).expect("parse config");
+        assert_eq!(options.goal, Some("gcp".into()));
+        assert_eq!(options.model_provider, Some("openai".into()));
+        assert_eq!(options.model, Some("gpt-4".into()));
+        assert_eq!(options.api_key, Some("<shared-key>".into()));
+        assert_eq!(options.max_tokens, Some(2048));
+
+        let mut options = Options::new();
+        parse_toml_config(CONTENT, Some("claude"), &mut options).expect("parse config");
+        assert_eq!(options.goal, Some("aws".into()));
+        assert_eq!(options.model_provider, Some("anthropic".into()));
+        assert_eq!(options.model, Some("claude-3-5-sonnet-20241022".into()));
+        assert_eq!(options.api_key, Some("<shared-key>".into()));
+        assert_eq!(options.max_tokens, Some(1024));
+    }
+
+    #[test]
+    fn test_default_context_diagnostics() {
+        const CONTENT: &str = "
+default_context = \"gcp\"
+
+[google_cloud]
+model = \"gpt-4\"
+
+[aws]
+modle = \"claude\"
+
+[defaults]
+api_key = \"<shared-key>\"
+
+[settings]
+no_color = true
+";
+        let mut options = Options::new();
+        let err = parse_toml_config(CONTENT, None, &mut options).expect_err("missing context");
+        assert_eq!(err.to_string(), "Failed to parse config file: default context \"gcp\" is not found, available contexts: aws, google_cloud");
+
+        let err = parse_toml_config(CONTENT, Some("settings"), &mut options).expect_err("missing context");
+        assert_eq!(err.to_string(), "Failed to parse config file: context \"settings\" is not found, available contexts: aws, google_cloud");
+
+        let err = parse_toml_config(CONTENT, Some("defaults"), &mut options).expect_err("missing context");
+        assert_eq!(err.to_string(), "Failed to parse config file: context \"defaults\" is not found, available contexts: aws, google_cloud");
+
+        parse_toml_config(CONTENT, Some("google_cloud"), &mut options).expect("select context");
+        assert_eq!(options.model, Some("gpt-4".into()));
+
+        let content: Table = toml::from_str(CONTENT).expect("parse content");
+        let aws = content["aws"].as_table().expect("table");
+        assert_eq!(context_warnings("aws", aws), vec!["context \"aws\" has no recognized keys.".to_owned()]);
+
+        let ct: Table = toml::from_str("model = \"gpt-4\"\ntemprature = 0.5").expect("parse content");
+        assert_eq!(context_warnings("ctx", &ct), vec!["unknown key \"temprature\" in context \"ctx\" is ignored.".to_owned()]);
     }
 }
\ No newline at end of file
@@ -0,0 +1,105 @@
+use apprentice_lib::tools::{ParamType, ToolParam, ToolSpec};
+use apprentice_lib::llm::ToolParam as InputParam;
+use reqwest::blocking::Client;
+use reqwest::Url;
+use crate::error::AppError;
+use crate::term::Term;
+use crate::util::read_truncated;
+
+/// Maximum number of bytes of the response body returned to the model.
+const MAX_BYTES: u64 = 32768;
+
+/// Fetch a web page.
+pub struct Fetch {
+    client: Client,
+}
+
+impl Fetch {
+
+    /// Return tool specification.
+    pub fn get_tool_spec(&self) -> ToolSpec {
+        let description = format!(
+            "Makes an HTTP GET request and returns the response body as text (truncated to {} KB). User may cancel the request and will provide reason.",
+            MAX_BYTES / 1024
+        );
+
+        ToolSpec {
+            name: "FETCH".to_owned(),
+            description,
+            params: vec![
+                ToolParam::new("url".to_string(), "http or https URL to fetch".to_string(), ParamType::String, true)
+            ]
+        }
+    }
+
+    /// Create an instance.
+    pub fn new() -> Self {
+        Fetch {
+            client: Client::new(),
+        }
+    }
+
+    /// Ask user and fetch the url.
+    pub fn exec(&self, url: &str, term: &mut Term) -> Result<String, AppError> {
+        let parsed = match Url::parse(url) {
+            Ok(u) if u.scheme() == "http" || u.scheme() == "https" => u,
+            Ok(_) => return Ok("only http and https URLs are allowed.".to_owned()),
+            Err(err) => return Ok(format!("invalid URL: {}", err)),
+        };
+
+        term.print_tool_message("FETCH", url, None);
+
+        loop {
+            let user_input = term.tool_input("FETCH", "Fetch URL? (y - yes / n - no): ")?;
+            let user_input = user_input.trim();
+
+            if user_input.len() == 1 {
+                let ret = match user_input {
+                    "y" => Ok(self.get(parsed)),
+                    "n" => {
+                        let reason = term.tool_input("FETCH", "reason: ")?;
+                        Ok(format!("User cancelled the operation with the reason: {}", reason))
+                    },
+                    _ => continue
+                };
+
+                return ret
+            }
+        }
+    }
+
+    fn get(&self, url: Url) -> String {
+        let response = match self.client.get(url).send() {
+            Ok(r) => r,
+            Err(err) => return format!("request failed: {}", err),
+        };
+
+        let status = response.status();
+
+        // Only the returned part of the body is downloaded.
+        let body = match read_truncated(response, MAX_BYTES) {
+            Ok(b) => b,
+            Err(err) => return format!("failed to read response body: {}", err),
+        };
+
+        format!("STATUS: {}\nBODY:\n{}", status, body)
+    }
+
+    /// Check params and execute tool.
+    pub fn call_tool(&self, params: &[InputParam], term: &mut Term) -> Result<String, AppError> {
+        if params.len() == 1 {
+            let param = &params[0];
+            if param.name == "url" {
+                if let Some(url) = param.value.as_str() {
+                    self.exec(url, term)
+                } else {
+                    Ok("wrong parameter value type, expect 1 parameter called \"url\" of type string.".to_owned())
+                }
+            } else {
+                Ok("wrong parameter name, expect 1 parameter called \"url\" of type string.".to_owned())
+            }
+        } else {
+            Ok("wrong number of input parameters, expect 1 parameter called \"url\" of type string.".to_owned())
+        }
+    }
+}
@@ -1,12 +1,17 @@
 use apprentice_lib::tools::{ParamType, ToolParam, ToolSpec};
 use apprentice_lib::llm::ToolParam as InputParam;
-use crate::config::Goal;
 use crate::error::AppError;
-use crate::util::exec_pipe;
+use crate::util::{exec_pipe, Redactor, SHELL_OPERATORS};
+
+/// Default suffix appended to a command to request its help page.
+pub const DEFAULT_HELP_SUFFIX: &str = " --help";
 
 /// Ask user something.
 pub struct Help {
-    goal: Goal,
+    commands: Vec<(String, String)>,
+    echo: bool,
+    max_output: usize,
+    redactor: Option<Redactor>,
 }
 
 impl Help {
@@ -20,20 +25,22 @@ impl Help {
             name: "HELP".to_owned(),
             description,
             params: vec![
-                ToolParam {
-                    name: "command".to_string(), 
-                    description: "command for which the help is required".to_string(), 
-                    data_type: ParamType::String, 
-                    required: true
-                }
+                ToolParam::new("command".to_string(), "command for which the help is required".to_string(), ParamType::String, true)
             ]
         }
     }
 
     /// Create an instance.
-    pub fn new(goal: Goal) -> Self {
+    /// `commands` are pairs of allowed command prefix and the suffix that requests the help page,
+    /// e.g. `("aws", " help")`.
+    /// If `echo` is set, help pages are also printed to the terminal.
+    /// Help pages are truncated to `max_output` bytes, and masked with `redactor` like the shell output.
+    pub fn new(commands: Vec<(String, String)>, echo: bool, max_output: usize, redactor: Option<Redactor>) -> Self {
         Help {
-            goal,
+            commands,
+            echo,
+            max_output,
+            redactor,
         }
     }
 
@@ -43,26 +50,22 @@ impl Help {
             let param = &params[0];
             if param.name == "command" {
                 if let Some(command) = param.value.as_str() {
-                    let full_cmd = match self.goal {
-                        Goal::Gcp => {
-                            if command.starts_with("gcloud ") || command.starts_with("bq ") || command.starts_with("gsutil ") {
-                                command.to_owned() + " --help"
-                            } else {
-                                return Ok("command must start with \"gcloud \",  \"bq  \", or  \"gsutil  \".".to_owned());
-                            }
-                        },
-                        Goal::Aws => if command.starts_with("aws ") {
-                            command.to_owned() + " help"
-                        } else {
-                            return Ok("command must start with \"aws \".".to_owned());
-                        }
-                        Goal::Azure => if command.starts_with("az ") {
-                            command.to_owned() + " --help"
-                        } else {
-                            return Ok("command must start with \"az \".".to_owned());
-                        }
-                    };
-                    exec_pipe(&full_cmd)
+                    // Help pages are requested without confirmation, so only the configured commands are allowed,
+                    // and they can't be chained with other commands.
+                    let suffix = self.commands.iter()
+                        .find(|(prefix, _)| command.starts_with(&format!("{prefix} ")))
+                        .map(|(_, suffix)| suffix);
+
+                    if command.contains(SHELL_OPERATORS) {
+                        Ok("command must not contain shell operators.".to_owned())
+                    } else if let Some(suffix) = suffix {
+                        exec_pipe(&(command.to_owned() + suffix), self.echo, None, Some(self.max_output), self.redactor.as_ref())
+                    } else if self.commands.is_empty() {
+                        Ok("help pages are not available.".to_owned())
+                    } else {
+                        let prefixes: Vec<String> = self.commands.iter().map(|(prefix, _)| format!("\"{prefix} \"")).collect();
+                        Ok(format!("command must start with one of: {}.", prefixes.join(", ")))
+                    }
                 } else {
                     Ok("wrong parameter value type, expect 1 parameter called \"command\" of type string.".to_owned())
                 }
@@ -74,4 +77,38 @@ impl Help {
         }
     }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_call_tool() {
+        let param = |command: &str| vec![InputParam { name: "command".to_owned(), value: json!(command) }];
+
+        let help = Help::new(vec![("gcloud".into(), DEFAULT_HELP_SUFFIX.into()), ("bq".into(), DEFAULT_HELP_SUFFIX.into())], false, 1024, None);
+        assert_eq!(help.call_tool(&param("rm -rf /")).expect("tool result"), "command must start with one of: \"gcloud \", \"bq \".");
+        assert_eq!(help.call_tool(&param("gcloudx compute")).expect("tool result"), "command must start with one of: \"gcloud \", \"bq \".");
+        assert_eq!(help.call_tool(&param("gcloud x; rm -rf ~")).expect("tool result"), "command must not contain shell operators.");
+        assert_eq!(help.call_tool(&param("gcloud $(rm -rf ~)")).expect("tool result"), "command must not contain shell operators.");
+
+        let help = Help::new(vec![], false, 1024, None);
+        assert_eq!(help.call_tool(&param("kubectl get")).expect("tool result"), "help pages are not available.");
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            let help = Help::new(vec![("echo".into(), " help".into())], false, 1024, None);
+            assert!(help.call_tool(&param("echo get")).expect("tool result").contains("get help"));
+
+            let redactor = Redactor::new(&[]).expect("compile patterns");
+            let help = Help::new(vec![("echo".into(), " help".into())], false, 1024, Some(redactor));
+            let output = help.call_tool(&param("echo api_key=abcdef")).expect("tool result");
+            assert!(output.contains("api_key=<redacted>"));
+
+            let help = Help::new(vec![("echo".into(), " help".into())], false, 4, None);
+            assert!(help.call_tool(&param("echo get")).expect("tool result").contains("truncated"));
+        }
+    }
+}
@@ -1,5 +1,10 @@
 mod shell;
 mod help;
+mod read_file;
+mod fetch;
 
 pub use shell::Shell;
-pub use help::Help;
\ No newline at end of file
+pub use help::Help;
+pub use help::DEFAULT_HELP_SUFFIX;
+pub use read_file::ReadFile;
+pub use fetch::Fetch;
\ No newline at end of file
@@ -0,0 +1,139 @@
+use std::path::Path;
+use apprentice_lib::tools::{ParamType, ToolParam, ToolSpec};
+use apprentice_lib::llm::ToolParam as InputParam;
+use crate::error::AppError;
+use crate::util::read_truncated;
+
+/// Default maximum number of bytes returned to the model.
+const DEFAULT_MAX_BYTES: u64 = 16384;
+
+/// Upper limit of `max_bytes` requested by the model.
+const MAX_BYTES: u64 = 262144;
+
+/// Read file contents.
+pub struct ReadFile {
+    allow_any_path: bool,
+}
+
+impl ReadFile {
+
+    /// Return tool specification.
+    pub fn get_tool_spec(&self) -> ToolSpec {
+        let description = format!(
+            "Returns the contents of a text file. Output is truncated to max_bytes (default {}, at most {}).",
+            DEFAULT_MAX_BYTES, MAX_BYTES
+        );
+
+        ToolSpec {
+            name: "READ_FILE".to_owned(),
+            description,
+            params: vec![
+                ToolParam::new("path".to_string(), "path to the file, relative to the current directory".to_string(), ParamType::String, true),
+                ToolParam::new("max_bytes".to_string(), "maximum number of bytes to return".to_string(), ParamType::Integer, false)
+                    .with_maximum(MAX_BYTES as f64),
+            ]
+        }
+    }
+
+    /// Create an instance.
+    pub fn new(allow_any_path: bool) -> Self {
+        ReadFile {
+            allow_any_path,
+        }
+    }
+
+    /// Read up to `max_bytes` of the file, but not more than `MAX_BYTES`.
+    pub fn read(&self, path: &str, max_bytes: u64) -> Result<String, AppError> {
+        let path = Path::new(path);
+        let max_bytes = max_bytes.min(MAX_BYTES);
+
+        if !self.allow_any_path {
+            let cwd = std::env::current_dir()
+                .and_then(|p| p.canonicalize())
+                .map_err(|err| AppError::Error(format!("Failed to determine current directory: {}", err)))?;
+
+            match path.canonicalize() {
+                Ok(full_path) if full_path.starts_with(&cwd) => {},
+                Ok(_) => return Ok(format!("access denied: {} is outside of the current directory.", path.display())),
+                Err(err) => return Ok(format!("failed to read {}: {}", path.display(), err)),
+            }
+        }
+
+        let file = match std::fs::File::open(path) {
+            Ok(f) => f,
+            Err(err) => return Ok(format!("failed to read {}: {}", path.display(), err)),
+        };
+
+        match read_truncated(file, max_bytes) {
+            Ok(content) => Ok(content),
+            Err(err) => Ok(format!("failed to read {}: {}", path.display(), err)),
+        }
+    }
+
+    /// Check params and execute tool.
+    pub fn call_tool(&self, params: &[InputParam]) -> Result<String, AppError> {
+        let mut path = None;
+        let mut max_bytes = DEFAULT_MAX_BYTES;
+
+        for param in params {
+            if param.name == "path" {
+                if let Some(p) = param.value.as_str() {
+                    path = Some(p);
+                } else {
+                    return Ok("wrong parameter value type, \"path\" must be of type string.".to_owned());
+                }
+            } else if param.name == "max_bytes" {
+                if let Some(n) = param.value.as_u64() {
+                    max_bytes = n.min(MAX_BYTES);
+                } else if !param.value.is_null() {
+                    return Ok("wrong parameter value type, \"max_bytes\" must be a non-negative integer.".to_owned());
+                }
+            } else {
+                return Ok(format!("unexpected parameter \"{}\", expect \"path\" and optional \"max_bytes\".", param.name));
+            }
+        }
+
+        if let Some(path) = path {
+            self.read(path, max_bytes)
+        } else {
+            Ok("missing parameter, expect parameter called \"path\" of type string.".to_owned())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_file() {
+        let tool = ReadFile::new(false);
+
+        let content = tool.read("Cargo.toml", 9).expect("read file");
+        assert_eq!(content, format!("[package]{}", crate::util::TRUNCATED_MARKER));
+
+        let content = tool.read("Cargo.toml", 100000).expect("read file");
+        assert!(content.starts_with("[package]") && !content.contains("truncated"));
+
+        let content = tool.read("Cargo.toml", u64::MAX).expect("read file");
+        assert!(content.starts_with("[package]") && !content.contains("truncated"));
+
+        let params = [
+            InputParam { name: "path".to_owned(), value: serde_json::json!("Cargo.toml") },
+            InputParam { name: "max_bytes".to_owned(), value: serde_json::json!(u64::MAX) },
+        ];
+        let content = tool.call_tool(&params).expect("call tool");
+        assert!(content.starts_with("[package]") && !content.contains("truncated"));
+
+        let outside = std::env::temp_dir().join(format!("apprentice_read_file_test_{}.txt", std::process::id()));
+        std::fs::write(&outside, "secret").expect("write temp file");
+
+        let content = tool.read(outside.to_str().unwrap(), 100).expect("read file");
+        assert!(content.starts_with("access denied"));
+
+        let content = ReadFile::new(true).read(outside.to_str().unwrap(), 100).expect("read file");
+        assert_eq!(content, "secret");
+
+        std::fs::remove_file(outside).expect("remove temp file");
+    }
+}
@@ -1,11 +1,18 @@
 use apprentice_lib::tools::{ParamType, ToolParam, ToolSpec};
 use apprentice_lib::llm::ToolParam as InputParam;
+use std::time::Duration;
 use crate::error::AppError;
 use crate::term::Term;
-use crate::util::exec_pipe;
+use crate::util::{exec_pipe, Redactor, SHELL_OPERATORS};
 
 /// Ask user something.
-pub struct Shell {}
+pub struct Shell {
+    dry_run: bool,
+    auto_approve: Vec<String>,
+    timeout: Option<Duration>,
+    max_output: usize,
+    redactor: Option<Redactor>,
+}
 
 impl Shell {
 
@@ -23,34 +30,62 @@ impl Shell {
             name: "SHELL".to_owned(),
             description,
             params: vec![
-                ToolParam {
-                    name: "command".to_string(), 
-                    description: "command to execute".to_string(), 
-                    data_type: ParamType::String, 
-                    required: true
-                }
+                ToolParam::new("command".to_string(), "command to execute".to_string(), ParamType::String, true),
+                ToolParam::new("explanation".to_string(), "short explanation of what the command does, shown to the user before confirmation; provide it for commands that modify or delete anything".to_string(), ParamType::String, false)
             ]
         }
     }
 
     /// Create an instance.
-    pub fn new() -> Self {
-        Shell {}
+    pub fn new(dry_run: bool, auto_approve: Vec<String>, timeout: Option<Duration>, max_output: usize, redactor: Option<Redactor>) -> Self {
+        Shell {
+            dry_run,
+            auto_approve,
+            timeout,
+            max_output,
+            redactor,
+        }
+    }
+
+    /// Check if command matches one of the auto-approved prefixes.
+    /// Commands that chain or redirect with shell operators are never auto-approved.
+    fn is_auto_approved(&self, command: &str) -> bool {
+        let command = command.trim();
+
+        if command.contains(SHELL_OPERATORS) {
+            return false;
+        }
+
+        self.auto_approve.iter().any(|prefix| {
+            let prefix = prefix.trim();
+            !prefix.is_empty() && (command == prefix || command.starts_with(&format!("{} ", prefix)))
+        })
     }
 
     /// Ask user and get reply.
-    pub fn exec(&self, command: &str, term: &mut Term) -> Result<String, AppError> {
-        term.print_tool_message("SHELL", command);
+    pub fn exec(&self, command: &str, explanation: Option<&str>, term: &mut Term) -> Result<String, AppError> {
+        term.print_tool_message("SHELL", command, explanation);
+
+        if self.dry_run {
+            return Ok(format!("DRY RUN: would execute: {}", command));
+        }
+
+        if self.is_auto_approved(command) {
+            term.begin_tool_format();
+            let ret = exec_pipe(command, !term.is_json(), self.timeout, Some(self.max_output), self.redactor.as_ref());
+            term.end_tool_format();
+            return ret;
+        }
 
         loop {
-            let user_input = term.tool_input("SHELL", "Execute command? (y - yes / n - no): ")?;
+            let user_input = term.tool_input("SHELL", "Execute command? (y - yes / n - no / q - quit): ")?;
             let user_input = user_input.trim();
 
             if user_input.len() == 1 {
                 let ret = match user_input {
                     "y" => {
                         term.begin_tool_format();
-                        let ret = exec_pipe(command);
+                        let ret = exec_pipe(command, !term.is_json(), self.timeout, Some(self.max_output), self.redactor.as_ref());
                         term.end_tool_format();
                         ret
                     },
@@ -58,6 +93,7 @@ impl Shell {
                         let reason = term.tool_input("SHELL", "reason: ")?;
                         Ok(format!("User cancelled the operation with the reason: {}", reason))
                     },
+                    "q" => Err(AppError::Aborted),
                     _ => continue
                 };
 
@@ -66,21 +102,65 @@ impl Shell {
         }
     }
 
-    pub fn call_tool(&self, params: &[InputParam], term: &mut Term) -> Result<String, AppError> {
-        if params.len() == 1 {
-            let param = &params[0];
-            if param.name == "command" {
-                if let Some(command) = param.value.as_str() {
-                    self.exec(command, term)
-                } else {
-                    Ok("wrong parameter value type, expect 1 parameter called \"command\" of type string.".to_owned())
-                }
-            } else {
-                Ok("wrong parameter name, expect 1 parameter called \"command\" of type string.".to_owned())
+    /// Return command and optional explanation, or the message describing wrong parameters.
+    fn parse_params(params: &[InputParam]) -> Result<(&str, Option<&str>), &'static str> {
+        let mut command = None;
+        let mut explanation = None;
+
+        for param in params {
+            let value = param.value.as_str();
+            match param.name.as_str() {
+                "command" if value.is_some() => command = value,
+                "explanation" if value.is_some() => explanation = value,
+                "command" | "explanation" => return Err("wrong parameter value type, expect parameters \"command\" and \"explanation\" of type string."),
+                _ => return Err("wrong parameter name, expect parameter called \"command\" and optional parameter called \"explanation\"."),
             }
-        } else {
-            Ok("wrong number of input parameters, expect 1 parameter called \"command\" of type string.".to_owned())
+        }
+
+        match command {
+            Some(command) => Ok((command, explanation.filter(|e| !e.trim().is_empty()))),
+            None => Err("missing parameter, expect parameter called \"command\" of type string."),
         }
     }
 
+    pub fn call_tool(&self, params: &[InputParam], term: &mut Term) -> Result<String, AppError> {
+        match Self::parse_params(params) {
+            Ok((command, explanation)) => self.exec(command, explanation, term),
+            Err(message) => Ok(message.to_owned()),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_auto_approved() {
+        let shell = Shell::new(false, vec!["gcloud compute instances list".to_owned(), "bq show".to_owned()], None, 1024, None);
+
+        assert!(shell.is_auto_approved("gcloud compute instances list"));
+        assert!(shell.is_auto_approved(" gcloud compute instances list --zone=us-east1-b "));
+        assert!(shell.is_auto_approved("bq show dataset"));
+        assert!(!shell.is_auto_approved("bq shows"));
+        assert!(!shell.is_auto_approved("gcloud compute instances delete vm1"));
+        assert!(!shell.is_auto_approved("bq show dataset; rm -rf ~"));
+        assert!(!shell.is_auto_approved("bq show dataset && gcloud compute instances delete vm1"));
+        assert!(!shell.is_auto_approved("bq show $(rm -rf ~)"));
+        assert!(!Shell::new(false, vec![], None, 1024, None).is_auto_approved("bq show"));
+    }
+
+    #[test]
+    fn test_parse_params() {
+        let param = |name: &str, value: serde_json::Value| InputParam { name: name.to_owned(), value };
+
+        assert_eq!(Shell::parse_params(&[param("command", "ls".into())]), Ok(("ls", None)));
+        assert_eq!(Shell::parse_params(&[param("explanation", "Lists files".into()), param("command", "ls".into())]), Ok(("ls", Some("Lists files"))));
+        assert_eq!(Shell::parse_params(&[param("command", "ls".into()), param("explanation", " ".into())]), Ok(("ls", None)));
+        assert!(Shell::parse_params(&[param("explanation", "Lists files".into())]).is_err());
+        assert!(Shell::parse_params(&[param("command", 1.into())]).is_err());
+        assert!(Shell::parse_params(&[param("cmd", "ls".into())]).is_err());
+        assert!(Shell::parse_params(&[]).is_err());
+    }
 }
\ No newline at end of file
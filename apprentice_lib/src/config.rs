@@ -1,7 +1,9 @@
+use std::fmt::Display;
+use std::str::FromStr;
 use crate::error::Error;
 
 /// Model providers.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ModelProvider {
     /// Open AI.
     OpenAI,
@@ -9,6 +11,18 @@ pub enum ModelProvider {
     Anthropic,
     /// GCP.
     GCP,
+    /// DeepSeek (OpenAI-compatible API).
+    DeepSeek,
+    /// Groq (OpenAI-compatible API).
+    Groq,
+    /// Anthropic models on AWS Bedrock.
+    Bedrock,
+    /// Cohere.
+    Cohere,
+    /// OpenRouter (OpenAI-compatible API), model names include the vendor, e.g. `anthropic/claude-3.5-sonnet`.
+    OpenRouter,
+    /// Offline provider that echoes the last message back, for demos and tests. Needs no API key.
+    Echo,
 }
 
 impl TryFrom<&str> for ModelProvider {
@@ -19,12 +33,142 @@ impl TryFrom<&str> for ModelProvider {
             "openai" => Ok(ModelProvider::OpenAI),
             "anthropic" => Ok(ModelProvider::Anthropic),
             "gcp" => Ok(ModelProvider::GCP),
+            "deepseek" => Ok(ModelProvider::DeepSeek),
+            "groq" => Ok(ModelProvider::Groq),
+            "bedrock" => Ok(ModelProvider::Bedrock),
+            "cohere" => Ok(ModelProvider::Cohere),
+            "openrouter" => Ok(ModelProvider::OpenRouter),
+            "echo" => Ok(ModelProvider::Echo),
             _ => Err(Error::Error(format!("unknown provider: {val}"))),
         }
     }
 }
 
+impl ModelProvider {
+    /// Default API URL of the provider for the model.
+    /// Bedrock URL is for the `us-east-1` region, use `url_with_base` with
+    /// `https://bedrock-runtime.<region>.amazonaws.com` for other regions.
+    pub fn default_url(&self, model: &str) -> String {
+        let base = match self {
+            ModelProvider::OpenAI => "https://api.openai.com",
+            ModelProvider::Anthropic => "https://api.anthropic.com",
+            ModelProvider::GCP => "https://generativelanguage.googleapis.com",
+            ModelProvider::DeepSeek => "https://api.deepseek.com",
+            ModelProvider::Groq => "https://api.groq.com",
+            ModelProvider::Bedrock => "https://bedrock-runtime.us-east-1.amazonaws.com",
+            ModelProvider::Cohere => "https://api.cohere.com",
+            ModelProvider::OpenRouter => "https://openrouter.ai",
+            ModelProvider::Echo => "",
+        };
+        self.url_with_base(base, model)
+    }
+
+    /// API URL of the provider for the model with the scheme and the host replaced by `base`,
+    /// e.g. to route the requests through a gateway.
+    pub fn url_with_base(&self, base: &str, model: &str) -> String {
+        let base = base.trim_end_matches('/');
+        match self {
+            ModelProvider::OpenAI | ModelProvider::DeepSeek => format!("{}/v1/chat/completions", base),
+            ModelProvider::Groq => format!("{}/openai/v1/chat/completions", base),
+            // Model ids contain `:`, e.g. `anthropic.claude-3-5-sonnet-20240620-v1:0`.
+            ModelProvider::Bedrock => format!("{}/model/{}/invoke", base, model.replace(':', "%3A")),
+            ModelProvider::Cohere => format!("{}/v2/chat", base),
+            ModelProvider::OpenRouter => format!("{}/api/v1/chat/completions", base),
+            // No requests are sent.
+            ModelProvider::Echo => base.to_owned(),
+            ModelProvider::Anthropic => format!("{}/v1/messages", base),
+            ModelProvider::GCP => format!("{}/v1beta/models/{}:generateContent", base, model),
+        }
+    }
+}
+
+impl FromStr for ModelProvider {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ModelProvider::try_from(s)
+    }
+}
+
+impl Display for ModelProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ModelProvider::OpenAI => "openai",
+            ModelProvider::Anthropic => "anthropic",
+            ModelProvider::GCP => "gcp",
+            ModelProvider::DeepSeek => "deepseek",
+            ModelProvider::Groq => "groq",
+            ModelProvider::Bedrock => "bedrock",
+            ModelProvider::Cohere => "cohere",
+            ModelProvider::OpenRouter => "openrouter",
+            ModelProvider::Echo => "echo",
+        })
+    }
+}
+
+/// Parameters that can be omitted with `Config::omit`.
+const OPTIONAL_PARAMS: [&str; 11] = ["max_tokens", "n", "temperature", "top_p", "top_k", "frequency_penalty",
+    "presence_penalty", "stop_sequences", "reasoning_effort", "thinking_budget", "logprobs"];
+
+/// AWS credentials for signing the requests (Bedrock only).
+#[derive(Clone, Debug)]
+pub struct AwsCredentials {
+    /// Access key id.
+    pub access_key_id: String,
+    /// Secret access key.
+    pub secret_access_key: String,
+    /// Session token of temporary credentials.
+    pub session_token: Option<String>,
+    /// Region, e.g. `us-east-1`.
+    pub region: String,
+}
+
+/// How the API key is passed to the provider.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthMode {
+    /// Provider specific way: `Authorization` header for OpenAI, `x-api-key` header for Anthropic,
+    /// `key` query parameter for GCP.
+    ApiKey,
+    /// `api_key` is an OAuth access token sent in the `Authorization: Bearer` header, e.g. for Vertex AI (GCP only).
+    Bearer,
+}
+
+impl TryFrom<&str> for AuthMode {
+    type Error = Error;
+
+    fn try_from(val: &str) -> Result<Self, Self::Error> {
+        match val {
+            "api_key" => Ok(AuthMode::ApiKey),
+            "bearer" => Ok(AuthMode::Bearer),
+            _ => Err(Error::Error(format!("unknown auth mode: {val}"))),
+        }
+    }
+}
+
 /// Model parameters.
+/// 
+/// Not every provider honors every parameter:
+/// 
+/// - OpenAI ignores `api_version` and `top_k`,
+/// - DeepSeek ignores the same parameters as OpenAI, and `n`, which is not sent,
+/// - Bedrock ignores the same parameters as Anthropic, uses `aws_credentials` instead of `api_key`,
+///   and `api_version` defaults to `bedrock-2023-05-31`,
+/// - Cohere ignores `api_version` and `n`,
+/// - OpenRouter ignores the same parameters as OpenAI, and `reasoning_effort`,
+/// - Groq ignores the same parameters as OpenAI, `n`, and `logprobs`, which it rejects, so they are not sent,
+/// - Anthropic ignores `frequency_penalty` and `presence_penalty`, and makes a separate request for each of `n` variants,
+/// - GCP ignores `api_version`,
+/// - Echo ignores all the parameters,
+/// - `reasoning_effort` is used only by OpenAI, `thinking_budget` only by Anthropic and GCP,
+///   both are omitted for models that don't support reasoning,
+/// - `prompt_caching` is used only by Anthropic,
+/// - `non_streaming_max_tokens` is used only by Anthropic (not Bedrock),
+/// - `safety_settings` are used only by GCP,
+/// - `logprobs` is used only by OpenAI,
+/// - `auth_mode` is used only by GCP.
+/// 
+/// Use `Config::unsupported_fields` to detect the parameters that are set but will be ignored,
+/// and `Config::omit` to suppress the parameters rejected by a backend.
 #[derive(Clone, Debug)]
 pub struct Config {
     /// Model name.
@@ -33,6 +177,8 @@ pub struct Config {
     pub name: String,
     /// API key.
     pub api_key: String,
+    /// How the API key is passed to the provider.
+    pub auth_mode: AuthMode,
     /// Model API URL.
     pub api_url: String,
     /// Model API version.
@@ -52,18 +198,42 @@ pub struct Config {
     /// Penalize new tokens based on whether they appear in the text so far.
     pub presence_penalty: Option<f64>,
     /// Sequences at which model will stop generating.
-    pub stop_sequence: Option<String>,
+    pub stop_sequences: Option<Vec<String>>,
+    /// Reasoning effort for reasoning models, e.g. `low`, `medium`, `high` (OpenAI only).
+    pub reasoning_effort: Option<String>,
+    /// Maximum number of tokens the model can use for thinking (Anthropic and GCP).
+    pub thinking_budget: Option<i64>,
+    /// Mark the system prompt and tool definitions as cacheable (Anthropic only).
+    pub prompt_caching: bool,
+    /// Safety filter thresholds by harm category (GCP only),
+    /// e.g. `("HARM_CATEGORY_DANGEROUS_CONTENT", "BLOCK_ONLY_HIGH")`.
+    pub safety_settings: Vec<(String, String)>,
+    /// Return log probabilities of the output tokens with this number of the most likely
+    /// alternatives for each token, `Some(0)` returns only the chosen tokens (OpenAI only).
+    pub logprobs: Option<i64>,
+    /// AWS credentials (Bedrock only).
+    pub aws_credentials: Option<AwsCredentials>,
+    /// Headers added to each request, e.g. for a gateway.
+    pub extra_headers: Vec<(String, String)>,
+    /// Parameters that are not sent even if set, by field name, see `Config::omit`.
+    pub omit_params: Vec<String>,
+    /// Largest `max_tokens` of a non-streaming request, requests with more are streamed,
+    /// as Anthropic rejects long non-streaming requests; `None` means 21333 (Anthropic only).
+    pub non_streaming_max_tokens: Option<i64>,
 }
 
 
 impl Config {
 
     /// Create minimal config using provider, model name, API key, and API URL.
+    /// Empty API URL is replaced with the provider's default URL.
     pub fn new(provider: ModelProvider, name: String, api_key: String, api_url: String) -> Self {
+        let api_url = if api_url.is_empty() { provider.default_url(&name) } else { api_url };
         Config {
             provider,
             name,
             api_key,
+            auth_mode: AuthMode::ApiKey,
             api_url,
             api_version: None,
             max_tokens: None,
@@ -73,7 +243,166 @@ impl Config {
             top_k: None,
             frequency_penalty: None,
             presence_penalty: None,
-            stop_sequence: None
+            stop_sequences: None,
+            reasoning_effort: None,
+            thinking_budget: None,
+            prompt_caching: false,
+            safety_settings: vec![],
+            logprobs: None,
+            aws_credentials: None,
+            extra_headers: vec![],
+            omit_params: vec![],
+            non_streaming_max_tokens: None,
         }
     }
+
+    /// Don't send the optional `params`, given by field name, e.g. `top_p`,
+    /// for OpenAI-compatible servers that reject them, while keeping their values in the config.
+    pub fn omit(&mut self, params: &[&str]) -> Result<(), Error> {
+        for param in params {
+            if !OPTIONAL_PARAMS.contains(param) {
+                return Err(Error::Error(format!("unknown optional parameter: {param}")));
+            }
+            self.omit_params.push(param.to_string());
+        }
+        Ok(())
+    }
+
+    /// Copy of the config with the parameters listed in `omit_params` unset.
+    pub(crate) fn without_omitted(mut self) -> Self {
+        for param in std::mem::take(&mut self.omit_params) {
+            match param.as_str() {
+                "max_tokens" => self.max_tokens = None,
+                "n" => self.n = None,
+                "temperature" => self.temperature = None,
+                "top_p" => self.top_p = None,
+                "top_k" => self.top_k = None,
+                "frequency_penalty" => self.frequency_penalty = None,
+                "presence_penalty" => self.presence_penalty = None,
+                "stop_sequences" => self.stop_sequences = None,
+                "reasoning_effort" => self.reasoning_effort = None,
+                "thinking_budget" => self.thinking_budget = None,
+                "logprobs" => self.logprobs = None,
+                _ => {},
+            }
+        }
+        self
+    }
+
+    /// Return names of the parameters that are set, but are not supported by the provider.
+    pub fn unsupported_fields(&self, provider: ModelProvider) -> Vec<&'static str> {
+        let mut fields = vec![];
+
+        match provider {
+            ModelProvider::OpenAI | ModelProvider::OpenRouter => {
+                if self.api_version.is_some() { fields.push("api_version"); }
+                if self.top_k.is_some() { fields.push("top_k"); }
+                if self.thinking_budget.is_some() { fields.push("thinking_budget"); }
+                if self.prompt_caching { fields.push("prompt_caching"); }
+                if !self.safety_settings.is_empty() { fields.push("safety_settings"); }
+                if self.auth_mode != AuthMode::ApiKey { fields.push("auth_mode"); }
+                if provider == ModelProvider::OpenRouter && self.reasoning_effort.is_some() { fields.push("reasoning_effort"); }
+                if self.non_streaming_max_tokens.is_some() { fields.push("non_streaming_max_tokens"); }
+            },
+            ModelProvider::Anthropic | ModelProvider::Bedrock => {
+                if self.frequency_penalty.is_some() { fields.push("frequency_penalty"); }
+                if self.presence_penalty.is_some() { fields.push("presence_penalty"); }
+                if self.reasoning_effort.is_some() { fields.push("reasoning_effort"); }
+                if !self.safety_settings.is_empty() { fields.push("safety_settings"); }
+                if self.auth_mode != AuthMode::ApiKey { fields.push("auth_mode"); }
+                if self.logprobs.is_some() { fields.push("logprobs"); }
+                if provider == ModelProvider::Bedrock && self.non_streaming_max_tokens.is_some() { fields.push("non_streaming_max_tokens"); }
+            },
+            ModelProvider::GCP => {
+                if self.api_version.is_some() { fields.push("api_version"); }
+                if self.reasoning_effort.is_some() { fields.push("reasoning_effort"); }
+                if self.prompt_caching { fields.push("prompt_caching"); }
+                if self.logprobs.is_some() { fields.push("logprobs"); }
+                if self.non_streaming_max_tokens.is_some() { fields.push("non_streaming_max_tokens"); }
+            },
+            ModelProvider::DeepSeek => {
+                if self.api_version.is_some() { fields.push("api_version"); }
+                if self.n.is_some() { fields.push("n"); }
+                if self.top_k.is_some() { fields.push("top_k"); }
+                if self.reasoning_effort.is_some() { fields.push("reasoning_effort"); }
+                if self.thinking_budget.is_some() { fields.push("thinking_budget"); }
+                if self.prompt_caching { fields.push("prompt_caching"); }
+                if !self.safety_settings.is_empty() { fields.push("safety_settings"); }
+                if self.auth_mode != AuthMode::ApiKey { fields.push("auth_mode"); }
+                if self.non_streaming_max_tokens.is_some() { fields.push("non_streaming_max_tokens"); }
+            },
+            ModelProvider::Groq => {
+                if self.api_version.is_some() { fields.push("api_version"); }
+                if self.n.is_some() { fields.push("n"); }
+                if self.top_k.is_some() { fields.push("top_k"); }
+                if self.thinking_budget.is_some() { fields.push("thinking_budget"); }
+                if self.prompt_caching { fields.push("prompt_caching"); }
+                if !self.safety_settings.is_empty() { fields.push("safety_settings"); }
+                if self.auth_mode != AuthMode::ApiKey { fields.push("auth_mode"); }
+                if self.logprobs.is_some() { fields.push("logprobs"); }
+                if self.non_streaming_max_tokens.is_some() { fields.push("non_streaming_max_tokens"); }
+            },
+            ModelProvider::Cohere => {
+                if self.api_version.is_some() { fields.push("api_version"); }
+                if self.n.is_some() { fields.push("n"); }
+                if self.reasoning_effort.is_some() { fields.push("reasoning_effort"); }
+                if self.thinking_budget.is_some() { fields.push("thinking_budget"); }
+                if self.prompt_caching { fields.push("prompt_caching"); }
+                if !self.safety_settings.is_empty() { fields.push("safety_settings"); }
+                if self.auth_mode != AuthMode::ApiKey { fields.push("auth_mode"); }
+                if self.logprobs.is_some() { fields.push("logprobs"); }
+                if self.non_streaming_max_tokens.is_some() { fields.push("non_streaming_max_tokens"); }
+            },
+            // Echo doesn't call a model, so ignoring the parameters changes nothing.
+            ModelProvider::Echo => {},
+        }
+
+        fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_round_trip() {
+        for provider in [ModelProvider::OpenAI, ModelProvider::Anthropic, ModelProvider::GCP, ModelProvider::DeepSeek, ModelProvider::Groq, ModelProvider::Bedrock, ModelProvider::Cohere, ModelProvider::OpenRouter, ModelProvider::Echo] {
+            assert_eq!(ModelProvider::from_str(&provider.to_string()).expect("parse provider"), provider);
+        }
+        assert!("openai2".parse::<ModelProvider>().is_err());
+    }
+
+    #[test]
+    fn test_default_url() {
+        assert_eq!(ModelProvider::OpenAI.default_url("gpt-4"), "https://api.openai.com/v1/chat/completions");
+        assert_eq!(ModelProvider::DeepSeek.default_url("deepseek-chat"), "https://api.deepseek.com/v1/chat/completions");
+        assert_eq!(ModelProvider::Groq.default_url("llama-3.3-70b-versatile"), "https://api.groq.com/openai/v1/chat/completions");
+        assert_eq!(ModelProvider::Bedrock.default_url("anthropic.claude-v2:1"),
+            "https://bedrock-runtime.us-east-1.amazonaws.com/model/anthropic.claude-v2%3A1/invoke");
+        assert_eq!(ModelProvider::OpenRouter.default_url("anthropic/claude-3.5-sonnet"), "https://openrouter.ai/api/v1/chat/completions");
+        assert_eq!(ModelProvider::Anthropic.default_url("claude"), "https://api.anthropic.com/v1/messages");
+        assert_eq!(ModelProvider::GCP.default_url("gemini"), "https://generativelanguage.googleapis.com/v1beta/models/gemini:generateContent");
+        assert_eq!(ModelProvider::GCP.url_with_base("http://localhost:8080/", "gemini"), "http://localhost:8080/v1beta/models/gemini:generateContent");
+
+        let config = Config::new(ModelProvider::GCP, "gemini".to_owned(), "<api-key>".to_owned(), String::new());
+        assert_eq!(config.api_url, "https://generativelanguage.googleapis.com/v1beta/models/gemini:generateContent");
+
+        let config = Config::new(ModelProvider::GCP, "gemini".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        assert_eq!(config.api_url, "<api-uri>");
+    }
+
+    #[test]
+    fn test_unsupported_fields() {
+        let mut config = Config::new(ModelProvider::Anthropic, "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        config.api_version = Some("<api-ver>".to_owned());
+        config.top_k = Some(5);
+        config.presence_penalty = Some(0.22);
+        config.auth_mode = AuthMode::Bearer;
+        config.logprobs = Some(0);
+
+        assert_eq!(config.unsupported_fields(ModelProvider::OpenAI), vec!["api_version", "top_k", "auth_mode"]);
+        assert_eq!(config.unsupported_fields(ModelProvider::Anthropic), vec!["presence_penalty", "auth_mode", "logprobs"]);
+        assert_eq!(config.unsupported_fields(ModelProvider::GCP), vec!["api_version", "logprobs"]);
+    }
 }
\ No newline at end of file
@@ -19,6 +19,17 @@ pub enum Error {
     #[error("Failed to parse LLM response: {0}")]
     LLMResponseError(&'static str),
 
+    /// Tool call arguments returned by LLM are not a JSON object.
+    #[error("Failed to parse arguments of tool {name}: {message}, arguments: {arguments}")]
+    ToolArgumentsError {
+        /// Tool name.
+        name: String,
+        /// Arguments as returned by LLM.
+        arguments: String,
+        /// Parsing error.
+        message: String,
+    },
+
     /// General error.
     #[error("{0}")]
     Error(String),
@@ -27,6 +38,38 @@ pub enum Error {
     #[error("LLM provider responded with error: {0}")]
     LLMErrorMessage(String),
 
+    /// LLM response error with the provider-specific details,
+    /// e.g. code `insufficient_quota` for OpenAI, or `API_KEY_INVALID` for GCP.
+    #[error("LLM provider responded with error: {message}")]
+    ProviderError {
+        /// Error code, e.g. `insufficient_quota` (OpenAI), `API_KEY_INVALID` (GCP).
+        code: Option<String>,
+        /// Error type, e.g. `authentication_error` (Anthropic), `INVALID_ARGUMENT` (GCP).
+        kind: Option<String>,
+        /// Error message.
+        message: String,
+    },
+
+    /// Prompt was blocked by the provider.
+    #[error("LLM provider blocked the prompt, reason: {0}")]
+    PromptBlocked(String),
+
+    /// LLM stopped generating without returning any content.
+    #[error("LLM provider returned no content, finish reason: {0}")]
+    ResponseStopped(String),
+
+    /// LLM response has the expected format, but contains no choices, candidates, or content.
+    #[error("LLM provider returned an empty response")]
+    EmptyResponse,
+
+    /// Tool loop was aborted by the approval callback.
+    #[error("Tool call was aborted")]
+    Aborted,
+
+    /// Request was cancelled with the cancellation token.
+    #[error("Request was cancelled")]
+    Cancelled,
+
     /// LLM response error message.
     #[cfg(test)]
     #[error("Test error: {0}")]
@@ -39,4 +82,65 @@ pub enum Error {
     /// Hf API error.
     #[error("Huggingface hub API call: {0}")]
     HfApiCall(#[from] hf_hub::api::sync::ApiError),
-}
\ No newline at end of file
+}
+
+/// Provider error types and codes of rate limits, overloaded, and internal server errors.
+const RETRYABLE_PROVIDER_ERRORS: [&str; 13] = [
+    // OpenAI
+    "server_error", "rate_limit_exceeded",
+    // Anthropic
+    "rate_limit_error", "overloaded_error", "api_error",
+    // GCP
+    "RESOURCE_EXHAUSTED", "UNAVAILABLE", "INTERNAL", "DEADLINE_EXCEEDED", "429", "500", "503", "504",
+];
+
+impl Error {
+    /// Whether repeating the same request may succeed.
+    ///
+    /// - `LLMCallError`: timeouts, connection errors, and responses with 429 or 5xx status are retryable.
+    /// - `ProviderError`: rate limits, overloaded and internal server errors are retryable;
+    ///   exceeded quota, authentication, and invalid request errors are not.
+    /// - Other variants are not retryable: invalid arguments, malformed responses, blocked prompts, etc.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::LLMCallError(err) => {
+                err.is_timeout()
+                    || err.is_connect()
+                    || err.status().is_some_and(|status| status.as_u16() == 429 || status.is_server_error())
+            },
+            Error::ProviderError { code, kind, .. } => {
+                code.as_deref() != Some("insufficient_quota")
+                    && [code, kind].into_iter().flatten().any(|val| RETRYABLE_PROVIDER_ERRORS.contains(&val.as_str()))
+            },
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider_error(code: Option<&str>, kind: Option<&str>) -> Error {
+        Error::ProviderError { code: code.map(str::to_owned), kind: kind.map(str::to_owned), message: String::new() }
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(provider_error(Some("rate_limit_exceeded"), Some("requests")).is_retryable());
+        assert!(provider_error(None, Some("overloaded_error")).is_retryable());
+        assert!(provider_error(Some("503"), Some("UNAVAILABLE")).is_retryable());
+
+        assert!(!provider_error(Some("insufficient_quota"), Some("insufficient_quota")).is_retryable());
+        assert!(!provider_error(None, Some("authentication_error")).is_retryable());
+        assert!(!provider_error(Some("API_KEY_INVALID"), Some("INVALID_ARGUMENT")).is_retryable());
+
+        assert!(!Error::LLMErrorMessage("error".to_owned()).is_retryable());
+        assert!(!Error::LLMResponseError("unexpected message type.").is_retryable());
+        assert!(!Error::PromptBlocked("SAFETY".to_owned()).is_retryable());
+
+        // Nothing listens on port 1, so the request fails with a connection error.
+        let err = reqwest::blocking::Client::new().post("http://127.0.0.1:1").send().expect_err("connection error");
+        assert!(Error::LLMCallError(err).is_retryable());
+    }
+}
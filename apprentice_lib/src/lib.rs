@@ -49,7 +49,9 @@
 #![allow(clippy::comparison_chain)]
 #![allow(clippy::collapsible_else_if)]
 #![allow(clippy::collapsible_if)]
+#![cfg_attr(test, allow(clippy::vec_init_then_push))]
 
+mod logging;
 mod error;
 mod config;
 pub mod rag;
@@ -59,4 +61,6 @@ pub mod request;
 
 pub use error::Error;
 pub use config::Config;
-pub use config::ModelProvider;
\ No newline at end of file
+pub use config::ModelProvider;
+pub use config::AuthMode;
+pub use config::AwsCredentials;
\ No newline at end of file
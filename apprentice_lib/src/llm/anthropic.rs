@@ -1,14 +1,118 @@
 use crate::llm::util::tool_params_to_value;
 use crate::llm::{LLMChat, Role};
-use crate::config::Config;
+use crate::config::{Config, ModelProvider};
 use crate::error::Error;
+use crate::logging::log_debug;
 use crate::tools::{ToolChoice, ToolSpec};
 use crate::val_as_str;
+use std::collections::HashMap;
 use serde_json::{json, Value};
-use crate::request::Client;
+use crate::request::{sigv4, Client};
 use super::messages::Text;
-use super::{Message, ToolCall, ToolParam};
-use super::util::{self, llm_to_role, role_to_llm};
+use super::{Message, StopReason, ToolCall, ToolParam, Usage};
+use super::util::{self, llm_to_error, llm_to_role, llm_to_stop_reason, llm_to_usage, role_to_llm, supports_reasoning, Candidates};
+
+/// Anthropic API version of the requests to Bedrock.
+const BEDROCK_API_VERSION: &str = "bedrock-2023-05-31";
+
+/// Largest `max_tokens` of a non-streaming request unless set with `Config::non_streaming_max_tokens`.
+const NON_STREAMING_MAX_TOKENS: i64 = 21_333;
+
+// History entries, messages, and reasoning of a response.
+type ProcessedResponse = (Vec<Value>, Vec<Message>, Option<String>);
+
+/// Assembles the response from the server-sent events of a streamed message.
+#[derive(Default)]
+struct StreamedMessage {
+    response: Value,
+    // `input_json_delta` fragments of the tool calls by the content block index.
+    tool_inputs: HashMap<usize, String>,
+    // Lines that are not events, e.g. the JSON body of an error response.
+    body: String,
+}
+
+impl StreamedMessage {
+
+    fn push(&mut self, line: &str, on_delta: &mut dyn FnMut(&Message)) -> Result<(), Error> {
+        let Some(data) = line.strip_prefix("data:") else {
+            if !line.starts_with("event:") && !line.starts_with(':') {
+                self.body.push_str(line);
+            }
+            return Ok(());
+        };
+
+        let event: Value = serde_json::from_str(data.trim())?;
+        let index = event["index"].as_u64().unwrap_or(0) as usize;
+
+        match event["type"].as_str() {
+            Some("message_start") => self.response = event["message"].clone(),
+            Some("content_block_start") => {
+                let content = self.response["content"]
+                    .as_array_mut()
+                    .ok_or(Error::LLMResponseError("content block before the message start."))?;
+                if content.len() <= index {
+                    content.resize(index + 1, Value::Null);
+                }
+                content[index] = event["content_block"].clone();
+            },
+            Some("content_block_delta") => {
+                let block = self.response["content"]
+                    .get_mut(index)
+                    .ok_or(Error::LLMResponseError("delta of an unknown content block."))?;
+                let delta = &event["delta"];
+                match delta["type"].as_str() {
+                    Some("text_delta") => {
+                        let text = delta["text"].as_str().unwrap_or_default();
+                        block["text"] = Value::String(block["text"].as_str().unwrap_or_default().to_owned() + text);
+                        on_delta(&Message::text(Role::Model, text.to_owned()));
+                    },
+                    Some("input_json_delta") => {
+                        self.tool_inputs.entry(index).or_default().push_str(delta["partial_json"].as_str().unwrap_or_default());
+                    },
+                    Some("thinking_delta") => {
+                        let text = delta["thinking"].as_str().unwrap_or_default();
+                        block["thinking"] = Value::String(block["thinking"].as_str().unwrap_or_default().to_owned() + text);
+                    },
+                    Some("signature_delta") => block["signature"] = delta["signature"].clone(),
+                    _ => {},
+                }
+            },
+            Some("content_block_stop") => {
+                // Tools without parameters get no fragments, and keep the empty input of the block start.
+                if let Some(input) = self.tool_inputs.remove(&index).filter(|input| !input.is_empty()) {
+                    if let Some(block) = self.response["content"].get_mut(index) {
+                        block["input"] = serde_json::from_str(&input)?;
+                    }
+                }
+            },
+            Some("message_delta") => {
+                for (key, value) in event["delta"].as_object().into_iter().flatten() {
+                    self.response[key] = value.clone();
+                }
+                // Output tokens are cumulative.
+                for (key, value) in event["usage"].as_object().into_iter().flatten() {
+                    self.response["usage"][key] = value.clone();
+                }
+            },
+            Some("error") => self.response = event,
+            // `ping`, `message_stop`.
+            _ => {},
+        }
+
+        Ok(())
+    }
+
+    /// Response in the format of the non-streamed message.
+    fn finish(self) -> Result<Value, Error> {
+        if !self.response.is_null() {
+            Ok(self.response)
+        } else if !self.body.is_empty() {
+            Ok(serde_json::from_str(&self.body)?)
+        } else {
+            Err(Error::EmptyResponse)
+        }
+    }
+}
 
 pub struct AnthropicChat {
     system_prompt: String,
@@ -16,11 +120,16 @@ pub struct AnthropicChat {
     config: Config,
     client: Box<dyn Client>,
     tools: Vec<ToolSpec>,
+    candidates: Candidates,
 }
 
 impl AnthropicChat {
     pub(super) fn new(config: Config, client: Box<dyn Client>, tools: Vec<ToolSpec>) -> Result<Self, Error> {
-        if config.api_version.is_none() {
+        if config.provider == ModelProvider::Bedrock {
+            if config.aws_credentials.is_none() {
+                return Err(Error::MissingArgError("aws credentials are mandatory for bedrock."))
+            }
+        } else if config.api_version.is_none() {
             return Err(Error::MissingArgError("api-version is mandatory for anthropic."))
         }
         if config.max_tokens.is_none() {
@@ -33,19 +142,40 @@ impl AnthropicChat {
             config,
             client,
             tools,
+            candidates: Candidates::default(),
         })
     }
 
-    fn prep_payload(&mut self, messages: &[Message], tools: ToolChoice) -> Value {
+    // Anthropic rejects non-streaming requests that may take too long, so large `max_tokens` are streamed.
+    fn requires_streaming(&self) -> bool {
+        let limit = self.config.non_streaming_max_tokens.unwrap_or(NON_STREAMING_MAX_TOKENS);
+        self.config.provider == ModelProvider::Anthropic && self.config.max_tokens.is_some_and(|max_tokens| max_tokens > limit)
+    }
 
+    // Send the streaming request, and return the streamed events merged into a regular response.
+    fn stream_response(&self, mut payload: Value, headers: &[(&str, &str)], on_delta: &mut dyn FnMut(&Message)) -> Result<Value, Error> {
+        payload["stream"] = Value::Bool(true);
+        // The headers are created without the `stream` field, which is fine as only Bedrock signs the payload.
+        let mut message = StreamedMessage::default();
+        self.client.make_streaming_request(&self.config.api_url, payload, headers, &[], &mut |line| message.push(line, on_delta))?;
+        message.finish()
+    }
+
+    fn push_messages(&mut self, messages: &[Message]) {
         for message in messages {
-            if let Message::Text(txt) = message {
+            if let Message::Text(Text {role: Role::System, message}) = message {
+                // Anthropic has no system messages in the conversation.
+                self.history.push(json!({
+                    "role": role_to_llm(self.config.provider, Role::User),
+                    "content": format!("System instruction: {}", message)
+                }));
+            } else if let Message::Text(txt) = message {
                 self.history.push(json!({
                     "role": role_to_llm(self.config.provider, txt.role),
                     "content": txt.message
                 }));
             } else if let Message::ToolResult(res) = message {
-                self.history.push(json!({
+                util::push_tool_result(&mut self.history, json!({
                     "role": role_to_llm(self.config.provider, Role::User),
                     "content": [
                         {
@@ -57,12 +187,48 @@ impl AnthropicChat {
                 }));
             }
         }
+    }
+
+    fn prep_payload(&mut self, messages: &[Message], tools: ToolChoice) -> Value {
+
+        self.push_messages(messages);
+
+        // Prefill must not end with whitespace.
+        if util::has_prefill(messages) {
+            if let Some(Value::String(content)) = self.history.last_mut().map(|entry| &mut entry["content"]) {
+                content.truncate(content.trim_end().len());
+            }
+        }
 
         let mut payload = json!({
-            "model": self.config.name,
             "system": self.system_prompt,
         });
 
+        // Bedrock takes the model from the URL, and the API version from the payload.
+        if self.config.provider == ModelProvider::Bedrock {
+            let api_ver = self.config.api_version.as_deref().unwrap_or(BEDROCK_API_VERSION);
+            payload["anthropic_version"] = Value::String(api_ver.to_owned());
+        } else {
+            payload["model"] = Value::String(self.config.name.clone());
+        }
+
+        if let Some(budget) = self.config.thinking_budget {
+            if supports_reasoning(self.config.provider, &self.config.name) {
+                payload["thinking"] = json!({
+                    "type": "enabled",
+                    "budget_tokens": budget
+                });
+            }
+        }
+
+        if self.config.prompt_caching {
+            payload["system"] = json!([{
+                "type": "text",
+                "text": self.system_prompt,
+                "cache_control": {"type": "ephemeral"}
+            }]);
+        }
+
         payload["messages"] = Value::Array(self.history.clone());
 
         util::set_i64_param(&mut payload, "max_tokens", &self.config.max_tokens);
@@ -70,8 +236,8 @@ impl AnthropicChat {
         util::set_i64_param(&mut payload, "top_k", &self.config.top_k);
         util::set_f64_param(&mut payload, "temperature", &self.config.temperature);
 
-        if let Some(val) = &self.config.stop_sequence {
-            payload["stop_sequences"] = Value::Array(vec![Value::String(val.clone())]);
+        if let Some(val) = &self.config.stop_sequences {
+            payload["stop_sequences"] = json!(val);
         }
 
         self.prep_tool_use(&mut payload, tools);
@@ -87,14 +253,14 @@ impl AnthropicChat {
                     "type": "auto",
                     "disable_parallel_tool_use": true,
                 });
-                self.add_tools(payload);
+                self.add_tools(payload, |_| true);
             },
             ToolChoice::CallOne => {
                 payload["tool_choice"] = json!({
                     "type": "any",
                     "disable_parallel_tool_use": true,
                 });
-                self.add_tools(payload);
+                self.add_tools(payload, |_| true);
             },
             ToolChoice::Force(tool) => {
                 payload["tool_choice"] = json!({
@@ -102,45 +268,93 @@ impl AnthropicChat {
                     "name": tool,
                     "disable_parallel_tool_use": true,
                 });
-                self.add_tools(payload);
+                self.add_tools(payload, |_| true);
+            },
+            ToolChoice::Except(excluded) => {
+                payload["tool_choice"] = json!({
+                    "type": "auto",
+                    "disable_parallel_tool_use": true,
+                });
+                self.add_tools(payload, |name| !excluded.iter().any(|excl| excl == name));
+            },
+            ToolChoice::Subset(allowed) => {
+                payload["tool_choice"] = json!({
+                    "type": "auto",
+                    "disable_parallel_tool_use": true,
+                });
+                self.add_tools(payload, |name| allowed.iter().any(|allow| allow == name));
             },
         };
     }
 
-    fn add_tools(&self, payload: &mut Value) {
-        let mut arr = Vec::with_capacity(self.tools.len());
-        for spec in self.tools.iter() {
-            arr.push(json!({
-                "description": spec.description,
-                "name": spec.name,
-                "input_schema": tool_params_to_value(&spec.params, self.config.provider)
-            }));
+    fn add_tools(&self, payload: &mut Value, include: impl Fn(&str) -> bool) {
+        let mut tools = Self::tools_to_json(self.tools.iter().filter(|spec| include(&spec.name)), self.config.provider);
+        if self.config.prompt_caching {
+            // Cache breakpoint on the last tool caches all tool definitions.
+            if let Some(last) = tools.as_array_mut().and_then(|arr| arr.last_mut()) {
+                last["cache_control"] = json!({"type": "ephemeral"});
+            }
         }
-        payload["tools"] = Value::Array(arr);
+        payload["tools"] = tools;
+    }
+
+    pub(super) fn tools_to_json<'a>(tools: impl Iterator<Item = &'a ToolSpec>, provider: ModelProvider) -> Value {
+        let arr = tools.map(|spec| json!({
+            "description": spec.description,
+            "name": spec.name,
+            "input_schema": tool_params_to_value(&spec.params, provider)
+        })).collect();
+        Value::Array(arr)
+    }
+
+    // Authentication headers, Bedrock requests are signed along with the payload.
+    fn auth_headers(&self, payload: &Value) -> Result<Vec<(String, String)>, Error> {
+        Ok(match (self.config.provider, &self.config.aws_credentials) {
+            // The transport serializes the payload the same way, so the signature matches the body.
+            (ModelProvider::Bedrock, Some(credentials)) => sigv4::sign(credentials, "bedrock",
+                &self.config.api_url, payload.to_string().as_bytes(), std::time::SystemTime::now())?,
+            _ => vec![
+                ("x-api-key".to_owned(), self.config.api_key.clone()),
+                ("anthropic-version".to_owned(), self.config.api_version.clone().unwrap_or_default()),
+            ],
+        })
     }
 
     fn check_for_error(&self, response: &Value) -> Result<(), Error> {
         if let Some(error) = response.get("error") {
-            let errmes = val_as_str!(error["message"], "error message").to_owned();
-            return Err(Error::LLMErrorMessage(errmes));
+            return Err(llm_to_error(self.config.provider, error)?);
+        }
+        // Bedrock errors have only the message.
+        if self.config.provider == ModelProvider::Bedrock && response.get("content").is_none() {
+            if let Some(message) = response["message"].as_str().or(response["Message"].as_str()) {
+                return Err(Error::LLMErrorMessage(message.to_owned()));
+            }
         }
         Ok(())
     }
 
-    fn process_response(&mut self, response: Value) -> Result<Vec<Message>, Error> {
+    fn process_response(&self, response: Value) -> Result<ProcessedResponse, Error> {
 
-        self.check_for_error(&response)?;
+        self.check_for_error(&response)
+            .inspect_err(|err| log_debug!("{} returned error: {}", self.config.provider, err))?;
 
+        let mut entries = Vec::new();
         let mut result = Vec::new();
+        let mut thinking: Option<String> = None;
 
         let role = val_as_str!(response["role"], "role");
         let role = llm_to_role(role)?;
 
-        for msg in response["content"]
+        let content = response["content"]
             .as_array()
-            .ok_or(Error::LLMResponseError("can't enumerate messages in the response."))?
-        {
-            self.history.push(json!({
+            .ok_or(Error::LLMResponseError("can't enumerate messages in the response."))?;
+
+        if content.is_empty() {
+            return Err(Error::EmptyResponse);
+        }
+
+        for msg in content {
+            entries.push(json!({
                 "role": &response["role"],
                 "content": [&msg]
             }));
@@ -168,47 +382,166 @@ impl AnthropicChat {
                     params.push(ToolParam {name, value});
                 }
 
-                result.push(Message::ToolCall(ToolCall{call_id, name, params}));
+                let raw_arguments = Some(msg["input"].to_string());
+                result.push(Message::ToolCall(ToolCall{call_id, name, params, raw_arguments}));
 
-            } else {
+            } else if "thinking" == msg_type {
+
+                let text = val_as_str!(msg["thinking"], "thinking");
+                thinking = Some(match thinking {
+                    Some(prev) => prev + "\n" + text,
+                    None => text.to_owned(),
+                });
+
+            } else if "redacted_thinking" != msg_type {
                 return Err(Error::LLMResponseError("unexpected message type."))
             }
         }
 
-        Ok(result)
+        log_debug!("{} returned {} content block(s), stop reason {:?}", self.config.provider, content.len(), response["stop_reason"]);
+
+        Ok((entries, result, thinking))
     }
 }
 
 impl LLMChat for AnthropicChat {
 
-    fn get_inference(&mut self, messages: &[Message], tools: ToolChoice) -> Result<Vec<Message>, Error> {
+    fn get_candidates(&mut self, messages: &[Message], tools: ToolChoice) -> Result<Vec<Vec<Message>>, Error> {
 
         let payload = self.prep_payload(messages, tools);
+        let stream = self.requires_streaming();
 
-        let api_ver: &str = self.config.api_version.as_ref().unwrap();
-        let headers = &[
-            ("x-api-key", self.config.api_key.as_ref()),
-            ("anthropic-version", api_ver),
-        ];
+        let headers = self.auth_headers(&payload)?;
+        let headers: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        let headers = util::with_extra_headers(&headers, &self.config);
+        let headers = headers.as_slice();
+
+        // Anthropic API returns a single response, so each candidate takes a separate request.
+        let n = self.config.n.unwrap_or(1).max(1);
+
+        let mut candidates = Vec::new();
+        let mut entries = Vec::new();
+        let mut stop_reasons = Vec::new();
+        let mut reasoning = Vec::new();
+        let mut usage: Option<Usage> = None;
+
+        for _ in 0..n {
+            let response = if stream {
+                self.stream_response(payload.clone(), headers, &mut |_| {})?
+            } else {
+                self.client.make_json_request(&self.config.api_url, payload.clone(), headers, &[])?
+            };
+            stop_reasons.push(response["stop_reason"].as_str().map(|r| llm_to_stop_reason(self.config.provider, r)));
+            if let Some(response_usage) = llm_to_usage(self.config.provider, &response) {
+                *usage.get_or_insert_with(Usage::default) += response_usage;
+            }
+            let (candidate_entries, result, thinking) = self.process_response(response)?;
+            entries.push(candidate_entries);
+            reasoning.push(thinking);
+            candidates.push(result);
+        }
+
+        self.candidates.keep(&mut self.history, entries, stop_reasons, reasoning);
+        self.candidates.set_usage(usage);
+
+        Ok(candidates)
+    }
+
+    fn get_inference_streaming(&mut self, messages: &[Message], tools: ToolChoice, on_delta: &mut dyn FnMut(&Message)) -> Result<Vec<Message>, Error> {
+        // Bedrock streams in the binary AWS event stream format, so the text is passed as a single delta.
+        if self.config.provider == ModelProvider::Bedrock {
+            let result = self.get_inference(messages, tools)?;
+            result.iter().filter(|message| matches!(message, Message::Text(_))).for_each(on_delta);
+            return Ok(result);
+        }
+
+        // A single candidate is streamed, `n` is ignored.
+        let payload = self.prep_payload(messages, tools);
+
+        let headers = self.auth_headers(&payload)?;
+        let headers: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        let headers = util::with_extra_headers(&headers, &self.config);
+
+        let response = self.stream_response(payload, &headers, on_delta)?;
+
+        let stop_reason = response["stop_reason"].as_str().map(|r| llm_to_stop_reason(self.config.provider, r));
+        let usage = llm_to_usage(self.config.provider, &response);
+        let (entries, result, thinking) = self.process_response(response)?;
+
+        self.candidates.keep(&mut self.history, vec![entries], vec![stop_reason], vec![thinking]);
+        self.candidates.set_usage(usage);
 
-        let response = self.client.make_json_request(&self.config.api_url, payload, headers, &[])?;
+        Ok(result)
+    }
 
-        self.process_response(response)
+    fn select_candidate(&mut self, index: usize) -> Result<(), Error> {
+        self.candidates.select(&mut self.history, index)
+    }
+
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.candidates.stop_reason()
+    }
+
+    fn reasoning(&self) -> Option<String> {
+        self.candidates.reasoning()
+    }
+
+    fn usage(&self) -> Option<Usage> {
+        self.candidates.usage()
+    }
+
+    fn provider(&self) -> ModelProvider {
+        self.config.provider
+    }
+
+    fn model_name(&self) -> &str {
+        &self.config.name
     }
 
     fn clear_history(&mut self) {
         self.history.clear();
+        self.candidates.clear();
     }
 
     fn set_system_prompt(&mut self, prompt: String) {
         self.system_prompt = prompt;
     }
+
+    fn get_inference_with_system(&mut self, system: &str, messages: &[Message], tools: ToolChoice) -> Result<Vec<Message>, Error> {
+        let stored = std::mem::replace(&mut self.system_prompt, system.to_owned());
+        let result = self.get_inference(messages, tools);
+        self.system_prompt = stored;
+        result
+    }
+
+    fn add_tool(&mut self, tool: ToolSpec) {
+        self.tools.push(tool);
+    }
+
+    fn set_tools(&mut self, tools: Vec<ToolSpec>) {
+        self.tools = tools;
+    }
+
+    fn add_messages(&mut self, messages: &[Message]) {
+        self.push_messages(messages);
+    }
+
+    fn export_history(&self) -> Vec<Value> {
+        self.history.clone()
+    }
+
+    fn import_history(&mut self, history: Vec<Value>) {
+        self.history = history;
+        self.candidates.clear();
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::tools_to_json;
     use crate::request::stub::StubClient;
+    use crate::config::AuthMode;
     use crate::llm::Role;
     use crate::tools::{ParamType, ToolParam};
 
@@ -218,6 +551,7 @@ mod tests {
             provider: "anthropic".try_into().expect("determine model provider"),
             name: "<model-name>".to_owned(),
             api_key: "<api-key>".to_owned(),
+            auth_mode: AuthMode::ApiKey,
             api_url: "<api-uri>".to_owned(),
             api_version: Some("<api-ver>".to_owned()),
             max_tokens: Some(4096),
@@ -227,7 +561,16 @@ mod tests {
             top_k: Some(5),
             frequency_penalty: Some(-0.11),
             presence_penalty: Some(0.22),
-            stop_sequence: Some("<stop>".to_owned()),
+            stop_sequences: Some(vec!["<stop>".to_owned()]),
+            reasoning_effort: None,
+            thinking_budget: None,
+            prompt_caching: false,
+            safety_settings: vec![],
+            logprobs: None,
+            aws_credentials: None,
+            extra_headers: vec![],
+            omit_params: vec![],
+            non_streaming_max_tokens: None,
         };
 
         let sys_msg = "test sys message";
@@ -251,7 +594,7 @@ mod tests {
                 {"role": "user", "content": user_msg}
             ],
             "system": sys_msg,
-            "stop_sequences": [config.stop_sequence.as_ref().unwrap()],
+            "stop_sequences": config.stop_sequences.as_ref().unwrap(),
             "temperature": config.temperature.unwrap(),
             "top_k": config.top_k.unwrap(),
             "top_p": config.top_p.unwrap(),
@@ -300,6 +643,7 @@ mod tests {
             provider: "anthropic".try_into().expect("determine model provider"),
             name: "<model-name>".to_owned(),
             api_key: "<api-key>".to_owned(),
+            auth_mode: AuthMode::ApiKey,
             api_url: "<api-uri>".to_owned(),
             api_version: Some("<api-ver>".to_owned()),
             max_tokens: Some(4096),
@@ -309,7 +653,16 @@ mod tests {
             top_k: Some(5),
             frequency_penalty: Some(-0.11),
             presence_penalty: Some(0.22),
-            stop_sequence: Some("<stop>".to_owned()),
+            stop_sequences: Some(vec!["<stop>".to_owned()]),
+            reasoning_effort: None,
+            thinking_budget: None,
+            prompt_caching: false,
+            safety_settings: vec![],
+            logprobs: None,
+            aws_credentials: None,
+            extra_headers: vec![],
+            omit_params: vec![],
+            non_streaming_max_tokens: None,
         };
 
         let sys_msg = "test sys message";
@@ -331,7 +684,7 @@ mod tests {
                 {"role": "user", "content": user_msg}
             ],
             "system": sys_msg,
-            "stop_sequences": [config.stop_sequence.as_ref().unwrap()],
+            "stop_sequences": config.stop_sequences.as_ref().unwrap(),
             "temperature": config.temperature.unwrap(),
             "top_k": config.top_k.unwrap(),
             "top_p": config.top_p.unwrap(),
@@ -352,8 +705,10 @@ mod tests {
 
         let response = chat.get_inference(&messages, ToolChoice::None);
 
-        if let Err(Error::LLMErrorMessage(msg)) = response {
-            assert_eq!(msg, model_msg);
+        if let Err(Error::ProviderError { code, kind, message }) = response {
+            assert_eq!(code, None);
+            assert_eq!(kind, Some("invalid_request_error".to_owned()));
+            assert_eq!(message, model_msg);
         } else {
             panic!("type mismatch");
         }
@@ -366,6 +721,7 @@ mod tests {
             provider: "anthropic".try_into().expect("determine model provider"),
             name: "<model-name>".to_owned(),
             api_key: "<api-key>".to_owned(),
+            auth_mode: AuthMode::ApiKey,
             api_url: "<api-uri>".to_owned(),
             api_version: Some("<api-ver>".to_owned()),
             max_tokens: Some(4096),
@@ -375,7 +731,16 @@ mod tests {
             top_k: Some(5),
             frequency_penalty: Some(-0.11),
             presence_penalty: Some(0.22),
-            stop_sequence: Some("<stop>".to_owned()),
+            stop_sequences: Some(vec!["<stop>".to_owned()]),
+            reasoning_effort: None,
+            thinking_budget: None,
+            prompt_caching: false,
+            safety_settings: vec![],
+            logprobs: None,
+            aws_credentials: None,
+            extra_headers: vec![],
+            omit_params: vec![],
+            non_streaming_max_tokens: None,
         };
 
         let tools = vec![
@@ -383,36 +748,16 @@ mod tests {
                 name: "tool1".to_owned(),
                 description: "tool desc 1".to_owned(),
                 params: vec![
-                    ToolParam {
-                        name: "tool1_param1".to_string(),
-                        description: "tool1_param1 desc".to_string(), 
-                        data_type: ParamType::Integer, 
-                        required: true
-                    },
-                    ToolParam {
-                        name: "tool1_param2".to_string(),
-                        description: "tool1_param2 desc".to_string(), 
-                        data_type: ParamType::String, 
-                        required: false
-                    },
+                    ToolParam::new("tool1_param1".to_string(), "tool1_param1 desc".to_string(), ParamType::Integer, true),
+                    ToolParam::new("tool1_param2".to_string(), "tool1_param2 desc".to_string(), ParamType::String, false),
                 ]
             },
             ToolSpec {
                 name: "tool2".to_owned(),
                 description: "tool desc 2".to_owned(),
                 params: vec![
-                    ToolParam {
-                        name: "tool2_param1".to_string(),
-                        description: "tool2_param1 desc".to_string(), 
-                        data_type: ParamType::Boolean, 
-                        required: false
-                    },
-                    ToolParam {
-                        name: "tool2_param2".to_string(),
-                        description: "tool2_param2 desc".to_string(), 
-                        data_type: ParamType::Number, 
-                        required: true
-                    },
+                    ToolParam::new("tool2_param1".to_string(), "tool2_param1 desc".to_string(), ParamType::Boolean, false),
+                    ToolParam::new("tool2_param2".to_string(), "tool2_param2 desc".to_string(), ParamType::Number, true),
                 ]
             },
         ];
@@ -431,7 +776,7 @@ mod tests {
         messages.push(Message::text(Role::User, user_msg.to_owned()));
         let mut expected_messages = Vec::new();
         expected_messages.push(Message::text(Role::Model, model_msg.to_owned()));
-        expected_messages.push(Message::tool_use(call_id.to_owned(), call_tool.to_owned(), call_params));
+        expected_messages.push(Message::tool_call(call_id.to_owned(), call_tool.to_owned(), call_params));
 
         let expected_headers = vec![
             ("x-api-key".to_owned(), config.api_key.clone()), 
@@ -445,7 +790,7 @@ mod tests {
                 {"role": "user", "content": user_msg}
             ],
             "system": sys_msg,
-            "stop_sequences": [config.stop_sequence.as_ref().unwrap()],
+            "stop_sequences": config.stop_sequences.as_ref().unwrap(),
             "temperature": config.temperature.unwrap(),
             "top_k": config.top_k.unwrap(),
             "top_p": config.top_p.unwrap(),
@@ -519,6 +864,8 @@ mod tests {
             }
           });
 
+        assert_eq!(tools_to_json(&tools, config.provider), expected_payload["tools"]);
+
         let client = Box::new(StubClient::new(expected_headers, expected_params, expected_payload, response_body));
 
         let mut chat = AnthropicChat::new(config, client, tools).expect("AnthropicChat initialization");
@@ -547,4 +894,465 @@ mod tests {
         }
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_prompt_caching() {
+        let mut config = Config::new("anthropic".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        config.api_version = Some("<api-ver>".to_owned());
+        config.max_tokens = Some(4096);
+        config.prompt_caching = true;
+
+        let tools = vec![
+            ToolSpec { name: "tool1".to_owned(), description: "tool desc 1".to_owned(), params: vec![] },
+            ToolSpec { name: "tool2".to_owned(), description: "tool desc 2".to_owned(), params: vec![] },
+        ];
+
+        let sys_msg = "test sys message";
+        let user_msg = "test user message";
+        let model_msg = "test resp message";
+
+        let expected_payload = json!({
+            "model": config.name,
+            "max_tokens": config.max_tokens.unwrap(),
+            "system": [{
+                "type": "text",
+                "text": sys_msg,
+                "cache_control": {"type": "ephemeral"}
+            }],
+            "messages": [
+                {"role": "user", "content": user_msg}
+            ],
+            "tool_choice": {
+                "type": "auto",
+                "disable_parallel_tool_use": true,
+            },
+            "tools": [{
+                "name": "tool1",
+                "description": "tool desc 1",
+                "input_schema": {"type": "object", "properties": {}, "required": [], "additionalProperties": false}
+            },
+            {
+                "name": "tool2",
+                "description": "tool desc 2",
+                "input_schema": {"type": "object", "properties": {}, "required": [], "additionalProperties": false},
+                "cache_control": {"type": "ephemeral"}
+            }]
+        });
+
+        let response_body = json!({
+            "content": [{"text": model_msg, "type": "text"}],
+            "role": "assistant",
+            "stop_reason": "end_turn",
+            "type": "message"
+        });
+
+        let client = Box::new(StubClient::new(vec![], vec![], expected_payload, response_body));
+
+        let mut chat = AnthropicChat::new(config, client, tools).expect("AnthropicChat initialization");
+
+        chat.set_system_prompt(sys_msg.to_owned());
+
+        let response = chat.get_inference(&[Message::text(Role::User, user_msg.to_owned())], ToolChoice::Auto)
+            .expect("receive response");
+
+        assert_eq!(response.len(), 1);
+    }
+
+    #[test]
+    fn test_empty_response() {
+        let mut config = Config::new("anthropic".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        config.api_version = Some("<api-version>".to_owned());
+        config.max_tokens = Some(1024);
+
+        let client = Box::new(StubClient::with_response(json!({"role": "assistant", "content": [], "stop_reason": "end_turn"})));
+        let mut chat = AnthropicChat::new(config, client, vec![]).expect("AnthropicChat initialization");
+
+        let response = chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None);
+        assert!(matches!(response, Err(Error::EmptyResponse)));
+    }
+
+    #[test]
+    fn test_system_message() {
+        let mut config = Config::new("anthropic".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        config.api_version = Some("<api-ver>".to_owned());
+        config.max_tokens = Some(4096);
+
+        let expected_payload = json!({
+            "model": config.name,
+            "max_tokens": config.max_tokens.unwrap(),
+            "system": "sys",
+            "messages": [
+                {"role": "user", "content": "System instruction: be brief"},
+                {"role": "user", "content": "hi"}
+            ]
+        });
+
+        let response_body = json!({
+            "content": [{"text": "hello", "type": "text"}],
+            "role": "assistant",
+            "stop_reason": "end_turn",
+            "type": "message"
+        });
+
+        let client = Box::new(StubClient::new(vec![], vec![], expected_payload, response_body));
+
+        let mut chat = AnthropicChat::new(config, client, vec![]).expect("AnthropicChat initialization");
+        chat.set_system_prompt("sys".to_owned());
+
+        let messages = [Message::system("be brief".to_owned()), Message::text(Role::User, "hi".to_owned())];
+        chat.get_inference(&messages, ToolChoice::None).expect("receive response");
+    }
+
+    #[test]
+    fn test_except_tools() {
+        let mut config = Config::new("anthropic".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        config.api_version = Some("<api-ver>".to_owned());
+        config.max_tokens = Some(1024);
+        let tools = ["tool1", "tool2", "tool3"].iter()
+            .map(|name| ToolSpec { name: name.to_string(), description: String::new(), params: vec![] })
+            .collect();
+        let mut chat = AnthropicChat::new(config, Box::new(StubClient::with_response(Value::Null)), tools).expect("AnthropicChat initialization");
+
+        let payload = chat.prep_payload(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::Except(vec!["tool2".to_owned()]));
+        let names: Vec<&str> = payload["tools"].as_array().expect("tools array").iter()
+            .map(|spec| spec["name"].as_str().expect("tool name"))
+            .collect();
+        assert_eq!(names, vec!["tool1", "tool3"]);
+    }
+
+    #[test]
+    fn test_subset_tools() {
+        let mut config = Config::new("anthropic".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        config.api_version = Some("<api-ver>".to_owned());
+        config.max_tokens = Some(1024);
+        let tools = ["tool1", "tool2", "tool3"].iter()
+            .map(|name| ToolSpec { name: name.to_string(), description: String::new(), params: vec![] })
+            .collect();
+        let mut chat = AnthropicChat::new(config, Box::new(StubClient::with_response(Value::Null)), tools).expect("AnthropicChat initialization");
+
+        let payload = chat.prep_payload(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::Subset(vec!["tool2".to_owned()]));
+        assert_eq!(payload["tools"].as_array().expect("tools array").len(), 1);
+        assert_eq!(payload["tools"][0]["name"], "tool2");
+    }
+
+    #[test]
+    fn test_bedrock() {
+        use crate::config::AwsCredentials;
+        use crate::request::stub::RecordingClient;
+
+        let mut config = Config::new("bedrock".try_into().expect("determine model provider"),
+            "anthropic.claude-3-5-sonnet-20240620-v1:0".to_owned(), String::new(), String::new());
+        config.max_tokens = Some(1024);
+        config.aws_credentials = Some(AwsCredentials {
+            access_key_id: "AKID".to_owned(),
+            secret_access_key: "secret".to_owned(),
+            session_token: Some("token".to_owned()),
+            region: "us-east-1".to_owned(),
+        });
+
+        // The signature depends on the current time, so the headers are checked separately.
+        let client = RecordingClient::new(json!({"content": [{"type": "text", "text": "hello"}], "role": "assistant", "stop_reason": "end_turn"}));
+        let mut chat = AnthropicChat::new(config, Box::new(client.clone()), vec![]).expect("AnthropicChat initialization");
+
+        let response = chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None).expect("receive response");
+        assert!(matches!(&response[0], Message::Text(txt) if txt.message == "hello"));
+        assert_eq!(chat.provider(), ModelProvider::Bedrock);
+
+        let request = client.requests().remove(0);
+        assert_eq!(request.url, "https://bedrock-runtime.us-east-1.amazonaws.com/model/anthropic.claude-3-5-sonnet-20240620-v1%3A0/invoke");
+        assert_eq!(request.payload, json!({
+            "anthropic_version": "bedrock-2023-05-31",
+            "max_tokens": 1024,
+            "system": "",
+            "messages": [{"role": "user", "content": "hi"}],
+        }));
+
+        let names: Vec<&str> = request.headers.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(names, vec!["Authorization", "x-amz-date", "x-amz-security-token"]);
+        assert!(request.headers[0].1.starts_with("AWS4-HMAC-SHA256 Credential=AKID/"));
+        assert!(request.headers[0].1.contains("/us-east-1/bedrock/aws4_request, SignedHeaders=host;x-amz-date;x-amz-security-token, Signature="));
+
+        // Errors of Bedrock have no type.
+        let client = Box::new(StubClient::with_response(json!({"message": "The security token included in the request is invalid."})));
+        let mut config = Config::new(ModelProvider::Bedrock, "anthropic.claude-v2".to_owned(), String::new(), String::new());
+        config.max_tokens = Some(1024);
+        config.aws_credentials = Some(AwsCredentials {
+            access_key_id: "AKID".to_owned(),
+            secret_access_key: "secret".to_owned(),
+            session_token: None,
+            region: "us-east-1".to_owned(),
+        });
+        let mut chat = AnthropicChat::new(config, client, vec![]).expect("AnthropicChat initialization");
+        assert!(matches!(chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None),
+            Err(Error::LLMErrorMessage(msg)) if msg.starts_with("The security token")));
+    }
+
+    #[test]
+    fn test_prefill() {
+        let mut config = Config::new("anthropic".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        config.api_version = Some("<api-ver>".to_owned());
+        config.max_tokens = Some(1024);
+
+        let expected_payload = json!({
+            "model": config.name,
+            "max_tokens": config.max_tokens.unwrap(),
+            "system": "",
+            "messages": [
+                {"role": "user", "content": "list vms as json"},
+                {"role": "assistant", "content": "{"}
+            ]
+        });
+
+        let response_body = json!({
+            "content": [{"type": "text", "text": "\"vms\": []}"}],
+            "role": "assistant",
+            "stop_reason": "end_turn",
+        });
+
+        let client = Box::new(StubClient::new(vec![], vec![], expected_payload, response_body));
+        let mut chat = AnthropicChat::new(config, client, vec![]).expect("AnthropicChat initialization");
+
+        let messages = [Message::text(Role::User, "list vms as json".to_owned()), Message::text(Role::Model, "{ \n".to_owned())];
+        let response = chat.get_inference(&messages, ToolChoice::None).expect("receive response");
+        assert!(matches!(&response[0], Message::Text(txt) if txt.message == "\"vms\": []}"));
+    }
+
+    #[test]
+    fn test_thinking() {
+        let mut config = Config::new("anthropic".try_into().expect("determine model provider"),
+            "claude-sonnet-4-20250514".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        config.api_version = Some("<api-ver>".to_owned());
+        config.max_tokens = Some(4096);
+        config.thinking_budget = Some(1024);
+
+        let expected_payload = json!({
+            "model": config.name,
+            "max_tokens": config.max_tokens.unwrap(),
+            "system": "",
+            "thinking": {"type": "enabled", "budget_tokens": 1024},
+            "messages": [
+                {"role": "user", "content": "hi"}
+            ]
+        });
+
+        let response_body = json!({
+            "content": [
+                {"type": "thinking", "thinking": "user greets me", "signature": "sig"},
+                {"type": "text", "text": "hello"}
+            ],
+            "role": "assistant",
+            "stop_reason": "end_turn",
+            "type": "message"
+        });
+
+        let client = Box::new(StubClient::new(vec![], vec![], expected_payload.clone(), response_body.clone()));
+        let mut chat = AnthropicChat::new(config.clone(), client, vec![]).expect("AnthropicChat initialization");
+
+        let response = chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None)
+            .expect("receive response");
+
+        assert_eq!(response.len(), 1);
+        assert_eq!(chat.reasoning(), Some("user greets me".to_owned()));
+        assert_eq!(chat.history.len(), 3);
+
+        // Thinking is omitted for models without reasoning support.
+        config.name = "claude-3-5-sonnet-20241022".to_owned();
+        let mut expected_payload = expected_payload;
+        expected_payload["model"] = json!(config.name);
+        expected_payload.as_object_mut().unwrap().remove("thinking");
+
+        let client = Box::new(StubClient::new(vec![], vec![], expected_payload, response_body));
+        let mut chat = AnthropicChat::new(config, client, vec![]).expect("AnthropicChat initialization");
+        chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None)
+            .expect("receive response");
+    }
+
+    #[test]
+    fn test_system_override() {
+        use crate::request::stub::RecordingClient;
+
+        let mut config = Config::new("anthropic".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        config.api_version = Some("<api-ver>".to_owned());
+        config.max_tokens = Some(1024);
+
+        let client = RecordingClient::new(json!({"content": [{"type": "text", "text": "hello"}], "role": "assistant", "stop_reason": "end_turn"}));
+        let mut chat = AnthropicChat::new(config, Box::new(client.clone()), vec![]).expect("AnthropicChat initialization");
+        chat.set_system_prompt("sys".to_owned());
+
+        chat.get_inference_with_system("other", &[Message::text(Role::User, "hi".to_owned())], ToolChoice::None).expect("receive response");
+        chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None).expect("receive response");
+
+        let requests = client.requests();
+        assert_eq!(requests[0].payload["system"], "other");
+        assert_eq!(requests[1].payload["system"], "sys");
+        assert_eq!(requests[1].payload["messages"].as_array().expect("messages array").len(), 3);
+    }
+
+    #[test]
+    fn test_streaming() {
+        use crate::request::stub::RecordingClient;
+
+        let stream = r#"event: message_start
+data: {"type": "message_start", "message": {"id": "msg_1", "type": "message", "role": "assistant", "model": "claude", "content": [], "stop_reason": null, "stop_sequence": null, "usage": {"input_tokens": 10, "output_tokens": 1}}}
+
+event: content_block_start
+data: {"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}
+
+event: ping
+data: {"type": "ping"}
+
+event: content_block_delta
+data: {"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Okay, "}}
+
+event: content_block_delta
+data: {"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "let me check."}}
+
+event: content_block_stop
+data: {"type": "content_block_stop", "index": 0}
+
+event: content_block_start
+data: {"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "SHELL", "input": {}}}
+
+event: content_block_delta
+data: {"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"comm"}}
+
+event: content_block_delta
+data: {"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "and\": \"ls\"}"}}
+
+event: content_block_stop
+data: {"type": "content_block_stop", "index": 1}
+
+event: message_delta
+data: {"type": "message_delta", "delta": {"stop_reason": "tool_use", "stop_sequence": null}, "usage": {"output_tokens": 20}}
+
+event: message_stop
+data: {"type": "message_stop"}
+"#;
+
+        let response_body = json!({
+            "id": "msg_1", "type": "message", "role": "assistant", "model": "claude",
+            "content": [
+                {"type": "text", "text": "Okay, let me check."},
+                {"type": "tool_use", "id": "toolu_1", "name": "SHELL", "input": {"command": "ls"}},
+            ],
+            "stop_reason": "tool_use", "stop_sequence": null,
+            "usage": {"input_tokens": 10, "output_tokens": 20},
+        });
+
+        let mut config = Config::new(ModelProvider::Anthropic, "claude".to_owned(), "<api-key>".to_owned(), String::new());
+        config.api_version = Some("<api-ver>".to_owned());
+        config.max_tokens = Some(1024);
+        let messages = [Message::text(Role::User, "list files".to_owned())];
+
+        let client = RecordingClient::streaming(stream);
+        let mut chat = AnthropicChat::new(config.clone(), Box::new(client.clone()), vec![]).expect("AnthropicChat initialization");
+        let mut deltas = vec![];
+        let response = chat.get_inference_streaming(&messages, ToolChoice::Auto,
+            &mut |delta| if let Message::Text(txt) = delta { deltas.push(txt.message.clone()) }).expect("receive response");
+
+        assert_eq!(deltas, vec!["Okay, ", "let me check."]);
+        assert!(matches!(&response[0], Message::Text(txt) if txt.message == "Okay, let me check."));
+        assert!(matches!(&response[1], Message::ToolCall(call) if call.call_id == "toolu_1" && call.params[0].value == "ls"));
+        assert_eq!(client.requests()[0].payload["stream"], true);
+        assert_eq!(chat.stop_reason(), Some(StopReason::ToolUse));
+        assert_eq!(chat.usage(), Some(Usage { input_tokens: 10, output_tokens: 20 }));
+
+        let mut expected = AnthropicChat::new(config.clone(), Box::new(StubClient::with_response(response_body)), vec![]).expect("AnthropicChat initialization");
+        expected.get_inference(&messages, ToolChoice::Auto).expect("receive response");
+        assert_eq!(chat.export_history(), expected.export_history());
+
+        // Error responses are plain JSON.
+        let client = RecordingClient::streaming(r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}"#);
+        let mut chat = AnthropicChat::new(config, Box::new(client), vec![]).expect("AnthropicChat initialization");
+        let err = chat.get_inference_streaming(&messages, ToolChoice::Auto, &mut |_| {});
+        assert!(matches!(err, Err(Error::ProviderError { kind: Some(kind), .. }) if kind == "overloaded_error"));
+    }
+
+    #[test]
+    fn test_large_max_tokens() {
+        use crate::request::stub::RecordingClient;
+
+        let stream = r#"event: message_start
+data: {"type": "message_start", "message": {"id": "msg_1", "type": "message", "role": "assistant", "model": "claude", "content": [], "stop_reason": null, "stop_sequence": null, "usage": {"input_tokens": 10, "output_tokens": 1}}}
+
+event: content_block_start
+data: {"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}
+
+event: content_block_delta
+data: {"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "hello"}}
+
+event: content_block_stop
+data: {"type": "content_block_stop", "index": 0}
+
+event: message_delta
+data: {"type": "message_delta", "delta": {"stop_reason": "end_turn", "stop_sequence": null}, "usage": {"output_tokens": 2}}
+
+event: message_stop
+data: {"type": "message_stop"}
+"#;
+
+        let mut config = Config::new(ModelProvider::Anthropic, "claude".to_owned(), "<api-key>".to_owned(), String::new());
+        config.api_version = Some("<api-ver>".to_owned());
+        config.max_tokens = Some(64000);
+        let messages = [Message::text(Role::User, "write a long story".to_owned())];
+
+        // Requests above the limit are streamed.
+        let client = RecordingClient::streaming(stream);
+        let mut chat = AnthropicChat::new(config.clone(), Box::new(client.clone()), vec![]).expect("AnthropicChat initialization");
+        let response = chat.get_inference(&messages, ToolChoice::None).expect("receive response");
+        assert!(matches!(&response[0], Message::Text(txt) if txt.message == "hello"));
+        assert_eq!(client.requests()[0].payload["stream"], true);
+        assert_eq!(chat.stop_reason(), Some(StopReason::EndTurn));
+
+        config.non_streaming_max_tokens = Some(64000);
+        let client = RecordingClient::new(json!({"content": [{"type": "text", "text": "hello"}], "role": "assistant", "stop_reason": "end_turn"}));
+        let mut chat = AnthropicChat::new(config, Box::new(client.clone()), vec![]).expect("AnthropicChat initialization");
+        chat.get_inference(&messages, ToolChoice::None).expect("receive response");
+        assert!(client.requests()[0].payload.get("stream").is_none());
+    }
+
+    #[test]
+    fn test_add_messages() {
+        let mut config = Config::new(ModelProvider::Anthropic, "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        config.api_version = Some("<api-ver>".to_owned());
+        config.max_tokens = Some(4096);
+
+        let expected_payload = json!({
+            "model": config.name,
+            "max_tokens": config.max_tokens.unwrap(),
+            "system": "",
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+
+        let response_body = json!({
+            "content": [
+                {"type": "tool_use", "id": "call_1", "name": "SHELL", "input": {"command": "ls"}},
+                {"type": "tool_use", "id": "call_2", "name": "SHELL", "input": {"command": "pwd"}}
+            ],
+            "role": "assistant",
+            "stop_reason": "tool_use",
+            "type": "message"
+        });
+
+        let client = Box::new(StubClient::new(vec![], vec![], expected_payload, response_body));
+        let mut chat = AnthropicChat::new(config, client, vec![]).expect("AnthropicChat initialization");
+
+        let response = chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None).expect("receive response");
+        let results: Vec<Message> = response.iter()
+            .filter_map(|msg| if let Message::ToolCall(call) = msg { Some(call.to_result("aborted".to_owned())) } else { None })
+            .collect();
+        chat.add_messages(&results);
+
+        // Each tool use block is answered.
+        let history = chat.export_history();
+        assert_eq!(history.len(), 5);
+        assert_eq!(history[3]["content"][0]["tool_use_id"], "call_1");
+        assert_eq!(history[4]["content"][0]["tool_use_id"], "call_2");
+    }
+}
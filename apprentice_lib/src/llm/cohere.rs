@@ -0,0 +1,388 @@
+use crate::llm::util::{role_to_llm, tool_params_to_value};
+use crate::llm::LLMChat;
+use crate::config::{Config, ModelProvider};
+use crate::error::Error;
+use crate::logging::log_debug;
+use crate::tools::{ToolChoice, ToolSpec};
+use crate::val_as_str;
+use serde_json::{json, Value};
+use crate::request::Client;
+use super::messages::Text;
+use super::{Message, StopReason, ToolCall, ToolParam, Usage};
+use super::util::{self, llm_to_role, llm_to_stop_reason, llm_to_usage, Candidates};
+
+pub struct CohereChat {
+    system_prompt: Option<String>,
+    history: Vec<Value>,
+    config: Config,
+    client: Box<dyn Client>,
+    tools: Vec<ToolSpec>,
+    candidates: Candidates,
+}
+
+impl CohereChat {
+    pub(super) fn new(config: Config, client: Box<dyn Client>, tools: Vec<ToolSpec>) -> Self {
+        CohereChat {
+            system_prompt: None,
+            history: vec![],
+            config,
+            client,
+            tools,
+            candidates: Candidates::default(),
+        }
+    }
+
+    fn push_messages(&mut self, messages: &[Message]) {
+        for message in messages {
+            if let Message::Text(txt) = message {
+                self.history.push(json!({
+                    "role": role_to_llm(self.config.provider, txt.role),
+                    "content": txt.message
+                }));
+            } else if let Message::ToolResult(res) = message {
+                util::push_tool_result(&mut self.history, json!({
+                    "role": "tool",
+                    "tool_call_id": res.call_id,
+                    "content": res.result
+                }));
+            }
+        }
+    }
+
+    fn prep_payload(&mut self, messages: &[Message], tools: ToolChoice) -> Value {
+
+        let mut payload = json!({
+            "model": self.config.name
+        });
+
+        self.push_messages(messages);
+
+        payload["messages"] = self.messages_to_json();
+
+        util::set_f64_param(&mut payload, "frequency_penalty", &self.config.frequency_penalty);
+        util::set_f64_param(&mut payload, "presence_penalty", &self.config.presence_penalty);
+        util::set_f64_param(&mut payload, "p", &self.config.top_p);
+        util::set_i64_param(&mut payload, "k", &self.config.top_k);
+        util::set_f64_param(&mut payload, "temperature", &self.config.temperature);
+        util::set_i64_param(&mut payload, "max_tokens", &self.config.max_tokens);
+
+        if let Some(val) = &self.config.stop_sequences {
+            payload["stop_sequences"] = json!(val);
+        }
+
+        self.prep_tool_use(&mut payload, tools);
+
+        payload
+    }
+
+    // Cohere has no tool choice for a specific tool, so only the forced tool is provided.
+    // Without `tool_choice` the model decides whether to call a tool.
+    fn prep_tool_use(&self, payload: &mut Value, tools: ToolChoice) {
+        match tools {
+            ToolChoice::None => {},
+            ToolChoice::Auto => {
+                self.add_tools(payload, |_| true);
+            },
+            ToolChoice::CallOne => {
+                payload["tool_choice"] = Value::String("REQUIRED".to_owned());
+                self.add_tools(payload, |_| true);
+            },
+            ToolChoice::Force(tool) => {
+                payload["tool_choice"] = Value::String("REQUIRED".to_owned());
+                self.add_tools(payload, |name| name == tool);
+            },
+            ToolChoice::Except(excluded) => {
+                self.add_tools(payload, |name| !excluded.iter().any(|excl| excl == name));
+            },
+            ToolChoice::Subset(allowed) => {
+                self.add_tools(payload, |name| allowed.iter().any(|allow| allow == name));
+            },
+        };
+    }
+
+    fn add_tools(&self, payload: &mut Value, include: impl Fn(&str) -> bool) {
+        payload["tools"] = Self::tools_to_json(self.tools.iter().filter(|spec| include(&spec.name)), self.config.provider);
+    }
+
+    pub(super) fn tools_to_json<'a>(tools: impl Iterator<Item = &'a ToolSpec>, provider: ModelProvider) -> Value {
+        let arr = tools.map(|spec| json!({
+            "type": "function",
+            "function": {
+                "name": spec.name,
+                "description": spec.description,
+                "parameters": tool_params_to_value(&spec.params, provider)
+            }
+        })).collect();
+        Value::Array(arr)
+    }
+
+    // The stored system prompt is kept out of the history, so the inline system messages stay in it.
+    fn messages_to_json(&self) -> Value {
+        let system = self.system_prompt.iter().map(|prompt| json!({"role": "system", "content": prompt}));
+        Value::Array(system.chain(self.history.iter().cloned()).collect())
+    }
+
+    // Cohere reports errors with the message only.
+    fn check_for_error(&self, response: &Value) -> Result<(), Error> {
+        if let Some(message) = response["message"].as_str() {
+            return Err(Error::LLMErrorMessage(message.to_owned()));
+        }
+        Ok(())
+    }
+
+    fn process_response(&mut self, response: Value) -> Result<Vec<Vec<Message>>, Error> {
+
+        self.check_for_error(&response)
+            .inspect_err(|err| log_debug!("{} returned error: {}", self.config.provider, err))?;
+
+        let msg = &response["message"];
+        if !msg.is_object() {
+            return Err(Error::LLMResponseError("unexpected answer format, response message is missing."));
+        }
+
+        let mut result = Vec::new();
+
+        let role = llm_to_role(val_as_str!(msg["role"], "message role"))?;
+
+        if let Some(content) = msg["content"].as_array() {
+            for item in content {
+                if item["type"] == "text" {
+                    let text = val_as_str!(item["text"], "message content").to_owned();
+                    result.push(Message::Text(Text{role, message: text}));
+                }
+            }
+        }
+
+        if let Some(calls) = msg["tool_calls"].as_array() {
+            for call in calls {
+                let call_id = val_as_str!(call["id"], "tool call id").to_owned();
+                let name = val_as_str!(call["function"]["name"], "tool name").to_owned();
+                let arguments = val_as_str!(call["function"]["arguments"], "tool arguments").to_owned();
+
+                let args_obj = serde_json::from_str::<Value>(&arguments)
+                    .map_err(|err| Error::ToolArgumentsError { name: name.clone(), arguments: arguments.clone(), message: err.to_string() })?;
+
+                let params = args_obj
+                    .as_object()
+                    .ok_or_else(|| Error::ToolArgumentsError { name: name.clone(), arguments: arguments.clone(), message: "not an object".to_owned() })?
+                    .iter()
+                    .map(|(k, v)| ToolParam {name: k.clone(), value: v.clone()})
+                    .collect();
+
+                result.push(Message::ToolCall(ToolCall{call_id, name, params, raw_arguments: Some(arguments)}));
+            }
+        }
+
+        if result.is_empty() {
+            return Err(Error::EmptyResponse);
+        }
+
+        // Tool plan is the model's reasoning about the tool calls.
+        let reasoning = msg["tool_plan"].as_str().map(|plan| plan.to_owned());
+        let stop_reason = response["finish_reason"].as_str().map(|r| llm_to_stop_reason(self.config.provider, r));
+        log_debug!("{} returned {} message(s), stop reason {:?}", self.config.provider, result.len(), stop_reason);
+
+        self.candidates.keep(&mut self.history, vec![vec![msg.clone()]], vec![stop_reason], vec![reasoning]);
+        self.candidates.set_usage(llm_to_usage(self.config.provider, &response));
+
+        Ok(vec![result])
+    }
+}
+
+impl LLMChat for CohereChat {
+
+    fn get_candidates(&mut self, messages: &[Message], tools: ToolChoice) -> Result<Vec<Vec<Message>>, Error> {
+        if util::has_prefill(messages) {
+            return Err(util::prefill_error(self.config.provider));
+        }
+
+        let payload = self.prep_payload(messages, tools);
+
+        let token = format!("Bearer {}", self.config.api_key);
+        let headers = util::with_extra_headers(&[("Authorization", token.as_ref())], &self.config);
+
+        let response = self.client.make_json_request(&self.config.api_url, payload, &headers, &[])?;
+
+        self.process_response(response)
+    }
+
+    fn select_candidate(&mut self, index: usize) -> Result<(), Error> {
+        self.candidates.select(&mut self.history, index)
+    }
+
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.candidates.stop_reason()
+    }
+
+    fn reasoning(&self) -> Option<String> {
+        self.candidates.reasoning()
+    }
+
+    fn usage(&self) -> Option<Usage> {
+        self.candidates.usage()
+    }
+
+    fn provider(&self) -> ModelProvider {
+        self.config.provider
+    }
+
+    fn model_name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn clear_history(&mut self) {
+        self.history.clear();
+        self.candidates.clear();
+    }
+
+    fn set_system_prompt(&mut self, prompt: String) {
+        self.system_prompt = Some(prompt);
+    }
+
+    fn get_inference_with_system(&mut self, system: &str, messages: &[Message], tools: ToolChoice) -> Result<Vec<Message>, Error> {
+        let stored = self.system_prompt.replace(system.to_owned());
+        let result = self.get_inference(messages, tools);
+        self.system_prompt = stored;
+        result
+    }
+
+    fn add_tool(&mut self, tool: ToolSpec) {
+        self.tools.push(tool);
+    }
+
+    fn set_tools(&mut self, tools: Vec<ToolSpec>) {
+        self.tools = tools;
+    }
+
+    fn add_messages(&mut self, messages: &[Message]) {
+        self.push_messages(messages);
+    }
+
+    fn export_history(&self) -> Vec<Value> {
+        self.history.clone()
+    }
+
+    fn import_history(&mut self, history: Vec<Value>) {
+        self.history = history;
+        self.candidates.clear();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::tools_to_json;
+    use crate::request::stub::StubClient;
+    use crate::llm::Role;
+    use crate::tools::{ParamType, ToolParam};
+
+    fn cohere_config() -> Config {
+        let mut config = Config::new("cohere".try_into().expect("determine model provider"),
+            "command-r-plus".to_owned(), "<api-key>".to_owned(), String::new());
+        config.max_tokens = Some(1024);
+        config.top_p = Some(0.5);
+        config
+    }
+
+    #[test]
+    fn test_request_response_ok() {
+        assert_eq!(cohere_config().api_url, "https://api.cohere.com/v2/chat");
+
+        let expected_headers = vec![("Authorization".to_owned(), "Bearer <api-key>".to_owned())];
+        let expected_payload = json!({
+            "model": "command-r-plus",
+            "messages": [
+                {"role": "system", "content": "be brief"},
+                {"role": "user", "content": "hi"}
+            ],
+            "max_tokens": 1024,
+            "p": 0.5,
+        });
+        let response_body = json!({
+            "id": "c14c80c3-18eb-4519-9460-6c92edd8cfb4",
+            "finish_reason": "COMPLETE",
+            "message": {"role": "assistant", "content": [{"type": "text", "text": "hello"}]},
+            "usage": {"billed_units": {"input_tokens": 5, "output_tokens": 1}, "tokens": {"input_tokens": 71, "output_tokens": 1}}
+        });
+
+        let client = Box::new(StubClient::new(expected_headers, vec![], expected_payload, response_body));
+        let mut chat = CohereChat::new(cohere_config(), client, vec![]);
+        chat.set_system_prompt("be brief".to_owned());
+
+        let response = chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None).expect("receive response");
+        assert!(matches!(&response[0], Message::Text(txt) if txt.role == Role::Model && txt.message == "hello"));
+        assert_eq!(chat.stop_reason(), Some(StopReason::EndTurn));
+        assert_eq!(chat.usage(), Some(Usage { input_tokens: 71, output_tokens: 1 }));
+
+        let client = Box::new(StubClient::with_response(json!({"id": "1", "message": "invalid api token"})));
+        let mut chat = CohereChat::new(cohere_config(), client, vec![]);
+        assert!(matches!(chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None),
+            Err(Error::LLMErrorMessage(msg)) if msg == "invalid api token"));
+    }
+
+    #[test]
+    fn test_request_response_tool_ok() {
+        let tools = vec![ToolSpec {
+            name: "SHELL".to_owned(),
+            description: "run shell command".to_owned(),
+            params: vec![ToolParam::new("command".to_owned(), "command line".to_owned(), ParamType::String, true)],
+        }];
+
+        let tool_call = json!({
+            "role": "assistant",
+            "tool_plan": "I will list the instances.",
+            "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "SHELL", "arguments": "{\"command\":\"gcloud compute instances list\"}"}}]
+        });
+
+        let expected_payload = json!({
+            "model": "command-r-plus",
+            "messages": [{"role": "user", "content": "list vms"}],
+            "max_tokens": 1024,
+            "p": 0.5,
+            "tool_choice": "REQUIRED",
+            "tools": [{"type": "function", "function": {
+                "name": "SHELL",
+                "description": "run shell command",
+                "parameters": {"type": "object", "properties": {"command": {"type": "string", "description": "command line"}}, "required": ["command"]}
+            }}]
+        });
+        let response_body = json!({"id": "1", "finish_reason": "TOOL_CALL", "message": tool_call});
+
+        assert_eq!(tools_to_json(&tools, ModelProvider::Cohere), expected_payload["tools"]);
+
+        let client = Box::new(StubClient::new(vec![], vec![], expected_payload, response_body));
+        let mut chat = CohereChat::new(cohere_config(), client, tools);
+
+        let response = chat.get_inference(&[Message::text(Role::User, "list vms".to_owned())], ToolChoice::Force("SHELL".to_owned()))
+            .expect("receive response");
+
+        if let Message::ToolCall(call) = &response[0] {
+            assert_eq!(call.call_id, "call_1");
+            assert_eq!(call.name, "SHELL");
+            assert_eq!(call.params[0].name, "command");
+            assert_eq!(call.params[0].value, "gcloud compute instances list");
+        } else {
+            panic!("type mismatch");
+        }
+        assert_eq!(chat.stop_reason(), Some(StopReason::ToolUse));
+        assert_eq!(chat.reasoning(), Some("I will list the instances.".to_owned()));
+
+        // The tool call is kept as is, and the result refers to it.
+        let response_body = json!({"id": "2", "finish_reason": "COMPLETE", "message": {"role": "assistant", "content": [{"type": "text", "text": "no vms"}]}});
+        chat.client = Box::new(StubClient::new(vec![], vec![], json!({
+            "model": "command-r-plus",
+            "messages": [
+                {"role": "user", "content": "list vms"},
+                tool_call,
+                {"role": "tool", "tool_call_id": "call_1", "content": "Listed 0 items."}
+            ],
+            "max_tokens": 1024,
+            "p": 0.5,
+        }), response_body));
+
+        let response = chat.get_inference(&[Message::tool_result("call_1".to_owned(), "SHELL".to_owned(), "Listed 0 items.".to_owned())], ToolChoice::None)
+            .expect("receive response");
+        assert!(matches!(&response[0], Message::Text(txt) if txt.message == "no vms"));
+    }
+}
@@ -0,0 +1,148 @@
+use crate::llm::util::{push_tool_result, role_to_llm};
+use crate::llm::LLMChat;
+use crate::config::{Config, ModelProvider};
+use crate::error::Error;
+use crate::tools::{ToolChoice, ToolSpec};
+use serde_json::{json, Value};
+use super::{Message, Role, StopReason, Usage};
+
+/// Chat that answers without a model: a user message is echoed back, and a tool result is summarized.
+/// Useful for offline demos and as a deterministic backend in tests.
+pub struct EchoChat {
+    system_prompt: String,
+    history: Vec<Value>,
+    config: Config,
+    tools: Vec<ToolSpec>,
+}
+
+impl EchoChat {
+    pub(super) fn new(config: Config, tools: Vec<ToolSpec>) -> Self {
+        EchoChat {
+            system_prompt: String::new(),
+            history: vec![],
+            config,
+            tools,
+        }
+    }
+
+    fn push_messages(&mut self, messages: &[Message]) {
+        for message in messages {
+            if let Message::Text(txt) = message {
+                self.history.push(json!({"role": role_to_llm(self.config.provider, txt.role), "content": txt.message}));
+            } else if let Message::ToolResult(res) = message {
+                push_tool_result(&mut self.history, json!({"role": "tool", "content": res.result, "tool_call_id": res.call_id}));
+            }
+        }
+    }
+
+    fn answer(messages: &[Message]) -> String {
+        match messages.iter().rev().find(|message| !matches!(message, Message::Text(txt) if txt.role == Role::System)) {
+            Some(Message::Text(txt)) => format!("Echo: {}", txt.message),
+            Some(Message::ToolResult(res)) => format!("Tool {} returned: {}", res.name, res.result),
+            Some(Message::ToolCall(call)) => format!("Echo: {}", call),
+            None => "Echo: nothing to echo.".to_owned(),
+        }
+    }
+}
+
+impl LLMChat for EchoChat {
+
+    fn get_candidates(&mut self, messages: &[Message], _tools: ToolChoice) -> Result<Vec<Vec<Message>>, Error> {
+        self.push_messages(messages);
+
+        let answer = Self::answer(messages);
+        self.history.push(json!({"role": role_to_llm(self.config.provider, Role::Model), "content": answer}));
+
+        Ok(vec![vec![Message::text(Role::Model, answer)]])
+    }
+
+    fn select_candidate(&mut self, index: usize) -> Result<(), Error> {
+        if index == 0 {
+            Ok(())
+        } else {
+            Err(Error::Error(format!("candidate index {} is out of range.", index)))
+        }
+    }
+
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.history.last().map(|_| StopReason::EndTurn)
+    }
+
+    fn usage(&self) -> Option<Usage> {
+        None
+    }
+
+    fn reasoning(&self) -> Option<String> {
+        None
+    }
+
+    fn provider(&self) -> ModelProvider {
+        self.config.provider
+    }
+
+    fn model_name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    fn set_system_prompt(&mut self, prompt: String) {
+        self.system_prompt = prompt;
+    }
+
+    fn get_inference_with_system(&mut self, system: &str, messages: &[Message], tools: ToolChoice) -> Result<Vec<Message>, Error> {
+        let stored = std::mem::replace(&mut self.system_prompt, system.to_owned());
+        let result = self.get_inference(messages, tools);
+        self.system_prompt = stored;
+        result
+    }
+
+    fn add_tool(&mut self, tool: ToolSpec) {
+        self.tools.push(tool);
+    }
+
+    fn set_tools(&mut self, tools: Vec<ToolSpec>) {
+        self.tools = tools;
+    }
+
+    fn add_messages(&mut self, messages: &[Message]) {
+        self.push_messages(messages);
+    }
+
+    fn export_history(&self) -> Vec<Value> {
+        self.history.clone()
+    }
+
+    fn import_history(&mut self, history: Vec<Value>) {
+        self.history = history;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::get_llm_chat;
+    use crate::request::stub::SequenceClient;
+
+    #[test]
+    fn test_echo() {
+        let config = Config::new(ModelProvider::Echo, "echo".to_owned(), String::new(), String::new());
+        // The client is not called.
+        let mut chat = get_llm_chat(config, Box::new(SequenceClient::new(vec![])), vec![]).expect("create chat");
+
+        let response = chat.get_inference(&[Message::text(Role::User, "list vms".to_owned())], ToolChoice::Auto).expect("receive response");
+        assert!(matches!(&response[0], Message::Text(txt) if txt.role == Role::Model && txt.message == "Echo: list vms"));
+        assert_eq!(chat.stop_reason(), Some(StopReason::EndTurn));
+
+        let result = Message::tool_result("call_1".to_owned(), "SHELL".to_owned(), "Listed 0 items.".to_owned());
+        let response = chat.get_inference(&[result], ToolChoice::Auto).expect("receive response");
+        assert!(matches!(&response[0], Message::Text(txt) if txt.message == "Tool SHELL returned: Listed 0 items."));
+
+        assert_eq!(chat.export_history().len(), 4);
+        assert_eq!(chat.provider(), ModelProvider::Echo);
+        assert_eq!(chat.model_name(), "echo");
+        assert!(!chat.capabilities().supports_tools);
+    }
+}
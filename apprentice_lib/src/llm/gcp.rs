@@ -1,14 +1,139 @@
 
 use crate::llm::util::{role_to_llm, tool_params_to_value};
-use crate::llm::LLMChat;
-use crate::config::Config;
+use crate::llm::{LLMChat, Role, Text};
+use crate::config::{AuthMode, Config, ModelProvider};
 use crate::error::Error;
+use crate::logging::log_debug;
 use crate::tools::{ToolChoice, ToolSpec};
 use crate::val_as_str;
 use serde_json::{json, Value};
 use crate::request::Client;
-use super::{Message, ToolCall, ToolParam};
-use super::util::{self, llm_to_role};
+use super::{Message, StopReason, ToolCall, ToolParam, Usage};
+use super::util::{self, llm_to_error, llm_to_role, llm_to_stop_reason, llm_to_usage, supports_reasoning, Candidates};
+
+/// Splits the streamed JSON array of response chunks into the chunks.
+#[derive(Default)]
+struct JsonChunks {
+    chunk: String,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl JsonChunks {
+
+    /// Consume the next line of the stream, and return the chunks completed by it.
+    fn push(&mut self, line: &str) -> Vec<String> {
+        let mut chunks = vec![];
+        for c in line.chars() {
+            if self.depth == 0 {
+                // Array brackets and separators between the chunks.
+                if c == '{' {
+                    self.chunk.push(c);
+                    self.depth = 1;
+                }
+                continue;
+            }
+
+            self.chunk.push(c);
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if c == '\\' {
+                    self.escaped = true;
+                } else if c == '"' {
+                    self.in_string = false;
+                }
+            } else {
+                match c {
+                    '"' => self.in_string = true,
+                    '{' | '[' => self.depth += 1,
+                    '}' | ']' => {
+                        self.depth -= 1;
+                        if self.depth == 0 {
+                            chunks.push(std::mem::take(&mut self.chunk));
+                        }
+                    },
+                    _ => {},
+                }
+            }
+        }
+        chunks
+    }
+}
+
+/// Merge the streamed chunk into the response, joining the text parts of each candidate,
+/// and pass the text of the first candidate to `on_delta`.
+fn merge_chunk(response: &mut Value, chunk: Value, on_delta: &mut dyn FnMut(&Message)) {
+    let Value::Object(chunk) = chunk else {
+        return;
+    };
+
+    for (key, value) in chunk {
+        if key != "candidates" {
+            // Usage metadata of the last chunk covers the whole response.
+            response[key] = value;
+            continue;
+        }
+
+        for candidate in value.as_array().into_iter().flatten() {
+            let index = candidate["index"].as_u64().unwrap_or(0) as usize;
+            if !response["candidates"].is_array() {
+                response["candidates"] = json!([]);
+            }
+            let Some(candidates) = response["candidates"].as_array_mut() else {
+                return;
+            };
+            if candidates.len() <= index {
+                candidates.resize(index + 1, json!({}));
+            }
+            let target = &mut candidates[index];
+
+            for (key, value) in candidate.as_object().into_iter().flatten() {
+                if key != "content" {
+                    target[key] = value.clone();
+                }
+            }
+
+            let Some(parts) = candidate["content"]["parts"].as_array() else {
+                continue;
+            };
+            target["content"]["role"] = candidate["content"]["role"].clone();
+            if !target["content"]["parts"].is_array() {
+                target["content"]["parts"] = json!([]);
+            }
+
+            for part in parts {
+                let thought = part["thought"].as_bool() == Some(true);
+                if let (0, false, Some(text)) = (index, thought, part["text"].as_str()) {
+                    on_delta(&Message::text(Role::Model, text.to_owned()));
+                }
+
+                let Some(target_parts) = target["content"]["parts"].as_array_mut() else {
+                    continue;
+                };
+                match (target_parts.last_mut(), part["text"].as_str()) {
+                    (Some(last), Some(text)) if last["text"].is_string() && last["thought"] == part["thought"] => {
+                        let joined = last["text"].as_str().unwrap_or_default().to_owned() + text;
+                        for (key, value) in part.as_object().into_iter().flatten() {
+                            last[key] = value.clone();
+                        }
+                        last["text"] = Value::String(joined);
+                    },
+                    // Function calls come complete in a single part.
+                    _ => target_parts.push(part.clone()),
+                }
+            }
+        }
+    }
+}
+
+// Headers or query parameters.
+type Pairs = Vec<(&'static str, String)>;
+
+fn as_pairs<'a>(pairs: &'a [(&'static str, String)]) -> Vec<(&'a str, &'a str)> {
+    pairs.iter().map(|(k, v)| (*k, v.as_str())).collect()
+}
 
 pub struct GcpChat {
     system_prompt: String,
@@ -16,6 +141,7 @@ pub struct GcpChat {
     config: Config,
     client: Box<dyn Client>,
     tools: Vec<ToolSpec>,
+    candidates: Candidates,
 }
 
 impl GcpChat {
@@ -26,20 +152,19 @@ impl GcpChat {
             config,
             client,
             tools,
+            candidates: Candidates::default(),
         })
     }
 
-    fn prep_payload(&mut self, messages: &[Message], tools: ToolChoice) -> Value {
-
-        let mut payload = json!({
-            "systemInstruction": {
-                "parts":
-                  { "text": self.system_prompt }
-            }
-        });
-
+    fn push_messages(&mut self, messages: &[Message]) {
         for message in messages {
-            if let Message::Text(txt) = message {
+            if let Message::Text(Text {role: Role::System, message}) = message {
+                // GCP has no system messages in the conversation.
+                self.history.push(json!({
+                    "role": role_to_llm(self.config.provider, Role::User),
+                    "parts": [{"text": format!("System instruction: {}", message)}]
+                }));
+            } else if let Message::Text(txt) = message {
                 self.history.push(json!({
                     "role": role_to_llm(self.config.provider, txt.role),
                     "parts": [{"text": txt.message}]
@@ -59,6 +184,18 @@ impl GcpChat {
                 }));
             }
         }
+    }
+
+    fn prep_payload(&mut self, messages: &[Message], tools: ToolChoice) -> Value {
+
+        self.push_messages(messages);
+
+        let mut payload = json!({
+            "systemInstruction": {
+                "parts":
+                  { "text": self.system_prompt }
+            }
+        });
 
         payload["contents"] = Value::Array(self.history.clone());
 
@@ -70,9 +207,25 @@ impl GcpChat {
         util::set_f64_param(&mut payload["generationConfig"], "temperature", &self.config.temperature);
         util::set_f64_param(&mut payload["generationConfig"], "presencePenalty", &self.config.presence_penalty);
         util::set_f64_param(&mut payload["generationConfig"], "frequencyPenalty", &self.config.frequency_penalty);
+        util::set_i64_param(&mut payload["generationConfig"], "candidateCount", &self.config.n);
+
+        if let Some(val) = &self.config.stop_sequences {
+            payload["generationConfig"]["stopSequences"] = json!(val);
+        }
+
+        if let Some(budget) = self.config.thinking_budget {
+            if supports_reasoning(self.config.provider, &self.config.name) {
+                payload["generationConfig"]["thinkingConfig"] = json!({
+                    "thinkingBudget": budget,
+                    "includeThoughts": true
+                });
+            }
+        }
 
-        if let Some(val) = &self.config.stop_sequence {
-            payload["generationConfig"]["stopSequences"] = Value::Array(vec![Value::String(val.clone())]);
+        if !self.config.safety_settings.is_empty() {
+            payload["safetySettings"] = Value::Array(self.config.safety_settings.iter()
+                .map(|(category, threshold)| json!({"category": category, "threshold": threshold}))
+                .collect());
         }
 
         self.prep_tool_use(&mut payload, tools);
@@ -80,6 +233,14 @@ impl GcpChat {
         payload
     }
 
+    // Headers and query parameters that authenticate the request.
+    fn auth(&self) -> (Pairs, Pairs) {
+        match self.config.auth_mode {
+            AuthMode::ApiKey => (vec![], vec![("key", self.config.api_key.clone())]),
+            AuthMode::Bearer => (vec![("Authorization", format!("Bearer {}", self.config.api_key))], vec![]),
+        }
+    }
+
     fn prep_tool_use(&self, payload: &mut Value, tools: ToolChoice) {
         match tools {
             ToolChoice::None => {},
@@ -89,7 +250,7 @@ impl GcpChat {
                         "mode": "AUTO"
                     }
                 });
-                self.add_tools(payload);
+                self.add_tools(payload, |_| true);
             },
             ToolChoice::CallOne => {
                 payload["tool_config"] = json!({
@@ -97,7 +258,7 @@ impl GcpChat {
                         "mode": "ANY"
                     }
                 });
-                self.add_tools(payload);
+                self.add_tools(payload, |_| true);
             },
             ToolChoice::Force(tool) => {
                 payload["tool_config"] = json!({
@@ -106,65 +267,112 @@ impl GcpChat {
                       "allowed_function_names": [tool]
                     },
                 });
-                self.add_tools(payload);
+                self.add_tools(payload, |_| true);
+            },
+            ToolChoice::Except(excluded) => {
+                payload["tool_config"] = json!({
+                    "function_calling_config": {
+                        "mode": "AUTO"
+                    }
+                });
+                self.add_tools(payload, |name| !excluded.iter().any(|excl| excl == name));
+            },
+            ToolChoice::Subset(allowed) => {
+                payload["tool_config"] = json!({
+                    "function_calling_config": {
+                        "mode": "AUTO"
+                    }
+                });
+                self.add_tools(payload, |name| allowed.iter().any(|allow| allow == name));
             },
         };
     }
 
-    fn add_tools(&self, payload: &mut Value) {
-        let mut arr = Vec::with_capacity(self.tools.len());
-        for spec in self.tools.iter() {
-            arr.push(json!({
-                "name": spec.name,
-                "description": spec.description,
-                "parameters": tool_params_to_value(&spec.params, self.config.provider)
-            }));
-        }
-        payload["tools"] = json!([{
+    fn add_tools(&self, payload: &mut Value, include: impl Fn(&str) -> bool) {
+        payload["tools"] = Self::tools_to_json(self.tools.iter().filter(|spec| include(&spec.name)), self.config.provider);
+    }
+
+    pub(super) fn tools_to_json<'a>(tools: impl Iterator<Item = &'a ToolSpec>, provider: ModelProvider) -> Value {
+        let arr: Vec<Value> = tools.map(|spec| json!({
+            "name": spec.name,
+            "description": spec.description,
+            "parameters": tool_params_to_value(&spec.params, provider)
+        })).collect();
+        json!([{
             "function_declarations": arr
-        }]);
+        }])
     }
 
     fn check_for_error(&self, response: &Value) -> Result<(), Error> {
         if let Some(error) = response.get("error") {
-            let errmes = val_as_str!(error["message"], "error message").to_owned();
-            return Err(Error::LLMErrorMessage(errmes));
+            return Err(llm_to_error(self.config.provider, error)?);
+        }
+        if let Some(reason) = response["promptFeedback"]["blockReason"].as_str() {
+            return Err(Error::PromptBlocked(reason.to_owned()));
         }
         Ok(())
     }
 
-    fn process_response(&mut self, response: Value) -> Result<Vec<Message>, Error> {
+    fn process_response(&mut self, response: Value) -> Result<Vec<Vec<Message>>, Error> {
 
-        self.check_for_error(&response)?;
+        self.check_for_error(&response)
+            .inspect_err(|err| log_debug!("{} returned error: {}", self.config.provider, err))?;
 
-        let mut result = Vec::new();
+        let mut candidates = Vec::new();
+        let mut entries = Vec::new();
+        let mut stop_reasons = Vec::new();
+        let mut reasoning = Vec::new();
 
-        for candidate in response["candidates"]
+        let response_candidates = response["candidates"]
             .as_array()
-            .ok_or(Error::LLMResponseError("can't enumerate messages in the response."))?
-        {
-            self.history.push(candidate["content"].clone());
+            .ok_or(Error::LLMResponseError("can't enumerate messages in the response."))?;
+
+        if response_candidates.is_empty() {
+            return Err(Error::EmptyResponse);
+        }
+
+        for candidate in response_candidates {
+            let parts = match (candidate["content"]["parts"].as_array(), candidate["finishReason"].as_str()) {
+                (Some(parts), _) => parts,
+                // Blocked candidates (SAFETY, RECITATION, ...) come without content.
+                (None, Some(reason)) => return Err(Error::ResponseStopped(reason.to_owned())),
+                (None, None) => return Err(Error::LLMResponseError("unexpected answer format, can't enumerate message parts.")),
+            };
+
+            entries.push(vec![candidate["content"].clone()]);
+            stop_reasons.push(candidate["finishReason"].as_str().map(|r| llm_to_stop_reason(self.config.provider, r)));
+
+            let mut result = Vec::new();
+            let mut thoughts: Option<String> = None;
 
             let role = llm_to_role(val_as_str!(candidate["content"]["role"], "message role"))?;
 
-            for part in candidate["content"]["parts"]
-                .as_array()
-                .ok_or(Error::LLMResponseError("unexpected answer format, can't enumerate message parts."))?
-            {
-                if part["functionCall"].is_object() {
+            for part in parts {
+                if part["thought"].as_bool() == Some(true) {
+                    let text = part["text"].as_str().unwrap_or_default();
+                    thoughts = Some(match thoughts {
+                        Some(prev) => prev + "\n" + text,
+                        None => text.to_owned(),
+                    });
+                } else if part["functionCall"].is_object() {
                     let name = val_as_str!(part["functionCall"]["name"], "tool name").to_owned();
                     let mut params = Vec::new();
 
-                    for (k, v) in part["functionCall"]["args"]
-                        .as_object()
-                        .ok_or(Error::LLMResponseError("can't enumerate tool call parameters."))?
-                    {
-                        let name = k.clone();
-                        let value = v.clone();
-                        params.push(ToolParam {name, value});
+                    // Gemini omits args for the tools without parameters.
+                    let args = &part["functionCall"]["args"];
+                    if !args.is_null() {
+                        for (k, v) in args
+                            .as_object()
+                            .ok_or(Error::LLMResponseError("can't enumerate tool call parameters."))?
+                        {
+                            let name = k.clone();
+                            let value = v.clone();
+                            params.push(ToolParam {name, value});
+                        }
                     }
 
-                    result.push(Message::ToolCall(ToolCall{call_id: String::new(), name, params}));
+                    let raw_arguments = (!args.is_null()).then(|| args.to_string());
+                    result.push(Message::ToolCall(ToolCall{call_id: String::new(), name, params, raw_arguments}));
 
                 } else if part["text"].is_string() {
                     let message = part["text"].as_str().unwrap().to_owned();
@@ -173,48 +381,168 @@ impl GcpChat {
                     return Err(Error::LLMResponseError("unexpected message type."))
                 }
             }
+
+            candidates.push(result);
+            reasoning.push(thoughts);
         }
 
-        Ok(result)
+        log_debug!("{} returned {} candidate(s), stop reasons {:?}", self.config.provider, candidates.len(), stop_reasons);
+
+        self.candidates.keep(&mut self.history, entries, stop_reasons, reasoning);
+        self.candidates.set_usage(llm_to_usage(self.config.provider, &response));
+
+        Ok(candidates)
     }
 }
 
 impl LLMChat for GcpChat {
 
-    fn get_inference(&mut self, messages: &[Message], tools: ToolChoice) -> Result<Vec<Message>, Error> {
+    fn get_candidates(&mut self, messages: &[Message], tools: ToolChoice) -> Result<Vec<Vec<Message>>, Error> {
+        if util::has_prefill(messages) {
+            return Err(util::prefill_error(self.config.provider));
+        }
 
         let payload = self.prep_payload(messages, tools);
 
-        let params = &[("key", self.config.api_key.as_ref())];
-
-        let response = self.client.make_json_request(&self.config.api_url, payload, &[], params)?;
+        let (headers, params) = self.auth();
+        let headers = util::with_extra_headers(&as_pairs(&headers), &self.config);
+        let response = self.client.make_json_request(&self.config.api_url, payload, &headers, &as_pairs(&params))?;
 
         self.process_response(response)
     }
 
+    fn get_inference_streaming(&mut self, messages: &[Message], tools: ToolChoice, on_delta: &mut dyn FnMut(&Message)) -> Result<Vec<Message>, Error> {
+        if util::has_prefill(messages) {
+            return Err(util::prefill_error(self.config.provider));
+        }
+
+        let payload = self.prep_payload(messages, tools);
+        let url = self.config.api_url.replace(":generateContent", ":streamGenerateContent");
+
+        let (headers, params) = self.auth();
+        let headers = util::with_extra_headers(&as_pairs(&headers), &self.config);
+
+        let mut chunks = JsonChunks::default();
+        let mut response = json!({});
+        self.client.make_streaming_request(&url, payload, &headers, &as_pairs(&params), &mut |line| {
+            for chunk in chunks.push(line) {
+                merge_chunk(&mut response, serde_json::from_str(&chunk)?, on_delta);
+            }
+            Ok(())
+        })?;
+
+        // The merged response is the same as the one of `generateContent`, so is the history.
+        Ok(self.process_response(response)?.into_iter().next().unwrap_or_default())
+    }
+
+    fn select_candidate(&mut self, index: usize) -> Result<(), Error> {
+        self.candidates.select(&mut self.history, index)
+    }
+
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.candidates.stop_reason()
+    }
+
+    fn reasoning(&self) -> Option<String> {
+        self.candidates.reasoning()
+    }
+
+    fn usage(&self) -> Option<Usage> {
+        self.candidates.usage()
+    }
+
+    fn provider(&self) -> ModelProvider {
+        self.config.provider
+    }
+
+    fn model_name(&self) -> &str {
+        &self.config.name
+    }
+
     fn clear_history(&mut self) {
         self.history.clear();
+        self.candidates.clear();
     }
 
     fn set_system_prompt(&mut self, prompt: String) {
         self.system_prompt = prompt;
     }
+
+    fn get_inference_with_system(&mut self, system: &str, messages: &[Message], tools: ToolChoice) -> Result<Vec<Message>, Error> {
+        let stored = std::mem::replace(&mut self.system_prompt, system.to_owned());
+        let result = self.get_inference(messages, tools);
+        self.system_prompt = stored;
+        result
+    }
+
+    fn add_tool(&mut self, tool: ToolSpec) {
+        self.tools.push(tool);
+    }
+
+    fn set_tools(&mut self, tools: Vec<ToolSpec>) {
+        self.tools = tools;
+    }
+
+    fn add_messages(&mut self, messages: &[Message]) {
+        self.push_messages(messages);
+    }
+
+    fn export_history(&self) -> Vec<Value> {
+        self.history.clone()
+    }
+
+    fn import_history(&mut self, history: Vec<Value>) {
+        self.history = history;
+        self.candidates.clear();
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::tools_to_json;
     use crate::request::stub::StubClient;
     use crate::llm::Role;
     use crate::tools::{ParamType, ToolParam};
 
+    #[test]
+    fn test_except_tools() {
+        let config = Config::new("gcp".try_into().expect("determine model provider"), 
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        let tools = ["tool1", "tool2", "tool3"].iter()
+            .map(|name| ToolSpec { name: name.to_string(), description: String::new(), params: vec![] })
+            .collect();
+        let mut chat = GcpChat::new(config, Box::new(StubClient::with_response(Value::Null)), tools).expect("GcpChat initialization");
+
+        let payload = chat.prep_payload(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::Except(vec!["tool2".to_owned()]));
+        let names: Vec<&str> = payload["tools"][0]["function_declarations"].as_array().expect("tools array").iter()
+            .map(|spec| spec["name"].as_str().expect("tool name"))
+            .collect();
+        assert_eq!(names, vec!["tool1", "tool3"]);
+    }
+
+    #[test]
+    fn test_subset_tools() {
+        let config = Config::new("gcp".try_into().expect("determine model provider"), 
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        let tools = ["tool1", "tool2", "tool3"].iter()
+            .map(|name| ToolSpec { name: name.to_string(), description: String::new(), params: vec![] })
+            .collect();
+        let mut chat = GcpChat::new(config, Box::new(StubClient::with_response(Value::Null)), tools).expect("GcpChat initialization");
+
+        let payload = chat.prep_payload(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::Subset(vec!["tool2".to_owned()]));
+        assert_eq!(payload["tools"][0]["function_declarations"].as_array().expect("tools array").len(), 1);
+        assert_eq!(payload["tools"][0]["function_declarations"][0]["name"], "tool2");
+    }
+
     #[test]
     fn test_request_response_ok() {
         let config = Config {
             provider: "gcp".try_into().expect("determine model provider"),
             name: "<model-name>".to_owned(),
             api_key: "<api-key>".to_owned(),
+            auth_mode: AuthMode::ApiKey,
             api_url: "<api-uri>".to_owned(),
             api_version: Some("<api-ver>".to_owned()),
             max_tokens: Some(4096),
@@ -224,7 +552,16 @@ mod tests {
             top_k: Some(5),
             frequency_penalty: Some(-0.11),
             presence_penalty: Some(0.22),
-            stop_sequence: Some("<stop>".to_owned()),
+            stop_sequences: Some(vec!["<stop>".to_owned()]),
+            reasoning_effort: None,
+            thinking_budget: None,
+            prompt_caching: false,
+            safety_settings: vec![],
+            logprobs: None,
+            aws_credentials: None,
+            extra_headers: vec![],
+            omit_params: vec![],
+            non_streaming_max_tokens: None,
         };
 
         let sys_msg = "test sys message";
@@ -266,9 +603,8 @@ mod tests {
                 "temperature": config.temperature.unwrap(),
                 "presencePenalty": config.presence_penalty.unwrap(),
                 "frequencyPenalty": config.frequency_penalty.unwrap(),
-                "stopSequences": [
-                    config.stop_sequence.as_ref().unwrap()
-                ]
+                "candidateCount": config.n.unwrap(),
+                "stopSequences": config.stop_sequences.as_ref().unwrap()
             },
             "tool_config": {
                 "function_calling_config": {
@@ -328,6 +664,7 @@ mod tests {
             provider: "gcp".try_into().expect("determine model provider"),
             name: "<model-name>".to_owned(),
             api_key: "<api-key>".to_owned(),
+            auth_mode: AuthMode::ApiKey,
             api_url: "<api-uri>".to_owned(),
             api_version: Some("<api-ver>".to_owned()),
             max_tokens: Some(4096),
@@ -337,7 +674,16 @@ mod tests {
             top_k: Some(5),
             frequency_penalty: Some(-0.11),
             presence_penalty: Some(0.22),
-            stop_sequence: Some("<stop>".to_owned()),
+            stop_sequences: Some(vec!["<stop>".to_owned()]),
+            reasoning_effort: None,
+            thinking_budget: None,
+            prompt_caching: false,
+            safety_settings: vec![],
+            logprobs: None,
+            aws_credentials: None,
+            extra_headers: vec![],
+            omit_params: vec![],
+            non_streaming_max_tokens: None,
         };
 
         let sys_msg = "test sys message";
@@ -377,9 +723,8 @@ mod tests {
                 "temperature": config.temperature.unwrap(),
                 "presencePenalty": config.presence_penalty.unwrap(),
                 "frequencyPenalty": config.frequency_penalty.unwrap(),
-                "stopSequences": [
-                    config.stop_sequence.as_ref().unwrap()
-                ]
+                "candidateCount": config.n.unwrap(),
+                "stopSequences": config.stop_sequences.as_ref().unwrap()
             },
             "tool_config": {
                 "function_calling_config": {
@@ -424,8 +769,10 @@ mod tests {
         
         let response = chat.get_inference(&messages, ToolChoice::Auto);
 
-        if let Err(Error::LLMErrorMessage(msg)) = response {
-            assert_eq!(msg, model_msg2);
+        if let Err(Error::ProviderError { code, kind, message }) = response {
+            assert_eq!(code, Some("API_KEY_INVALID".to_owned()));
+            assert_eq!(kind, Some("INVALID_ARGUMENT".to_owned()));
+            assert_eq!(message, model_msg2);
         } else {
             panic!("type mismatch");
         }
@@ -437,6 +784,7 @@ mod tests {
             provider: "gcp".try_into().expect("determine model provider"),
             name: "<model-name>".to_owned(),
             api_key: "<api-key>".to_owned(),
+            auth_mode: AuthMode::ApiKey,
             api_url: "<api-uri>".to_owned(),
             api_version: Some("<api-ver>".to_owned()),
             max_tokens: Some(4096),
@@ -446,7 +794,16 @@ mod tests {
             top_k: Some(5),
             frequency_penalty: Some(-0.11),
             presence_penalty: Some(0.22),
-            stop_sequence: Some("<stop>".to_owned()),
+            stop_sequences: Some(vec!["<stop>".to_owned()]),
+            reasoning_effort: None,
+            thinking_budget: None,
+            prompt_caching: false,
+            safety_settings: vec![],
+            logprobs: None,
+            aws_credentials: None,
+            extra_headers: vec![],
+            omit_params: vec![],
+            non_streaming_max_tokens: None,
         };
 
         let tools = vec![
@@ -454,36 +811,16 @@ mod tests {
                 name: "tool1".to_owned(),
                 description: "tool desc 1".to_owned(),
                 params: vec![
-                    ToolParam {
-                        name: "tool1_param1".to_string(),
-                        description: "tool1_param1 desc".to_string(), 
-                        data_type: ParamType::Integer, 
-                        required: true
-                    },
-                    ToolParam {
-                        name: "tool1_param2".to_string(),
-                        description: "tool1_param2 desc".to_string(), 
-                        data_type: ParamType::String, 
-                        required: false
-                    },
+                    ToolParam::new("tool1_param1".to_string(), "tool1_param1 desc".to_string(), ParamType::Integer, true),
+                    ToolParam::new("tool1_param2".to_string(), "tool1_param2 desc".to_string(), ParamType::String, false),
                 ]
             },
             ToolSpec {
                 name: "tool2".to_owned(),
                 description: "tool desc 2".to_owned(),
                 params: vec![
-                    ToolParam {
-                        name: "tool2_param1".to_string(),
-                        description: "tool2_param1 desc".to_string(), 
-                        data_type: ParamType::Boolean, 
-                        required: false
-                    },
-                    ToolParam {
-                        name: "tool2_param2".to_string(),
-                        description: "tool2_param2 desc".to_string(), 
-                        data_type: ParamType::Number, 
-                        required: true
-                    },
+                    ToolParam::new("tool2_param1".to_string(), "tool2_param1 desc".to_string(), ParamType::Boolean, false),
+                    ToolParam::new("tool2_param2".to_string(), "tool2_param2 desc".to_string(), ParamType::Number, true),
                 ]
             },
         ];
@@ -504,7 +841,7 @@ mod tests {
         messages.push(Message::text(Role::Model, model_msg1.to_owned()));
         messages.push(Message::text(Role::User, user_msg2.to_owned()));
         let mut expected_messages = Vec::new();
-        expected_messages.push(Message::tool_use(call_id.to_owned(), call_tool.to_owned(), call_params));
+        expected_messages.push(Message::tool_call(call_id.to_owned(), call_tool.to_owned(), call_params));
 
         let expected_headers = vec![];
         let expected_params = vec![
@@ -532,9 +869,8 @@ mod tests {
                 "temperature": config.temperature.unwrap(),
                 "presencePenalty": config.presence_penalty.unwrap(),
                 "frequencyPenalty": config.frequency_penalty.unwrap(),
-                "stopSequences": [
-                    config.stop_sequence.as_ref().unwrap()
-                ]
+                "candidateCount": config.n.unwrap(),
+                "stopSequences": config.stop_sequences.as_ref().unwrap()
             },
             "tool_config": {
                 "function_calling_config": {
@@ -609,6 +945,8 @@ mod tests {
             }
         });
 
+        assert_eq!(tools_to_json(&tools, config.provider), expected_payload["tools"]);
+
         let client = Box::new(StubClient::new(expected_headers, expected_params, expected_payload, response_body));
 
         let mut chat = GcpChat::new(config, client, tools).expect("Chat initialization");
@@ -630,5 +968,225 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_safety_settings_blocked() {
+        let mut config = Config::new("gcp".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        config.safety_settings = vec![("HARM_CATEGORY_DANGEROUS_CONTENT".to_owned(), "BLOCK_ONLY_HIGH".to_owned())];
+
+        let user_msg = "test user message";
+
+        let expected_params = vec![
+            ("key".to_owned(), config.api_key.clone()),
+        ];
+        let expected_payload = json!({
+            "systemInstruction": {
+                "parts": { "text": "" }
+            },
+            "contents": [
+              {"role": "user", "parts": [{"text": user_msg}]},
+            ],
+            "generationConfig": {},
+            "safetySettings": [
+                {"category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "BLOCK_ONLY_HIGH"}
+            ]
+        });
+
+        let response_body = json!({
+            "promptFeedback": {
+                "blockReason": "SAFETY"
+            }
+        });
+
+        let client = Box::new(StubClient::new(vec![], expected_params, expected_payload, response_body));
+
+        let mut chat = GcpChat::new(config, client, vec![]).expect("Chat initialization");
+
+        let response = chat.get_inference(&[Message::text(Role::User, user_msg.to_owned())], ToolChoice::None);
+
+        if let Err(Error::PromptBlocked(reason)) = response {
+            assert_eq!(reason, "SAFETY");
+        } else {
+            panic!("type mismatch");
+        }
+    }
+
+    #[test]
+    fn test_response_stopped() {
+        let config = Config::new("gcp".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+
+        let user_msg = "test user message";
+
+        let expected_params = vec![
+            ("key".to_owned(), config.api_key.clone()),
+        ];
+        let expected_payload = json!({
+            "systemInstruction": {
+                "parts": { "text": "" }
+            },
+            "contents": [
+              {"role": "user", "parts": [{"text": user_msg}]},
+            ],
+            "generationConfig": {}
+        });
+
+        let response_body = json!({
+            "candidates": [
+              {
+                "finishReason": "RECITATION",
+                "index": 0
+              }
+            ]
+        });
+
+        let client = Box::new(StubClient::new(vec![], expected_params, expected_payload, response_body));
+
+        let mut chat = GcpChat::new(config, client, vec![]).expect("Chat initialization");
+
+        let response = chat.get_inference(&[Message::text(Role::User, user_msg.to_owned())], ToolChoice::None);
+
+        if let Err(Error::ResponseStopped(reason)) = response {
+            assert_eq!(reason, "RECITATION");
+        } else {
+            panic!("type mismatch");
+        }
+    }
+
+    #[test]
+    fn test_tool_call_without_args() {
+        let config = Config::new("gcp".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+
+        let client = Box::new(StubClient::with_response(json!({"candidates": [{
+            "content": {"parts": [{"functionCall": {"name": "LIST_PROJECTS"}}], "role": "model"},
+            "finishReason": "STOP"
+        }]})));
+        let mut chat = GcpChat::new(config, client, vec![]).expect("Chat initialization");
+
+        let response = chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::Auto).expect("receive response");
+        if let Message::ToolCall(call) = &response[0] {
+            assert_eq!(call.name, "LIST_PROJECTS");
+            assert!(call.params.is_empty());
+            assert_eq!(call.raw_arguments, None);
+        } else {
+            panic!("type mismatch");
+        }
+    }
+
+    #[test]
+    fn test_empty_response() {
+        let config = Config::new("gcp".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+
+        let client = Box::new(StubClient::with_response(json!({"candidates": []})));
+        let mut chat = GcpChat::new(config, client, vec![]).expect("Chat initialization");
+
+        let response = chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None);
+        assert!(matches!(response, Err(Error::EmptyResponse)));
+    }
+
+    #[test]
+    fn test_bearer_auth() {
+        use crate::request::stub::RecordingClient;
+
+        let mut config = Config::new("gcp".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<access-token>".to_owned(), "<api-uri>".to_owned());
+        config.auth_mode = AuthMode::Bearer;
+
+        let client = RecordingClient::new(json!({"candidates": [{"content": {"parts": [{"text": "hi"}], "role": "model"}, "finishReason": "STOP"}]}));
+
+        let mut chat = GcpChat::new(config, Box::new(client.clone()), vec![]).expect("Chat initialization");
+        chat.get_inference(&[Message::text(Role::User, "hello".to_owned())], ToolChoice::None).expect("receive response");
 
-}
\ No newline at end of file
+        let requests = client.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].headers, vec![("Authorization".to_owned(), "Bearer <access-token>".to_owned())]);
+        assert!(requests[0].params.iter().all(|(k, _)| k != "key"));
+    }
+
+    #[test]
+    fn test_system_message() {
+        let config = Config::new("gcp".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+
+        let expected_params = vec![
+            ("key".to_owned(), config.api_key.clone()),
+        ];
+        let expected_payload = json!({
+            "systemInstruction": {
+                "parts": { "text": "sys" }
+            },
+            "contents": [
+              {"role": "user", "parts": [{"text": "System instruction: be brief"}]},
+              {"role": "user", "parts": [{"text": "hi"}]},
+            ],
+            "generationConfig": {}
+        });
+
+        let response_body = json!({
+            "candidates": [{
+                "content": {"parts": [{"text": "hello"}], "role": "model"},
+                "finishReason": "STOP"
+            }]
+        });
+
+        let client = Box::new(StubClient::new(vec![], expected_params, expected_payload, response_body));
+
+        let mut chat = GcpChat::new(config, client, vec![]).expect("Chat initialization");
+        chat.set_system_prompt("sys".to_owned());
+
+        let messages = [Message::system("be brief".to_owned()), Message::text(Role::User, "hi".to_owned())];
+        chat.get_inference(&messages, ToolChoice::None).expect("receive response");
+    }
+
+    #[test]
+    fn test_streaming() {
+        use crate::config::ModelProvider;
+        use crate::request::stub::RecordingClient;
+
+        // Chunks are pretty-printed over several lines, as returned by `streamGenerateContent`.
+        let stream = r#"[{
+  "candidates": [{"content": {"parts": [{"text": "Use {braces"}], "role": "model"}, "index": 0}]
+}
+,
+{
+  "candidates": [{"content": {"parts": [{"text": "} and \"quotes\"."}], "role": "model"}, "index": 0}]
+}
+,
+{
+  "candidates": [{"content": {"parts": [{"functionCall": {"name": "SHELL", "args": {"command": "ls"}}}], "role": "model"},
+    "finishReason": "STOP", "index": 0}],
+  "usageMetadata": {"promptTokenCount": 5, "candidatesTokenCount": 7}
+}
+]"#;
+
+        let config = Config::new(ModelProvider::GCP, "gemini".to_owned(), "<api-key>".to_owned(), String::new());
+        let client = RecordingClient::streaming(stream);
+        let mut chat = GcpChat::new(config, Box::new(client.clone()), vec![]).expect("GcpChat initialization");
+
+        let mut deltas = vec![];
+        let response = chat.get_inference_streaming(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::Auto,
+            &mut |delta| if let Message::Text(txt) = delta { deltas.push(txt.message.clone()) }).expect("receive response");
+
+        assert_eq!(deltas, vec!["Use {braces", "} and \"quotes\"."]);
+        assert_eq!(response.len(), 2);
+        assert!(matches!(&response[0], Message::Text(txt) if txt.message == "Use {braces} and \"quotes\"."));
+        assert!(matches!(&response[1], Message::ToolCall(call) if call.name == "SHELL"));
+
+        assert_eq!(client.requests()[0].url, "https://generativelanguage.googleapis.com/v1beta/models/gemini:streamGenerateContent");
+        assert_eq!(chat.export_history()[1], json!({"role": "model", "parts": [
+            {"text": "Use {braces} and \"quotes\"."},
+            {"functionCall": {"name": "SHELL", "args": {"command": "ls"}}},
+        ]}));
+        assert_eq!(chat.stop_reason(), Some(StopReason::EndTurn));
+        assert_eq!(chat.usage(), Some(Usage { input_tokens: 5, output_tokens: 7 }));
+
+        // Errors are streamed as an array with a single error object.
+        let client = RecordingClient::streaming(r#"[{"error": {"code": 400, "message": "API key not valid.", "status": "INVALID_ARGUMENT"}}]"#);
+        let config = Config::new(ModelProvider::GCP, "gemini".to_owned(), "<api-key>".to_owned(), String::new());
+        let mut chat = GcpChat::new(config, Box::new(client), vec![]).expect("GcpChat initialization");
+        let err = chat.get_inference_streaming(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::Auto, &mut |_| {});
+        assert!(matches!(err, Err(Error::ProviderError { message, .. }) if message == "API key not valid."));
+    }
+}
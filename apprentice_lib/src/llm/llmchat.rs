@@ -6,21 +6,167 @@ use crate::request::Client;
 use crate::tools::{ToolChoice, ToolSpec};
 use super::anthropic::AnthropicChat;
 use super::gcp::GcpChat;
-use super::Message;
+use super::cohere::CohereChat;
+use super::echo::EchoChat;
+use super::metrics::{MeteredChat, Metrics};
+use super::util;
+use super::{Message, Role, StopReason, TokenLogprob, ToolCall, Usage};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// User message that asks the model to continue a truncated response.
+const CONTINUE_PROMPT: &str = "Continue your previous response exactly from where it was cut off.";
+
+/// Average number of characters per token used by `LLMChat::estimate_tokens`.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Features of a model, e.g. to hide the UI elements the model doesn't support.
+/// The values are heuristics based on the known model names, unknown models get conservative values,
+/// so a feature may be supported even if it's reported as unsupported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Model can call tools.
+    pub supports_tools: bool,
+    /// Model accepts images.
+    pub supports_vision: bool,
+    /// `LLMChat::get_inference_streaming` passes the text in several deltas while it's generated.
+    pub supports_streaming: bool,
+    /// Context window in tokens, if known.
+    pub max_context: Option<u64>,
+}
 
 /// Chat with LLM with storing history.
 pub trait LLMChat {
 
     /// Add input messages to the message history.
     /// Input messages contains user message(s), and tool call results.
-    /// Returns n messages as the result, and/or tool call requests.
-    fn get_inference(&mut self, messages: &[Message], tools: ToolChoice) -> Result<Vec<Message>, Error>;
+    /// The history already has the messages of the previous inferences, so pass only the new ones:
+    /// re-sent messages are appended again (re-sent tool results panic in debug builds).
+    /// With Anthropic, the last message can be a model message, which the response continues (prefill),
+    /// e.g. `{` to force JSON output; other providers return an error for it.
+    /// System messages are placed according to the provider: OpenAI keeps them inline,
+    /// Anthropic receives them as user notes, and GCP appends them to the system instruction.
+    /// Returns messages of the first candidate as the result, and/or tool call requests.
+    fn get_inference(&mut self, messages: &[Message], tools: ToolChoice) -> Result<Vec<Message>, Error> {
+        Ok(self.get_candidates(messages, tools)?.into_iter().next().unwrap_or_default())
+    }
+
+    /// Same as `get_inference`, but passes the text of the first candidate to `on_delta`
+    /// as `Message::Text` deltas while the response is generated. Tool calls are returned when complete.
+    /// Providers without streaming support pass the whole text as a single delta.
+    fn get_inference_streaming(&mut self, messages: &[Message], tools: ToolChoice, on_delta: &mut dyn FnMut(&Message)) -> Result<Vec<Message>, Error> {
+        let result = self.get_inference(messages, tools)?;
+        result.iter().filter(|message| matches!(message, Message::Text(_))).for_each(on_delta);
+        Ok(result)
+    }
+
+    /// Same as `get_inference`, but returns messages of all `n` candidates.
+    /// The first candidate is added to the history, use `select_candidate` to keep another one.
+    fn get_candidates(&mut self, messages: &[Message], tools: ToolChoice) -> Result<Vec<Vec<Message>>, Error>;
+
+    /// Same as `get_candidates`, but returns the first message of each candidate with its score,
+    /// the average log probability of its tokens, sorted from the best; the best candidate is kept in the history.
+    /// The ranking is best-effort: candidates without log probabilities, e.g. when `Config::logprobs` is not set
+    /// or the provider doesn't return them, score `f64::NEG_INFINITY` and stay in the order of the response.
+    fn get_ranked_candidates(&mut self, messages: &[Message], tools: ToolChoice) -> Result<Vec<(Message, f64)>, Error> {
+        let candidates = self.get_candidates(messages, tools)?;
+
+        let mut ranked = Vec::with_capacity(candidates.len());
+        for (index, candidate) in candidates.into_iter().enumerate() {
+            self.select_candidate(index)?;
+            let score = self.logprobs()
+                .filter(|logprobs| !logprobs.is_empty())
+                .map(|logprobs| logprobs.iter().map(|token| token.logprob).sum::<f64>() / logprobs.len() as f64)
+                .unwrap_or(f64::NEG_INFINITY);
+            if let Some(message) = candidate.into_iter().next() {
+                ranked.push((index, message, score));
+            }
+        }
+
+        // Stable sort keeps the order of the candidates with equal scores.
+        ranked.sort_by(|a, b| b.2.total_cmp(&a.2));
+        if let Some((best, _, _)) = ranked.first() {
+            self.select_candidate(*best)?;
+        }
+
+        Ok(ranked.into_iter().map(|(_, message, score)| (message, score)).collect())
+    }
+
+    /// Replace the candidate kept in the history after the last inference with the candidate `index`.
+    fn select_candidate(&mut self, index: usize) -> Result<(), Error>;
+
+    /// Stop reason of the candidate kept in the history after the last inference.
+    fn stop_reason(&self) -> Option<StopReason>;
+
+    /// Token usage of the last inference, if the provider reported it.
+    /// With Anthropic and `n` greater than 1, usage of all requests is summed.
+    fn usage(&self) -> Option<Usage>;
+
+    /// Reasoning output of the candidate kept in the history after the last inference,
+    /// if the model returned it.
+    fn reasoning(&self) -> Option<String>;
+
+    /// Log probabilities of the output tokens of the candidate kept in the history after the last inference,
+    /// if they were requested with `Config::logprobs` and returned by the provider (OpenAI only).
+    fn logprobs(&self) -> Option<Vec<TokenLogprob>> {
+        None
+    }
+
+    /// Ask the model to continue the last response, e.g. when it was stopped with `StopReason::MaxTokens`.
+    fn continue_inference(&mut self) -> Result<Vec<Message>, Error> {
+        self.get_inference(&[Message::text(Role::User, CONTINUE_PROMPT.to_owned())], ToolChoice::None)
+    }
+
+    /// Rough number of prompt tokens of the history and the `messages`, e.g. to trim the history
+    /// before the provider rejects a request that exceeds the context window.
+    /// Counts 4 characters of the serialized JSON per token: English prose averages 4 characters per token,
+    /// JSON markup and code are denser, so expect an error of up to 30% either way.
+    /// The system prompt and the tool definitions are not counted.
+    fn estimate_tokens(&self, messages: &[Message]) -> usize {
+        let history: usize = self.export_history().iter().map(|entry| entry.to_string().chars().count()).sum();
+        let pending: usize = messages.iter()
+            .map(|message| serde_json::to_string(message).map(|s| s.chars().count()).unwrap_or(0))
+            .sum();
+        (history + pending).div_ceil(CHARS_PER_TOKEN)
+    }
+
+    /// Provider of the model, e.g. to show it in a UI, or to check the provider-specific behavior.
+    fn provider(&self) -> ModelProvider;
+
+    /// Model name as set in the config.
+    fn model_name(&self) -> &str;
+
+    /// What the model supports, guessed from the provider and the model name, see `Capabilities`.
+    fn capabilities(&self) -> Capabilities {
+        util::model_capabilities(self.provider(), self.model_name())
+    }
 
     /// Clear chat history.
     fn clear_history(&mut self);
 
     /// Update system prompt.
     fn set_system_prompt(&mut self, prompt: String);
+
+    /// Same as `get_inference`, but with `system` as the system prompt of this inference only,
+    /// e.g. to compare prompts in a loop. The stored system prompt is used again by the next inference.
+    fn get_inference_with_system(&mut self, system: &str, messages: &[Message], tools: ToolChoice) -> Result<Vec<Message>, Error>;
+
+    /// Add the tool to the tools provided to the model, takes effect on the next inference.
+    fn add_tool(&mut self, tool: ToolSpec);
+
+    /// Replace the tools provided to the model, takes effect on the next inference.
+    fn set_tools(&mut self, tools: Vec<ToolSpec>);
+
+    /// Add the messages to the history without inference, they are sent with the next one,
+    /// e.g. the results of the tool calls that were not run because the user ended the session.
+    fn add_messages(&mut self, messages: &[Message]);
+
+    /// Return chat history in the provider-specific format (system prompt excluded).
+    fn export_history(&self) -> Vec<Value>;
+
+    /// Replace chat history with the one previously returned by `export_history`
+    /// of a chat with the same provider. Current system prompt is kept.
+    fn import_history(&mut self, history: Vec<Value>);
 }
 
 /* TODO: split LLM and chat. Chat should keep history, LLm is stateless.
@@ -34,10 +180,252 @@ pub trait LLM {
  */
 
 /// Create LLMChat instance.
+/// Returns an error if the tools are not valid (see `ToolSpec::validate`), or two of them have the same name.
 pub fn get_llm_chat(config: Config, client: Box<dyn Client>, tools: Vec<ToolSpec>) -> Result<Box<dyn LLMChat>, Error> {
+    validate_tools(&tools)?;
+    let config = config.without_omitted();
     Ok(match config.provider {
-        ModelProvider::OpenAI => Box::new(OpenAIChat::new(config, client, tools)),
-        ModelProvider::Anthropic => Box::new(AnthropicChat::new(config, client, tools)?),
+        ModelProvider::OpenAI | ModelProvider::DeepSeek | ModelProvider::Groq | ModelProvider::OpenRouter => Box::new(OpenAIChat::new(config, client, tools)),
+        ModelProvider::Anthropic | ModelProvider::Bedrock => Box::new(AnthropicChat::new(config, client, tools)?),
         ModelProvider::GCP => Box::new(GcpChat::new(config, client, tools)?),
+        ModelProvider::Cohere => Box::new(CohereChat::new(config, client, tools)),
+        ModelProvider::Echo => Box::new(EchoChat::new(config, tools)),
     })
-}
\ No newline at end of file
+}
+
+fn validate_tools(tools: &[ToolSpec]) -> Result<(), Error> {
+    let mut names = HashSet::with_capacity(tools.len());
+    for tool in tools {
+        tool.validate()?;
+        if !names.insert(tool.name.as_str()) {
+            return Err(Error::Error(format!("duplicate tool name {}.", tool.name)));
+        }
+    }
+    Ok(())
+}
+
+/// Create LLMChat instance that passes the latency, token usage, and outcome of each inference to `metrics`.
+pub fn get_llm_chat_with_metrics(config: Config, client: Box<dyn Client>, tools: Vec<ToolSpec>, metrics: Box<dyn Metrics>) -> Result<Box<dyn LLMChat>, Error> {
+    let provider = config.provider;
+    Ok(Box::new(MeteredChat::new(get_llm_chat(config, client, tools)?, provider, metrics)))
+}
+
+/// Return the tool definitions in the format sent to the provider, e.g. to inspect or cache the schema.
+/// Echo doesn't send tools to a model, so an empty array is returned for it.
+pub fn tools_to_json(tools: &[ToolSpec], provider: ModelProvider) -> Value {
+    match provider {
+        ModelProvider::OpenAI | ModelProvider::DeepSeek | ModelProvider::Groq | ModelProvider::OpenRouter => OpenAIChat::tools_to_json(tools.iter(), provider),
+        ModelProvider::Anthropic | ModelProvider::Bedrock => AnthropicChat::tools_to_json(tools.iter(), provider),
+        ModelProvider::GCP => GcpChat::tools_to_json(tools.iter(), provider),
+        ModelProvider::Cohere => CohereChat::tools_to_json(tools.iter(), provider),
+        ModelProvider::Echo => Value::Array(vec![]),
+    }
+}
+
+/// Run a single inference without keeping the chat history, e.g. for classification or routing.
+/// Tools, if provided, are offered to the model with `ToolChoice::Auto`.
+pub fn complete(config: Config, client: Box<dyn Client>, system: &str, messages: &[Message], tools: Vec<ToolSpec>) -> Result<Vec<Message>, Error> {
+    let tool_choice = if tools.is_empty() { ToolChoice::None } else { ToolChoice::Auto };
+
+    let mut chat = get_llm_chat(config, client, tools)?;
+    chat.set_system_prompt(system.to_owned());
+    chat.get_inference(messages, tool_choice)
+}
+
+/// Decision on a tool call requested by the model, see `run_tool_loop`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Approval {
+    /// Call the tool.
+    Approve,
+    /// Don't call the tool, the reason is returned to the model as the tool result.
+    Deny(String),
+    /// Stop the loop.
+    Abort,
+}
+
+/// Send the messages and call the tools requested by the model until it responds without tool calls.
+/// Each tool call is passed to `approve` first, approved calls are executed with `call_tool`.
+/// Returns the last response of the model.
+/// On `Approval::Abort` returns `Error::Aborted`, and if `call_tool` fails returns its error.
+/// In both cases the unanswered tool call is removed from the history, so the chat can continue.
+pub fn run_tool_loop(chat: &mut dyn LLMChat,
+    messages: &[Message],
+    mut approve: impl FnMut(&ToolCall) -> Approval,
+    mut call_tool: impl FnMut(&ToolCall) -> Result<String, Error>) -> Result<Vec<Message>, Error>
+{
+    let mut response = chat.get_inference(messages, ToolChoice::Auto)?;
+
+    loop {
+        let mut tool_results = vec![];
+        for message in response.iter() {
+            if let Message::ToolCall(tool_call) = message {
+                let result = match approve(tool_call) {
+                    Approval::Approve => call_tool(tool_call),
+                    Approval::Deny(reason) => Ok(format!("User cancelled the operation with the reason: {}", reason)),
+                    Approval::Abort => Err(Error::Aborted),
+                };
+                let result = match result {
+                    Ok(result) => result,
+                    Err(err) => {
+                        let mut history = chat.export_history();
+                        history.pop();
+                        chat.import_history(history);
+                        return Err(err);
+                    }
+                };
+                tool_results.push(tool_call.to_result(result));
+            }
+        }
+
+        if tool_results.is_empty() {
+            return Ok(response);
+        }
+
+        response = chat.get_inference(&tool_results, ToolChoice::Auto)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::stub::StubClient;
+    use serde_json::json;
+
+    #[test]
+    fn test_complete() {
+        let config = Config::new(ModelProvider::OpenAI, "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+
+        let expected_payload = json!({
+            "model": config.name,
+            "messages": [
+                {"role": "system", "content": "classify"},
+                {"role": "user", "content": "list my vms"},
+            ],
+            "parallel_tool_calls": false,
+        });
+
+        let response_body = json!({
+            "choices": [
+                {"index": 0, "message": {"role": "assistant", "content": "compute"}, "finish_reason": "stop"},
+            ]
+        });
+
+        let client = Box::new(StubClient::new(vec![], vec![], expected_payload, response_body));
+
+        let response = complete(config, client, "classify", &[Message::text(Role::User, "list my vms".to_owned())], vec![])
+            .expect("receive response");
+
+        assert_eq!(response.len(), 1);
+        if let Message::Text(txt) = &response[0] {
+            assert_eq!(txt.message, "compute");
+        } else {
+            panic!("type mismatch");
+        }
+    }
+
+    #[test]
+    fn test_run_tool_loop() {
+        use crate::request::stub::SequenceClient;
+
+        let tool_call = |id: &str| json!({"choices": [{"message": {"role": "assistant", "tool_calls": [
+            {"id": id, "type": "function", "function": {"name": "SHELL", "arguments": "{\"command\": \"ls\"}"}}
+        ]}, "finish_reason": "tool_calls"}]});
+
+        let config = Config::new(ModelProvider::OpenAI, "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        let client = Box::new(SequenceClient::new(vec![
+            tool_call("call_1"),
+            tool_call("call_2"),
+            json!({"choices": [{"message": {"role": "assistant", "content": "done"}, "finish_reason": "stop"}]}),
+            tool_call("call_3"),
+            tool_call("call_4"),
+        ]));
+        let mut chat = get_llm_chat(config, client, vec![]).expect("chat created");
+
+        let mut approvals = vec![Approval::Deny("not now".to_owned()), Approval::Approve].into_iter();
+        let mut calls = vec![];
+        let response = run_tool_loop(chat.as_mut(), &[Message::text(Role::User, "hi".to_owned())],
+            |_| approvals.next().expect("approval"),
+            |tool_call| { calls.push(tool_call.call_id.clone()); Ok("file.txt".to_owned()) })
+            .expect("receive response");
+
+        assert!(matches!(&response[0], Message::Text(txt) if txt.message == "done"));
+        assert_eq!(calls, vec!["call_2"]);
+
+        let history = chat.export_history();
+        assert_eq!(history[2]["content"], "User cancelled the operation with the reason: not now");
+        assert_eq!(history[4]["content"], "file.txt");
+
+        let ret = run_tool_loop(chat.as_mut(), &[Message::text(Role::User, "again".to_owned())],
+            |_| Approval::Abort,
+            |_| panic!("tool must not be called"));
+
+        assert!(matches!(ret, Err(Error::Aborted)));
+        assert_eq!(chat.export_history().len(), history.len() + 1);
+
+        let ret = run_tool_loop(chat.as_mut(), &[Message::text(Role::User, "once more".to_owned())],
+            |_| Approval::Approve,
+            |_| Err(Error::Error("tool failed".to_owned())));
+
+        assert!(matches!(ret, Err(Error::Error(msg)) if msg == "tool failed"));
+        let history_after_error = chat.export_history();
+        assert_eq!(history_after_error.len(), history.len() + 2);
+        assert_eq!(history_after_error.last().expect("last message")["content"], "once more");
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        let config = Config::new(ModelProvider::Echo, "echo".to_owned(), String::new(), String::new());
+        let mut chat = get_llm_chat(config, Box::new(StubClient::with_response(Value::Null)), vec![]).expect("create chat");
+
+        let message = Message::text(Role::User, "list all vms in the project".to_owned());
+        let chars = serde_json::to_string(&message).expect("serialize message").len();
+        assert_eq!(chat.estimate_tokens(std::slice::from_ref(&message)), chars.div_ceil(4));
+        assert_eq!(chat.estimate_tokens(&[]), 0);
+
+        chat.get_inference(&[message], ToolChoice::Auto).expect("receive response");
+        let history: usize = chat.export_history().iter().map(|entry| entry.to_string().len()).sum();
+        assert_eq!(chat.estimate_tokens(&[]), history.div_ceil(4));
+    }
+
+    #[test]
+    fn test_omit_params() {
+        use crate::request::stub::RecordingClient;
+
+        let mut config = Config::new(ModelProvider::OpenAI, "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        config.temperature = Some(0.5);
+        config.top_p = Some(0.9);
+        config.presence_penalty = Some(0.1);
+        config.omit(&["top_p", "presence_penalty"]).expect("omit params");
+        assert!(config.omit(&["api_key"]).is_err());
+
+        let client = RecordingClient::new(json!({"choices": [{"message": {"role": "assistant", "content": "hi"}, "finish_reason": "stop"}]}));
+        let mut chat = get_llm_chat(config.clone(), Box::new(client.clone()), vec![]).expect("create chat");
+        chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None).expect("receive response");
+
+        let payload = &client.requests()[0].payload;
+        assert_eq!(payload["temperature"], 0.5);
+        assert!(payload.get("top_p").is_none());
+        assert!(payload.get("presence_penalty").is_none());
+        assert_eq!(config.top_p, Some(0.9));
+    }
+
+    #[test]
+    fn test_duplicate_tools() {
+        use crate::tools::{ParamType, ToolParam};
+
+        let tool = |name: &str, param: &str| ToolSpec {
+            name: name.to_owned(),
+            description: "tool desc".to_owned(),
+            params: vec![ToolParam::new(param.to_owned(), "param desc".to_owned(), ParamType::String, true)],
+        };
+        let config = Config::new(ModelProvider::Echo, "echo".to_owned(), String::new(), String::new());
+
+        assert!(get_llm_chat(config.clone(), Box::new(StubClient::with_response(Value::Null)), vec![tool("tool1", "a"), tool("tool2", "a")]).is_ok());
+
+        let ret = get_llm_chat(config.clone(), Box::new(StubClient::with_response(Value::Null)), vec![tool("tool1", "a"), tool("tool1", "b")]);
+        assert_eq!(ret.err().expect("duplicate tool").to_string(), "duplicate tool name tool1.");
+
+        let ret = get_llm_chat(config, Box::new(StubClient::with_response(Value::Null)), vec![tool("tool1", "")]);
+        assert_eq!(ret.err().expect("empty param name").to_string(), "tool tool1 has a parameter with empty name.");
+    }
+}
@@ -1,13 +1,17 @@
 use std::fmt::Display;
+use std::ops::AddAssign;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 
 /// Logical roles (provider-independent).
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Role {
     /// System.
     System = 0,
     /// Model.
+    #[serde(rename = "apprentice")]
     Model = 1,
     /// User.
     User = 2,
@@ -25,6 +29,8 @@ impl Display for Role {
 }
 
 /// Chat message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum Message {
     /// Text message.
     Text(Text),
@@ -40,19 +46,24 @@ impl Message {
         Message::Text(Text {role, message})
     }
 
+    /// Create system message, which adds guidance in the middle of the conversation.
+    pub fn system(message: String) -> Self {
+        Message::Text(Text {role: Role::System, message})
+    }
+
     /// Create tool result message.
     pub fn tool_result(call_id: String, name: String, result: String) -> Self {
         Message::ToolResult(ToolResult { call_id, name, result })
     }
 
-    /// Create tool use message.
-    #[cfg(test)]
-    pub(crate) fn tool_use(call_id: String, name: String, params: Vec<ToolParam>) -> Self {
-        Message::ToolCall(ToolCall { call_id, name, params })
+    /// Create tool call message, e.g. to replay a saved transcript or to inject a synthetic call.
+    pub fn tool_call(call_id: String, name: String, params: Vec<ToolParam>) -> Self {
+        Message::ToolCall(ToolCall { call_id, name, params, raw_arguments: None })
     }
 }
 
 /// Chat message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Text {
     /// Role.
     pub role: Role,
@@ -61,6 +72,7 @@ pub struct Text {
 }
 
 /// Tool call result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolResult {
     /// Call id.
     pub call_id: String,
@@ -71,6 +83,7 @@ pub struct ToolResult {
 }
 
 /// Tool call result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCall {
     /// Call id.
     pub call_id: String,
@@ -78,12 +91,174 @@ pub struct ToolCall {
     pub name: String,
     /// Call params.
     pub params: Vec<ToolParam>,
+    /// Arguments as returned by LLM, e.g. for custom parsing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_arguments: Option<String>,
 }
 
 /// Tool parameters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolParam {
     /// Parameter name.
     pub name: String,
     /// Value.
     pub value: Value,
+}
+
+impl ToolCall {
+    /// Create the result message of this call, with the call id and the tool name copied from it.
+    pub fn to_result(&self, result: String) -> Message {
+        Message::tool_result(self.call_id.clone(), self.name.clone(), result)
+    }
+}
+
+/// Reason the model stopped generating.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// Model finished its turn.
+    EndTurn,
+    /// Maximum number of tokens was reached, the response is truncated.
+    MaxTokens,
+    /// One of the stop sequences was generated.
+    StopSequence,
+    /// Model requested a tool call.
+    ToolUse,
+    /// Other provider-specific reason.
+    Other(String),
+}
+
+/// Number of tokens used by an inference.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Prompt tokens, including the history, tool definitions, and cached tokens.
+    pub input_tokens: u64,
+    /// Generated tokens, including reasoning.
+    pub output_tokens: u64,
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+    }
+}
+
+/// Log probability of an output token (OpenAI only).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenLogprob {
+    /// Token.
+    pub token: String,
+    /// Log probability of the token.
+    pub logprob: f64,
+    /// The most likely alternatives at this position with their log probabilities.
+    pub top_logprobs: Vec<(String, f64)>,
+}
+
+/// Maximum number of characters of a tool result shown by `Display`.
+const DISPLAY_RESULT_LEN: usize = 80;
+
+impl Display for ToolCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}(", self.name)?;
+        for (i, param) in self.params.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}={}", param.name, param.value)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Message::Text(text) => write!(f, "{}: {}", text.role, text.message),
+            Message::ToolCall(call) => write!(f, "tool call: {}", call),
+            Message::ToolResult(res) => {
+                write!(f, "tool result: {}: ", res.name)?;
+                if res.result.chars().count() > DISPLAY_RESULT_LEN {
+                    let truncated: String = res.result.chars().take(DISPLAY_RESULT_LEN).collect();
+                    write!(f, "{}...", truncated)
+                } else {
+                    f.write_str(&res.result)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_display() {
+        let msg = Message::text(Role::User, "list instances".to_owned());
+        assert_eq!(msg.to_string(), "user: list instances");
+
+        let msg = Message::text(Role::Model, "done".to_owned());
+        assert_eq!(msg.to_string(), "apprentice: done");
+
+        let msg = Message::tool_call("id".to_owned(), "SHELL".to_owned(), vec![
+            ToolParam { name: "command".to_owned(), value: Value::String("ls -l".to_owned()) },
+            ToolParam { name: "timeout".to_owned(), value: Value::from(10) },
+        ]);
+        assert_eq!(msg.to_string(), "tool call: SHELL(command=\"ls -l\", timeout=10)");
+
+        let msg = Message::tool_result("id".to_owned(), "HELP".to_owned(), "usage".to_owned());
+        assert_eq!(msg.to_string(), "tool result: HELP: usage");
+
+        let msg = Message::tool_result("id".to_owned(), "HELP".to_owned(), "x".repeat(100));
+        assert_eq!(msg.to_string(), format!("tool result: HELP: {}...", "x".repeat(80)));
+    }
+
+    #[test]
+    fn test_message_serde_roundtrip() {
+        let messages = vec![
+            Message::text(Role::User, "list instances".to_owned()),
+            Message::text(Role::Model, "calling tool".to_owned()),
+            Message::tool_call("id1".to_owned(), "SHELL".to_owned(), vec![
+                ToolParam { name: "command".to_owned(), value: Value::String("ls".to_owned()) },
+            ]),
+            Message::tool_result("id1".to_owned(), "SHELL".to_owned(), "file.txt".to_owned()),
+        ];
+
+        let json = serde_json::to_value(&messages).expect("serialize");
+        assert_eq!(json[0], serde_json::json!({"type": "text", "role": "user", "message": "list instances"}));
+        assert_eq!(json[1]["role"], "apprentice");
+        assert_eq!(json[2]["type"], "tool_call");
+        assert_eq!(json[3]["type"], "tool_result");
+
+        let restored: Vec<Message> = serde_json::from_value(json).expect("deserialize");
+        assert_eq!(restored.len(), messages.len());
+        for (msg1, msg2) in messages.iter().zip(restored.iter()) {
+            assert_eq!(msg1.to_string(), msg2.to_string());
+        }
+        if let Message::ToolCall(call) = &restored[2] {
+            assert_eq!(call.call_id, "id1");
+            assert_eq!(call.params[0].value, Value::String("ls".to_owned()));
+        } else {
+            panic!("type mismatch");
+        }
+    }
+
+    #[test]
+    fn test_tool_call_display() {
+        let call = ToolCall { call_id: "id".to_owned(), name: "HELP".to_owned(), params: vec![], raw_arguments: None };
+        assert_eq!(call.to_string(), "HELP()");
+    }
+
+    #[test]
+    fn test_tool_call_to_result() {
+        let call = ToolCall { call_id: "id1".to_owned(), name: "SHELL".to_owned(), params: vec![], raw_arguments: None };
+        if let Message::ToolResult(res) = call.to_result("file.txt".to_owned()) {
+            assert_eq!(res.call_id, "id1");
+            assert_eq!(res.name, "SHELL");
+            assert_eq!(res.result, "file.txt");
+        } else {
+            panic!("type mismatch");
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,170 @@
+use std::time::{Duration, Instant};
+use crate::config::ModelProvider;
+use crate::error::Error;
+use crate::tools::{ToolChoice, ToolSpec};
+use super::{Capabilities, LLMChat, Message, StopReason, TokenLogprob, Usage};
+use serde_json::Value;
+
+/// Receives a record of each inference of a chat created with `get_llm_chat_with_metrics`,
+/// e.g. to export latency and token counts to Prometheus or statsd.
+pub trait Metrics {
+
+    /// Called after each inference with the time spent on it, including retries,
+    /// and the token usage if the call succeeded and the provider reported it.
+    /// Does nothing by default.
+    fn record_call(&self, _provider: ModelProvider, _latency: Duration, _usage: Option<Usage>, _success: bool) {}
+}
+
+/// Metrics that are discarded.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoMetrics;
+
+impl Metrics for NoMetrics {}
+
+/// Chat that records each inference of the inner chat.
+pub(super) struct MeteredChat {
+    chat: Box<dyn LLMChat>,
+    provider: ModelProvider,
+    metrics: Box<dyn Metrics>,
+}
+
+impl MeteredChat {
+    pub(super) fn new(chat: Box<dyn LLMChat>, provider: ModelProvider, metrics: Box<dyn Metrics>) -> Self {
+        MeteredChat {
+            chat,
+            provider,
+            metrics,
+        }
+    }
+
+    fn measure<T>(&mut self, call: impl FnOnce(&mut dyn LLMChat) -> Result<T, Error>) -> Result<T, Error> {
+        let start = Instant::now();
+        let result = call(self.chat.as_mut());
+        let usage = if result.is_ok() { self.chat.usage() } else { None };
+        self.metrics.record_call(self.provider, start.elapsed(), usage, result.is_ok());
+        result
+    }
+}
+
+impl LLMChat for MeteredChat {
+
+    fn get_inference_streaming(&mut self, messages: &[Message], tools: ToolChoice, on_delta: &mut dyn FnMut(&Message)) -> Result<Vec<Message>, Error> {
+        self.measure(|chat| chat.get_inference_streaming(messages, tools, on_delta))
+    }
+
+    fn get_candidates(&mut self, messages: &[Message], tools: ToolChoice) -> Result<Vec<Vec<Message>>, Error> {
+        self.measure(|chat| chat.get_candidates(messages, tools))
+    }
+
+    fn select_candidate(&mut self, index: usize) -> Result<(), Error> {
+        self.chat.select_candidate(index)
+    }
+
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.chat.stop_reason()
+    }
+
+    fn usage(&self) -> Option<Usage> {
+        self.chat.usage()
+    }
+
+    fn reasoning(&self) -> Option<String> {
+        self.chat.reasoning()
+    }
+
+    fn logprobs(&self) -> Option<Vec<TokenLogprob>> {
+        self.chat.logprobs()
+    }
+
+    fn estimate_tokens(&self, messages: &[Message]) -> usize {
+        self.chat.estimate_tokens(messages)
+    }
+
+    fn provider(&self) -> ModelProvider {
+        self.chat.provider()
+    }
+
+    fn model_name(&self) -> &str {
+        self.chat.model_name()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.chat.capabilities()
+    }
+
+    fn clear_history(&mut self) {
+        self.chat.clear_history();
+    }
+
+    fn set_system_prompt(&mut self, prompt: String) {
+        self.chat.set_system_prompt(prompt);
+    }
+
+    fn get_inference_with_system(&mut self, system: &str, messages: &[Message], tools: ToolChoice) -> Result<Vec<Message>, Error> {
+        self.measure(|chat| chat.get_inference_with_system(system, messages, tools))
+    }
+
+    fn add_tool(&mut self, tool: ToolSpec) {
+        self.chat.add_tool(tool);
+    }
+
+    fn set_tools(&mut self, tools: Vec<ToolSpec>) {
+        self.chat.set_tools(tools);
+    }
+
+    fn add_messages(&mut self, messages: &[Message]) {
+        self.chat.add_messages(messages);
+    }
+
+    fn export_history(&self) -> Vec<Value> {
+        self.chat.export_history()
+    }
+
+    fn import_history(&mut self, history: Vec<Value>) {
+        self.chat.import_history(history);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::config::Config;
+    use crate::llm::{get_llm_chat_with_metrics, Role};
+    use crate::request::stub::SequenceClient;
+    use serde_json::json;
+
+    type Call = (ModelProvider, Option<Usage>, bool);
+
+    #[derive(Clone, Default)]
+    struct RecordingMetrics {
+        calls: Rc<RefCell<Vec<Call>>>,
+    }
+
+    impl Metrics for RecordingMetrics {
+        fn record_call(&self, provider: ModelProvider, _latency: Duration, usage: Option<Usage>, success: bool) {
+            self.calls.borrow_mut().push((provider, usage, success));
+        }
+    }
+
+    #[test]
+    fn test_metrics() {
+        let config = Config::new(ModelProvider::OpenAI, "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        let client = Box::new(SequenceClient::new(vec![
+            json!({"choices": [{"message": {"role": "assistant", "content": "hi"}, "finish_reason": "stop"}],
+                "usage": {"prompt_tokens": 10, "completion_tokens": 2}}),
+            json!({"error": {"message": "overloaded"}}),
+        ]));
+        let metrics = RecordingMetrics::default();
+        let mut chat = get_llm_chat_with_metrics(config, client, vec![], Box::new(metrics.clone())).expect("create chat");
+
+        chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None).expect("receive response");
+        assert!(chat.get_inference(&[Message::text(Role::User, "again".to_owned())], ToolChoice::None).is_err());
+
+        assert_eq!(*metrics.calls.borrow(), vec![
+            (ModelProvider::OpenAI, Some(Usage { input_tokens: 10, output_tokens: 2 }), true),
+            (ModelProvider::OpenAI, None, false),
+        ]);
+    }
+}
@@ -3,13 +3,28 @@ mod llmchat;
 mod openai;
 mod anthropic;
 mod gcp;
+mod cohere;
+mod echo;
+mod metrics;
 mod util;
 mod messages;
 
 pub use llmchat::LLMChat;
+pub use llmchat::Capabilities;
 pub use messages::Message;
 pub use messages::Role;
+pub use messages::StopReason;
+pub use messages::Text;
+pub use messages::TokenLogprob;
 pub use messages::ToolCall;
 pub use messages::ToolParam;
 pub use messages::ToolResult;
-pub use llmchat::get_llm_chat;
\ No newline at end of file
+pub use messages::Usage;
+pub use llmchat::get_llm_chat;
+pub use llmchat::get_llm_chat_with_metrics;
+pub use metrics::Metrics;
+pub use metrics::NoMetrics;
+pub use llmchat::complete;
+pub use llmchat::tools_to_json;
+pub use llmchat::run_tool_loop;
+pub use llmchat::Approval;
\ No newline at end of file
@@ -1,66 +1,98 @@
 use crate::llm::util::{role_to_llm, tool_params_to_value};
 use crate::llm::LLMChat;
-use crate::config::Config;
+use crate::config::{Config, ModelProvider};
 use crate::error::Error;
+use crate::logging::log_debug;
 use crate::tools::{ToolChoice, ToolSpec};
 use crate::val_as_str;
 use serde_json::{json, Value};
 use crate::request::Client;
 use super::messages::Text;
-use super::{Message, ToolCall, ToolParam};
-use super::util::{self, llm_to_role};
+use super::{Message, StopReason, TokenLogprob, ToolCall, ToolParam, Usage};
+use super::util::{self, llm_to_error, llm_to_role, llm_to_stop_reason, llm_to_usage, supports_reasoning, Candidates};
+
+/// Attribution headers sent to OpenRouter unless set in `Config::extra_headers`.
+const OPENROUTER_HEADERS: [(&str, &str); 2] = [
+    ("HTTP-Referer", "https://github.com/ai-gentic/apprentice"),
+    ("X-Title", "apprentice"),
+];
 
 pub struct OpenAIChat {
-    system_prompt: String,
+    system_prompt: Option<String>,
     history: Vec<Value>,
     config: Config,
     client: Box<dyn Client>,
     tools: Vec<ToolSpec>,
+    candidates: Candidates,
 }
 
 impl OpenAIChat {
     pub(super) fn new(config: Config, client: Box<dyn Client>, tools: Vec<ToolSpec>) -> Self {
         OpenAIChat {
-            system_prompt: String::new(),
+            system_prompt: None,
             history: vec![],
             config,
             client,
             tools,
+            candidates: Candidates::default(),
         }
     }
 
-    fn prep_payload(&mut self, messages: &[Message], tools: ToolChoice) -> Value {
-
-        let mut payload = json!({
-            "model": self.config.name
-        });
-
-        for message in messages {    
+    fn push_messages(&mut self, messages: &[Message]) {
+        for message in messages {
             if let Message::Text(txt) = message {
                 self.history.push(json!({
                     "role": role_to_llm(self.config.provider, txt.role), 
                     "content": txt.message
                 }));
             } else if let Message::ToolResult(res) = message {
-                self.history.push(json!({
+                util::push_tool_result(&mut self.history, json!({
                     "role": "tool",
                     "content": res.result,
                     "tool_call_id": res.call_id
                 }));
             }
         }
+    }
+
+    fn prep_payload(&mut self, messages: &[Message], tools: ToolChoice) -> Value {
+
+        let mut payload = json!({
+            "model": self.config.name
+        });
+
+        self.push_messages(messages);
 
-        payload["messages"] = Value::Array(self.history.clone());
+        payload["messages"] = self.messages_to_json();
 
         util::set_f64_param(&mut payload, "frequency_penalty", &self.config.frequency_penalty);
         util::set_f64_param(&mut payload, "presence_penalty", &self.config.presence_penalty);
-        util::set_i64_param(&mut payload, "n", &self.config.n);
         util::set_f64_param(&mut payload, "top_p", &self.config.top_p);
         util::set_f64_param(&mut payload, "temperature", &self.config.temperature);
         util::set_i64_param(&mut payload, "max_completion_tokens", &self.config.max_tokens);
 
-        if let Some(val) = &self.config.stop_sequence {
-            payload["stop"] = Value::String(val.clone());
+        if let Some(val) = &self.config.stop_sequences {
+            payload["stop"] = json!(val);
+        }
+
+        // Groq rejects `n` other than 1, and log probabilities, DeepSeek ignores `n`.
+        if !matches!(self.config.provider, ModelProvider::Groq | ModelProvider::DeepSeek) {
+            util::set_i64_param(&mut payload, "n", &self.config.n);
+        }
+
+        if self.config.provider != ModelProvider::Groq {
+            if let Some(val) = self.config.logprobs {
+                payload["logprobs"] = Value::Bool(true);
+                if val > 0 {
+                    util::set_i64_param(&mut payload, "top_logprobs", &Some(val));
+                }
+            }
+        }
+
+        if let Some(val) = &self.config.reasoning_effort {
+            if supports_reasoning(self.config.provider, &self.config.name) {
+                payload["reasoning_effort"] = Value::String(val.clone());
+            }
         }
 
         self.prep_tool_use(&mut payload, tools);
@@ -73,11 +105,11 @@ impl OpenAIChat {
             ToolChoice::None => {},
             ToolChoice::Auto => {
                 payload["tool_choice"] = Value::String("auto".to_owned());
-                self.add_tools(payload);
+                self.add_tools(payload, |_| true);
             },
             ToolChoice::CallOne => {
                 payload["tool_choice"] = Value::String("required".to_owned());
-                self.add_tools(payload);
+                self.add_tools(payload, |_| true);
             },
             ToolChoice::Force(tool) => {
                 payload["tool_choice"] = json!({
@@ -86,48 +118,101 @@ impl OpenAIChat {
                         "name": tool
                     }
                 });
-                self.add_tools(payload);
+                self.add_tools(payload, |_| true);
+            },
+            ToolChoice::Except(excluded) => {
+                payload["tool_choice"] = Value::String("auto".to_owned());
+                self.add_tools(payload, |name| !excluded.iter().any(|excl| excl == name));
+            },
+            ToolChoice::Subset(allowed) => {
+                payload["tool_choice"] = Value::String("auto".to_owned());
+                self.add_tools(payload, |name| allowed.iter().any(|allow| allow == name));
             },
         };
-        payload["parallel_tool_calls"] = Value::Bool(false);
+        // DeepSeek rejects the parameter, and calls one tool at a time anyway.
+        if self.config.provider != ModelProvider::DeepSeek {
+            payload["parallel_tool_calls"] = Value::Bool(false);
+        }
     }
 
-    fn add_tools(&self, payload: &mut Value) {
-        let mut arr = Vec::with_capacity(self.tools.len());
-        for spec in self.tools.iter() {
-            arr.push(json!({
-                "type": "function",
-                "function": {
-                    "description": spec.description,
-                    "name": spec.name,
-                    "parameters": tool_params_to_value(&spec.params, self.config.provider),
-                    "strict": true
-                }
-            }));
-        }
-        payload["tools"] = Value::Array(arr);
+    fn add_tools(&self, payload: &mut Value, include: impl Fn(&str) -> bool) {
+        payload["tools"] = Self::tools_to_json(self.tools.iter().filter(|spec| include(&spec.name)), self.config.provider);
+    }
+
+    pub(super) fn tools_to_json<'a>(tools: impl Iterator<Item = &'a ToolSpec>, provider: ModelProvider) -> Value {
+        let arr = tools.map(|spec| json!({
+            "type": "function",
+            "function": {
+                "description": spec.description,
+                "name": spec.name,
+                "parameters": tool_params_to_value(&spec.params, provider),
+                "strict": true
+            }
+        })).collect();
+        Value::Array(arr)
+    }
+
+    // Log probabilities are informational, so the entries of unexpected format are skipped.
+    fn parse_logprobs(logprobs: &Value) -> Option<Vec<TokenLogprob>> {
+        let content = logprobs["content"].as_array()?;
+        Some(content.iter().filter_map(|entry| {
+            Some(TokenLogprob {
+                token: entry["token"].as_str()?.to_owned(),
+                logprob: entry["logprob"].as_f64()?,
+                top_logprobs: entry["top_logprobs"].as_array()
+                    .map(|top| top.iter()
+                        .filter_map(|alt| Some((alt["token"].as_str()?.to_owned(), alt["logprob"].as_f64()?)))
+                        .collect())
+                    .unwrap_or_default(),
+            })
+        }).collect())
+    }
+
+    // The stored system prompt is kept out of the history, so the inline system messages stay in it.
+    fn messages_to_json(&self) -> Value {
+        let system = self.system_prompt.iter().map(|prompt| json!({"role": "system", "content": prompt}));
+        Value::Array(system.chain(self.history.iter().cloned()).collect())
     }
 
     fn check_for_error(&self, response: &Value) -> Result<(), Error> {
         if let Some(error) = response.get("error") {
-            let errmes = val_as_str!(error["message"], "error message").to_owned();
-            return Err(Error::LLMErrorMessage(errmes));
+            return Err(llm_to_error(self.config.provider, error)?);
         }
         Ok(())
     }
 
-    fn process_response(&mut self, response: Value) -> Result<Vec<Message>, Error> {
+    fn process_response(&mut self, response: Value) -> Result<Vec<Vec<Message>>, Error> {
+
+        self.check_for_error(&response)
+            .inspect_err(|err| log_debug!("{} returned error: {}", self.config.provider, err))?;
 
-        self.check_for_error(&response)?;
+        let mut candidates = Vec::new();
+        let mut entries = Vec::new();
+        let mut stop_reasons = Vec::new();
+        let mut reasoning = Vec::new();
+        let mut logprobs = Vec::new();
 
-        let mut result = Vec::new();
+        let choices = response["choices"].as_array()
+            .ok_or(Error::LLMResponseError("unexpected answer format, can't enumerate response messages."))?;
+
+        if choices.is_empty() {
+            return Err(Error::EmptyResponse);
+        }
 
-        for choice in response["choices"].as_array()
-            .ok_or(Error::LLMResponseError("unexpected answer format, can't enumerate response messages."))?
-        {
+        for choice in choices {
             let msg = &choice["message"];
 
-            self.history.push(msg.clone());
+            // Reasoning returned by OpenAI-compatible APIs must not be sent back.
+            let mut entry = msg.clone();
+            if let Some(obj) = entry.as_object_mut() {
+                obj.remove("reasoning_content");
+            }
+            entries.push(vec![entry]);
+            reasoning.push(msg["reasoning_content"].as_str().map(|r| r.to_owned()));
+            logprobs.push(Self::parse_logprobs(&choice["logprobs"]));
+            stop_reasons.push(choice["finish_reason"].as_str().map(|r| llm_to_stop_reason(self.config.provider, r)));
+
+            let mut result = Vec::new();
 
             let role = llm_to_role(val_as_str!(msg["role"], "message role"))?;
 
@@ -151,55 +236,121 @@ impl OpenAIChat {
 
                     let mut params = Vec::new();
 
-                    let args_obj = serde_json::from_str::<Value>(&arguments)?;
-                    
+                    let args_obj = serde_json::from_str::<Value>(&arguments)
+                        .map_err(|err| Error::ToolArgumentsError { name: name.clone(), arguments: arguments.clone(), message: err.to_string() })?;
+
                     for (k, v) in args_obj
                         .as_object()
-                        .ok_or(Error::LLMResponseError("can't enumerate arguments."))?
+                        .ok_or_else(|| Error::ToolArgumentsError { name: name.clone(), arguments: arguments.clone(), message: "not an object".to_owned() })?
                     {
                         let name = k.clone();
                         let value = v.clone();
                         params.push(ToolParam {name, value});
                     }
 
-                    result.push(Message::ToolCall(ToolCall{call_id, name, params}));
+                    result.push(Message::ToolCall(ToolCall{call_id, name, params, raw_arguments: Some(arguments)}));
                 }
             }
+
+            candidates.push(result);
         }
 
-        Ok(result)
+        log_debug!("{} returned {} candidate(s), stop reasons {:?}", self.config.provider, candidates.len(), stop_reasons);
+
+        self.candidates.keep(&mut self.history, entries, stop_reasons, reasoning);
+        self.candidates.set_logprobs(logprobs);
+        self.candidates.set_usage(llm_to_usage(self.config.provider, &response));
+
+        Ok(candidates)
     }
 }
 
 impl LLMChat for OpenAIChat {
 
-    fn get_inference(&mut self, messages: &[Message], tools: ToolChoice) -> Result<Vec<Message>, Error> {
+    fn get_candidates(&mut self, messages: &[Message], tools: ToolChoice) -> Result<Vec<Vec<Message>>, Error> {
+        if util::has_prefill(messages) {
+            return Err(util::prefill_error(self.config.provider));
+        }
+
         let payload = self.prep_payload(messages, tools);
 
         let token = format!("Bearer {}", self.config.api_key);
-        let headers = &[("Authorization", token.as_ref())];
+        let mut headers = vec![("Authorization", token.as_ref())];
 
-        let response = self.client.make_json_request(&self.config.api_url, payload, headers, &[])?;
+        if self.config.provider == ModelProvider::OpenRouter {
+            headers.extend(OPENROUTER_HEADERS.iter()
+                .filter(|(name, _)| !self.config.extra_headers.iter().any(|(k, _)| k.eq_ignore_ascii_case(name))));
+        }
+
+        let headers = util::with_extra_headers(&headers, &self.config);
+        let response = self.client.make_json_request(&self.config.api_url, payload, &headers, &[])?;
 
         self.process_response(response)
     }
 
+    fn select_candidate(&mut self, index: usize) -> Result<(), Error> {
+        self.candidates.select(&mut self.history, index)
+    }
+
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.candidates.stop_reason()
+    }
+
+    fn reasoning(&self) -> Option<String> {
+        self.candidates.reasoning()
+    }
+
+    fn usage(&self) -> Option<Usage> {
+        self.candidates.usage()
+    }
+
+    fn logprobs(&self) -> Option<Vec<TokenLogprob>> {
+        self.candidates.logprobs()
+    }
+
+    fn provider(&self) -> ModelProvider {
+        self.config.provider
+    }
+
+    fn model_name(&self) -> &str {
+        &self.config.name
+    }
+
     fn clear_history(&mut self) {
         self.history.clear();
+        self.candidates.clear();
     }
 
     fn set_system_prompt(&mut self, prompt: String) {
-        self.system_prompt = prompt;
-        let val = json!({
-            "role": "system", 
-            "content": self.system_prompt.clone(),
-        });
+        self.system_prompt = Some(prompt);
+    }
 
-        if self.history.is_empty() {
-            self.history.push(val);
-        } else {
-            self.history[0] = val;
-        }
+    fn get_inference_with_system(&mut self, system: &str, messages: &[Message], tools: ToolChoice) -> Result<Vec<Message>, Error> {
+        let stored = self.system_prompt.replace(system.to_owned());
+        let result = self.get_inference(messages, tools);
+        self.system_prompt = stored;
+        result
+    }
+
+    fn add_tool(&mut self, tool: ToolSpec) {
+        self.tools.push(tool);
+    }
+
+    fn set_tools(&mut self, tools: Vec<ToolSpec>) {
+        self.tools = tools;
+    }
+
+    fn add_messages(&mut self, messages: &[Message]) {
+        self.push_messages(messages);
+    }
+
+    fn export_history(&self) -> Vec<Value> {
+        self.history.clone()
+    }
+
+    fn import_history(&mut self, history: Vec<Value>) {
+        self.history = history;
+        self.candidates.clear();
     }
 }
 
@@ -207,8 +358,10 @@ impl LLMChat for OpenAIChat {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::request::stub::StubClient;
-    use crate::llm::Role;
+    use crate::llm::tools_to_json;
+    use crate::request::stub::{SequenceClient, StubClient};
+    use crate::config::AuthMode;
+    use crate::llm::{get_llm_chat, Role};
     use crate::tools::{ParamType, ToolParam};
 
     #[test]
@@ -217,6 +370,7 @@ mod tests {
             provider: "openai".try_into().expect("determine model provider"),
             name: "<model-name>".to_owned(),
             api_key: "<api-key>".to_owned(),
+            auth_mode: AuthMode::ApiKey,
             api_url: "<api-uri>".to_owned(),
             api_version: Some("<api-ver>".to_owned()),
             max_tokens: Some(4096),
@@ -226,7 +380,16 @@ mod tests {
             top_k: Some(5),
             frequency_penalty: Some(-0.11),
             presence_penalty: Some(0.22),
-            stop_sequence: Some("<stop>".to_owned()),
+            stop_sequences: Some(vec!["<stop>".to_owned()]),
+            reasoning_effort: None,
+            thinking_budget: None,
+            prompt_caching: false,
+            safety_settings: vec![],
+            logprobs: None,
+            aws_credentials: None,
+            extra_headers: vec![],
+            omit_params: vec![],
+            non_streaming_max_tokens: None,
         };
 
         let sys_msg = "test sys message";
@@ -270,7 +433,7 @@ mod tests {
             "max_completion_tokens": config.max_tokens.unwrap(),
             "n": config.n.unwrap(),
             "presence_penalty": config.presence_penalty.unwrap(),
-            "stop": config.stop_sequence.as_ref().unwrap(),
+            "stop": config.stop_sequences.as_ref().unwrap(),
             "temperature": config.temperature.unwrap(),
             "top_p": config.top_p.unwrap(),
             "parallel_tool_calls": false,
@@ -312,6 +475,7 @@ mod tests {
         chat.set_system_prompt(sys_msg.to_owned());
         
         let response = chat.get_inference(&messages, ToolChoice::Auto).expect("receive response");
+        assert_eq!(chat.usage(), Some(Usage { input_tokens: 9, output_tokens: 12 }));
 
         assert_eq!(expected_messages.len(), response.len());
         if let (Message::Text(txt1), Message::Text(txt2)) = (&expected_messages[0], &response[0]) {
@@ -320,6 +484,7 @@ mod tests {
         } else {
             panic!("type mismatch");
         }
+        assert_eq!(chat.stop_reason(), Some(StopReason::EndTurn));
     }
 
     #[test]
@@ -328,6 +493,7 @@ mod tests {
             provider: "openai".try_into().expect("determine model provider"),
             name: "<model-name>".to_owned(),
             api_key: "<api-key>".to_owned(),
+            auth_mode: AuthMode::ApiKey,
             api_url: "<api-uri>".to_owned(),
             api_version: Some("<api-ver>".to_owned()),
             max_tokens: Some(4096),
@@ -337,7 +503,16 @@ mod tests {
             top_k: Some(5),
             frequency_penalty: Some(-0.11),
             presence_penalty: Some(0.22),
-            stop_sequence: Some("<stop>".to_owned()),
+            stop_sequences: Some(vec!["<stop>".to_owned()]),
+            reasoning_effort: None,
+            thinking_budget: None,
+            prompt_caching: false,
+            safety_settings: vec![],
+            logprobs: None,
+            aws_credentials: None,
+            extra_headers: vec![],
+            omit_params: vec![],
+            non_streaming_max_tokens: None,
         };
 
         let sys_msg = "test sys message";
@@ -379,7 +554,7 @@ mod tests {
             "max_completion_tokens": config.max_tokens.unwrap(),
             "n": config.n.unwrap(),
             "presence_penalty": config.presence_penalty.unwrap(),
-            "stop": config.stop_sequence.as_ref().unwrap(),
+            "stop": config.stop_sequences.as_ref().unwrap(),
             "temperature": config.temperature.unwrap(),
             "top_p": config.top_p.unwrap(),
             "parallel_tool_calls": false,
@@ -404,8 +579,10 @@ mod tests {
         
         let response = chat.get_inference(&messages, ToolChoice::Auto);
 
-        if let Err(Error::LLMErrorMessage(msg)) = response {
-            assert_eq!(msg, model_msg2);
+        if let Err(Error::ProviderError { code, kind, message }) = response {
+            assert_eq!(code, Some("insufficient_quota".to_owned()));
+            assert_eq!(kind, Some("insufficient_quota".to_owned()));
+            assert_eq!(message, model_msg2);
         } else {
             panic!("type mismatch");
         }
@@ -417,6 +594,7 @@ mod tests {
             provider: "openai".try_into().expect("determine model provider"),
             name: "<model-name>".to_owned(),
             api_key: "<api-key>".to_owned(),
+            auth_mode: AuthMode::ApiKey,
             api_url: "<api-uri>".to_owned(),
             api_version: Some("<api-ver>".to_owned()),
             max_tokens: Some(4096),
@@ -426,7 +604,16 @@ mod tests {
             top_k: Some(5),
             frequency_penalty: Some(-0.11),
             presence_penalty: Some(0.22),
-            stop_sequence: Some("<stop>".to_owned()),
+            stop_sequences: Some(vec!["<stop>".to_owned()]),
+            reasoning_effort: None,
+            thinking_budget: None,
+            prompt_caching: false,
+            safety_settings: vec![],
+            logprobs: None,
+            aws_credentials: None,
+            extra_headers: vec![],
+            omit_params: vec![],
+            non_streaming_max_tokens: None,
         };
 
         let tools = vec![
@@ -434,36 +621,16 @@ mod tests {
                 name: "tool1".to_owned(),
                 description: "tool desc 1".to_owned(),
                 params: vec![
-                    ToolParam {
-                        name: "tool1_param1".to_string(),
-                        description: "tool1_param1 desc".to_string(), 
-                        data_type: ParamType::Integer, 
-                        required: true
-                    },
-                    ToolParam {
-                        name: "tool1_param2".to_string(),
-                        description: "tool1_param2 desc".to_string(), 
-                        data_type: ParamType::String, 
-                        required: false
-                    },
+                    ToolParam::new("tool1_param1".to_string(), "tool1_param1 desc".to_string(), ParamType::Integer, true),
+                    ToolParam::new("tool1_param2".to_string(), "tool1_param2 desc".to_string(), ParamType::String, false),
                 ]
             },
             ToolSpec {
                 name: "tool2".to_owned(),
                 description: "tool desc 2".to_owned(),
                 params: vec![
-                    ToolParam {
-                        name: "tool2_param1".to_string(),
-                        description: "tool2_param1 desc".to_string(), 
-                        data_type: ParamType::Boolean, 
-                        required: false
-                    },
-                    ToolParam {
-                        name: "tool2_param2".to_string(),
-                        description: "tool2_param2 desc".to_string(), 
-                        data_type: ParamType::Number, 
-                        required: true
-                    },
+                    ToolParam::new("tool2_param1".to_string(), "tool2_param1 desc".to_string(), ParamType::Boolean, false),
+                    ToolParam::new("tool2_param2".to_string(), "tool2_param2 desc".to_string(), ParamType::Number, true),
                 ]
             },
         ];
@@ -484,7 +651,7 @@ mod tests {
         messages.push(Message::text(Role::Model, model_msg1.to_owned()));
         messages.push(Message::text(Role::User, user_msg2.to_owned()));
         let mut expected_messages = Vec::new();
-        expected_messages.push(Message::tool_use(call_id.to_owned(), call_tool.to_owned(), call_params));
+        expected_messages.push(Message::tool_call(call_id.to_owned(), call_tool.to_owned(), call_params));
 
         let expected_headers = vec![];
         let expected_params = vec![
@@ -514,7 +681,7 @@ mod tests {
             "max_completion_tokens": config.max_tokens.unwrap(),
             "n": config.n.unwrap(),
             "presence_penalty": config.presence_penalty.unwrap(),
-            "stop": config.stop_sequence.as_ref().unwrap(),
+            "stop": config.stop_sequences.as_ref().unwrap(),
             "temperature": config.temperature.unwrap(),
             "top_p": config.top_p.unwrap(),
             "parallel_tool_calls": false,
@@ -603,6 +770,8 @@ mod tests {
           }
         });
 
+        assert_eq!(tools_to_json(&tools, config.provider), expected_payload["tools"]);
+
         let client = Box::new(StubClient::new(expected_headers, expected_params, expected_payload, response_body));
 
         let mut chat = OpenAIChat::new(config, client, tools);
@@ -624,4 +793,511 @@ mod tests {
         }
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_text_and_tool_call() {
+        let config = Config::new("openai".try_into().expect("determine model provider"), 
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+
+        let message = json!({"role": "assistant", "content": "Let me list the instances.", "tool_calls": [
+            {"id": "call_1", "type": "function", "function": {"name": "SHELL", "arguments": "{\"command\": \"gcloud compute instances list\"}"}}
+        ]});
+        let client = Box::new(StubClient::with_response(json!({"choices": [{"message": message, "finish_reason": "tool_calls"}]})));
+        let mut chat = OpenAIChat::new(config, client, vec![]);
+
+        let response = chat.get_inference(&[Message::text(Role::User, "list vms".to_owned())], ToolChoice::Auto)
+            .expect("receive response");
+
+        // Narration precedes the tool call, so it is shown before the tool runs.
+        assert_eq!(response.len(), 2);
+        assert!(matches!(&response[0], Message::Text(txt) if txt.message == "Let me list the instances."));
+        assert!(matches!(&response[1], Message::ToolCall(call) if call.call_id == "call_1" && call.name == "SHELL"));
+
+        // Text and tool calls stay in one history entry, as the API expects them before the tool result.
+        assert_eq!(chat.history.last(), Some(&message));
+    }
+
+    #[test]
+    fn test_logprobs() {
+        let mut config = Config::new("openai".try_into().expect("determine model provider"), 
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        config.logprobs = Some(2);
+
+        let expected_payload = json!({
+            "model": config.name,
+            "messages": [{"role": "user", "content": "yes or no?"}],
+            "logprobs": true,
+            "top_logprobs": 2,
+            "parallel_tool_calls": false,
+        });
+
+        let response_body = json!({"choices": [{
+            "message": {"role": "assistant", "content": "yes"},
+            "logprobs": {"content": [
+                {"token": "yes", "logprob": -0.01, "bytes": [121, 101, 115], "top_logprobs": [
+                    {"token": "yes", "logprob": -0.01, "bytes": [121, 101, 115]},
+                    {"token": "no", "logprob": -4.6, "bytes": [110, 111]}
+                ]}
+            ]},
+            "finish_reason": "stop"
+        }]});
+
+        let client = Box::new(StubClient::new(vec![], vec![], expected_payload, response_body));
+        let mut chat = OpenAIChat::new(config, client, vec![]);
+
+        chat.get_inference(&[Message::text(Role::User, "yes or no?".to_owned())], ToolChoice::None).expect("receive response");
+        assert_eq!(chat.logprobs(), Some(vec![TokenLogprob {
+            token: "yes".to_owned(),
+            logprob: -0.01,
+            top_logprobs: vec![("yes".to_owned(), -0.01), ("no".to_owned(), -4.6)],
+        }]));
+
+        // Responses without log probabilities are accepted.
+        let config = Config::new("openai".try_into().expect("determine model provider"), 
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        let client = Box::new(StubClient::with_response(json!({"choices": [{
+            "message": {"role": "assistant", "content": "yes"}, "finish_reason": "stop"
+        }]})));
+        let mut chat = OpenAIChat::new(config, client, vec![]);
+
+        chat.get_inference(&[Message::text(Role::User, "yes or no?".to_owned())], ToolChoice::None).expect("receive response");
+        assert_eq!(chat.logprobs(), None);
+    }
+
+    #[test]
+    fn test_ranked_candidates() {
+        let choice = |index: i64, content: &str, logprobs: &[f64]| json!({
+            "index": index,
+            "message": {"role": "assistant", "content": content},
+            "logprobs": {"content": logprobs.iter().map(|logprob| json!({"token": "t", "logprob": logprob, "top_logprobs": []})).collect::<Vec<_>>()},
+            "finish_reason": "stop"
+        });
+
+        let mut config = Config::new(ModelProvider::OpenAI, "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        config.n = Some(3);
+        config.logprobs = Some(0);
+        let client = Box::new(StubClient::with_response(json!({"choices": [
+            choice(0, "maybe", &[-1.0, -3.0]),
+            choice(1, "yes", &[-0.5, -0.5]),
+            {"index": 2, "message": {"role": "assistant", "content": "no"}, "finish_reason": "stop"},
+        ]})));
+        let mut chat = OpenAIChat::new(config, client, vec![]);
+
+        let ranked = chat.get_ranked_candidates(&[Message::text(Role::User, "yes or no?".to_owned())], ToolChoice::None).expect("receive response");
+        let ranked: Vec<(String, f64)> = ranked.into_iter()
+            .map(|(message, score)| match message {
+                Message::Text(txt) => (txt.message, score),
+                _ => panic!("type mismatch"),
+            })
+            .collect();
+        assert_eq!(ranked, vec![("yes".to_owned(), -0.5), ("maybe".to_owned(), -2.0), ("no".to_owned(), f64::NEG_INFINITY)]);
+        assert_eq!(chat.export_history().last().expect("history entry")["content"], "yes");
+    }
+
+    #[test]
+    fn test_except_tools() {
+        let config = Config::new("openai".try_into().expect("determine model provider"), 
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        let tools = ["tool1", "tool2", "tool3"].iter()
+            .map(|name| ToolSpec { name: name.to_string(), description: String::new(), params: vec![] })
+            .collect();
+        let mut chat = OpenAIChat::new(config, Box::new(StubClient::with_response(Value::Null)), tools);
+
+        let payload = chat.prep_payload(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::Except(vec!["tool2".to_owned()]));
+        let names: Vec<&str> = payload["tools"].as_array().expect("tools array").iter()
+            .map(|spec| spec["function"]["name"].as_str().expect("tool name"))
+            .collect();
+        assert_eq!(names, vec!["tool1", "tool3"]);
+    }
+
+    #[test]
+    fn test_add_tool() {
+        let config = Config::new("openai".try_into().expect("determine model provider"), 
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        let mut chat = OpenAIChat::new(config, Box::new(StubClient::with_response(Value::Null)), vec![]);
+
+        let payload = chat.prep_payload(&[], ToolChoice::Auto);
+        assert_eq!(payload["tools"], json!([]));
+
+        chat.add_tool(ToolSpec { name: "admin".to_owned(), description: String::new(), params: vec![] });
+        let payload = chat.prep_payload(&[], ToolChoice::Auto);
+        assert_eq!(payload["tools"][0]["function"]["name"], "admin");
+
+        chat.set_tools(vec![]);
+        let payload = chat.prep_payload(&[], ToolChoice::Auto);
+        assert_eq!(payload["tools"], json!([]));
+    }
+
+    #[test]
+    fn test_subset_tools() {
+        let config = Config::new("openai".try_into().expect("determine model provider"), 
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        let tools = ["tool1", "tool2", "tool3"].iter()
+            .map(|name| ToolSpec { name: name.to_string(), description: String::new(), params: vec![] })
+            .collect();
+        let mut chat = OpenAIChat::new(config, Box::new(StubClient::with_response(Value::Null)), tools);
+
+        let payload = chat.prep_payload(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::Subset(vec!["tool2".to_owned()]));
+        assert_eq!(payload["tools"].as_array().expect("tools array").len(), 1);
+        assert_eq!(payload["tools"][0]["function"]["name"], "tool2");
+    }
+
+    #[test]
+    fn test_deepseek() {
+        let mut config = Config::new("deepseek".try_into().expect("determine model provider"), 
+            "deepseek-chat".to_owned(), "<api-key>".to_owned(), String::new());
+        assert_eq!(config.api_url, "https://api.deepseek.com/v1/chat/completions");
+        config.n = Some(2);
+
+        let expected_headers = vec![("Authorization".to_owned(), "Bearer <api-key>".to_owned())];
+        let expected_payload = json!({
+            "model": "deepseek-chat",
+            "messages": [{"role": "user", "content": "hi"}],
+            "tool_choice": "auto",
+            "tools": [],
+        });
+        let response_body = json!({"choices": [{"message": {"role": "assistant", "content": "hello"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 4, "completion_tokens": 2, "total_tokens": 6}});
+
+        let client = Box::new(StubClient::new(expected_headers, vec![], expected_payload, response_body));
+        let mut chat = get_llm_chat(config, client, vec![]).expect("create chat");
+
+        let response = chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::Auto).expect("receive response");
+        assert!(matches!(&response[0], Message::Text(txt) if txt.role == Role::Model && txt.message == "hello"));
+        assert_eq!(chat.stop_reason(), Some(StopReason::EndTurn));
+        assert_eq!(chat.usage(), Some(Usage { input_tokens: 4, output_tokens: 2 }));
+        assert_eq!(chat.reasoning(), None);
+    }
+
+    #[test]
+    fn test_deepseek_reasoning() {
+        let config = Config::new("deepseek".try_into().expect("determine model provider"), 
+            "deepseek-reasoner".to_owned(), "<api-key>".to_owned(), String::new());
+
+        let client = Box::new(StubClient::with_response(json!({"choices": [{"message": {
+            "role": "assistant", "content": "4", "reasoning_content": "2 + 2 is 4."
+        }, "finish_reason": "stop"}]})));
+        let mut chat = OpenAIChat::new(config, client, vec![]);
+
+        let response = chat.get_inference(&[Message::text(Role::User, "2 + 2?".to_owned())], ToolChoice::None).expect("receive response");
+        assert!(matches!(&response[0], Message::Text(txt) if txt.message == "4"));
+        assert_eq!(chat.reasoning(), Some("2 + 2 is 4.".to_owned()));
+        // Reasoning must not be sent back in the next request.
+        assert_eq!(chat.history.last(), Some(&json!({"role": "assistant", "content": "4"})));
+    }
+
+    #[test]
+    fn test_groq() {
+        let mut config = Config::new("groq".try_into().expect("determine model provider"), 
+            "llama-3.3-70b-versatile".to_owned(), "<api-key>".to_owned(), String::new());
+        config.n = Some(2);
+        config.logprobs = Some(0);
+        config.temperature = Some(0.5);
+        assert_eq!(config.api_url, "https://api.groq.com/openai/v1/chat/completions");
+
+        let expected_headers = vec![("Authorization".to_owned(), "Bearer <api-key>".to_owned())];
+        let expected_payload = json!({
+            "model": "llama-3.3-70b-versatile",
+            "messages": [{"role": "user", "content": "hi"}],
+            "temperature": 0.5,
+            "parallel_tool_calls": false,
+        });
+        let response_body = json!({"choices": [{"message": {"role": "assistant", "content": "hello"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 4, "completion_tokens": 2, "total_tokens": 6}});
+
+        let client = Box::new(StubClient::new(expected_headers, vec![], expected_payload, response_body));
+        let mut chat = get_llm_chat(config, client, vec![]).expect("create chat");
+
+        let response = chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None).expect("receive response");
+        assert!(matches!(&response[0], Message::Text(txt) if txt.role == Role::Model && txt.message == "hello"));
+        assert_eq!(chat.stop_reason(), Some(StopReason::EndTurn));
+        assert_eq!(chat.usage(), Some(Usage { input_tokens: 4, output_tokens: 2 }));
+    }
+
+    #[test]
+    fn test_openrouter() {
+        use crate::request::stub::RecordingClient;
+
+        let mut config = Config::new("openrouter".try_into().expect("determine model provider"), 
+            "anthropic/claude-3.5-sonnet".to_owned(), "<api-key>".to_owned(), String::new());
+        config.extra_headers = vec![("X-Title".to_owned(), "my-app".to_owned())];
+
+        let client = RecordingClient::new(json!({"choices": [{"message": {"role": "assistant", "content": "hello"}, "finish_reason": "stop"}]}));
+        let mut chat = get_llm_chat(config, Box::new(client.clone()), vec![]).expect("create chat");
+
+        let response = chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None).expect("receive response");
+        assert!(matches!(&response[0], Message::Text(txt) if txt.message == "hello"));
+
+        let request = client.requests().remove(0);
+        assert_eq!(request.url, "https://openrouter.ai/api/v1/chat/completions");
+        assert_eq!(request.payload["model"], "anthropic/claude-3.5-sonnet");
+        assert_eq!(request.headers, vec![
+            ("Authorization".to_owned(), "Bearer <api-key>".to_owned()),
+            ("HTTP-Referer".to_owned(), "https://github.com/ai-gentic/apprentice".to_owned()),
+            ("X-Title".to_owned(), "my-app".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn test_prefill_unsupported() {
+        let config = Config::new("openai".try_into().expect("determine model provider"), 
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+
+        let client = Box::new(StubClient::with_response(Value::Null));
+        let mut chat = OpenAIChat::new(config, client, vec![]);
+
+        let messages = [Message::text(Role::User, "list vms as json".to_owned()), Message::text(Role::Model, "{".to_owned())];
+        assert!(matches!(chat.get_inference(&messages, ToolChoice::None), Err(Error::Error(msg)) if msg.contains("prefill")));
+        assert!(chat.history.is_empty());
+    }
+
+    #[test]
+    fn test_malformed_tool_arguments() {
+        let config = Config::new("openai".try_into().expect("determine model provider"), 
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+
+        let client = Box::new(StubClient::with_response(json!({"choices": [{"message": {"role": "assistant", "tool_calls": [
+            {"id": "call_1", "type": "function", "function": {"name": "SHELL", "arguments": "{\"command\": \"ls"}}
+        ]}, "finish_reason": "tool_calls"}]})));
+        let mut chat = OpenAIChat::new(config, client, vec![]);
+
+        let response = chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::Auto);
+        if let Err(Error::ToolArgumentsError { name, arguments, message }) = response {
+            assert_eq!(name, "SHELL");
+            assert_eq!(arguments, "{\"command\": \"ls");
+            assert!(message.contains("EOF"), "unexpected message {message}");
+        } else {
+            panic!("type mismatch");
+        }
+    }
+
+    #[test]
+    fn test_empty_response() {
+        let config = Config::new("openai".try_into().expect("determine model provider"), 
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+
+        let client = Box::new(StubClient::with_response(json!({"choices": []})));
+        let mut chat = OpenAIChat::new(config, client, vec![]);
+
+        let response = chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None);
+        assert!(matches!(response, Err(Error::EmptyResponse)));
+    }
+
+    #[test]
+    fn test_export_import_history() {
+        let config = Config::new("openai".try_into().expect("determine model provider"), 
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+
+        let client = Box::new(StubClient::with_response(Value::Null));
+        let mut chat = OpenAIChat::new(config.clone(), client, vec![]);
+        chat.set_system_prompt("old sys message".to_owned());
+        chat.history.push(json!({"role": "user", "content": "hi"}));
+
+        let history = chat.export_history();
+        assert_eq!(history, vec![json!({"role": "user", "content": "hi"})]);
+
+        let client = Box::new(StubClient::with_response(Value::Null));
+        let mut chat = OpenAIChat::new(config, client, vec![]);
+        chat.set_system_prompt("new sys message".to_owned());
+        chat.import_history(history);
+
+        assert_eq!(chat.messages_to_json(), json!([
+            {"role": "system", "content": "new sys message"},
+            {"role": "user", "content": "hi"},
+        ]));
+    }
+
+    #[test]
+    fn test_candidates() {
+        let mut config = Config::new("openai".try_into().expect("determine model provider"), 
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        config.n = Some(2);
+
+        let expected_payload = json!({
+            "model": config.name,
+            "messages": [{"role": "user", "content": "hi"}],
+            "n": 2,
+            "parallel_tool_calls": false,
+        });
+
+        let response_body = json!({
+            "choices": [
+                {"index": 0, "message": {"role": "assistant", "content": "hello"}, "finish_reason": "stop"},
+                {"index": 1, "message": {"role": "assistant", "content": "hey"}, "finish_reason": "stop"},
+            ]
+        });
+
+        let client = Box::new(StubClient::new(vec![], vec![], expected_payload, response_body));
+        let mut chat = OpenAIChat::new(config, client, vec![]);
+
+        let candidates = chat.get_candidates(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None)
+            .expect("receive response");
+
+        assert_eq!(candidates.len(), 2);
+        if let (Message::Text(txt1), Message::Text(txt2)) = (&candidates[0][0], &candidates[1][0]) {
+            assert_eq!(txt1.message, "hello");
+            assert_eq!(txt2.message, "hey");
+        } else {
+            panic!("type mismatch");
+        }
+
+        assert_eq!(chat.history.last(), Some(&json!({"role": "assistant", "content": "hello"})));
+
+        chat.select_candidate(1).expect("select candidate");
+        assert_eq!(chat.history, vec![
+            json!({"role": "user", "content": "hi"}),
+            json!({"role": "assistant", "content": "hey"}),
+        ]);
+
+        assert!(chat.select_candidate(2).is_err());
+    }
+
+    #[test]
+    fn test_continue_inference() {
+        let config = Config::new("openai".try_into().expect("determine model provider"), 
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+
+        let expected_payload = json!({
+            "model": config.name,
+            "messages": [
+                {"role": "user", "content": "hi"},
+                {"role": "assistant", "content": "hel"},
+                {"role": "user", "content": "Continue your previous response exactly from where it was cut off."},
+            ],
+            "parallel_tool_calls": false,
+        });
+
+        let response_body = json!({
+            "choices": [
+                {"index": 0, "message": {"role": "assistant", "content": "lo"}, "finish_reason": "length"},
+            ]
+        });
+
+        let client = Box::new(StubClient::new(vec![], vec![], expected_payload, response_body));
+        let mut chat = OpenAIChat::new(config, client, vec![]);
+        chat.import_history(vec![
+            json!({"role": "user", "content": "hi"}),
+            json!({"role": "assistant", "content": "hel"}),
+        ]);
+
+        assert_eq!(chat.stop_reason(), None);
+
+        let response = chat.continue_inference().expect("receive response");
+
+        assert_eq!(response.len(), 1);
+        assert_eq!(chat.stop_reason(), Some(StopReason::MaxTokens));
+    }
+
+    #[test]
+    fn test_system_message() {
+        let config = Config::new("openai".try_into().expect("determine model provider"), 
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+
+        let expected_payload = json!({
+            "model": config.name,
+            "messages": [
+                {"role": "system", "content": "sys"},
+                {"role": "system", "content": "be brief"},
+                {"role": "user", "content": "hi"},
+            ],
+            "parallel_tool_calls": false,
+        });
+
+        let response_body = json!({
+            "choices": [
+                {"index": 0, "message": {"role": "assistant", "content": "hello"}, "finish_reason": "stop"},
+            ]
+        });
+
+        let client = Box::new(StubClient::new(vec![], vec![], expected_payload, response_body));
+        let mut chat = OpenAIChat::new(config, client, vec![]);
+        chat.set_system_prompt("sys".to_owned());
+
+        let messages = [Message::system("be brief".to_owned()), Message::text(Role::User, "hi".to_owned())];
+        chat.get_inference(&messages, ToolChoice::None).expect("receive response");
+    }
+
+    #[test]
+    fn test_inline_system_message_first() {
+        use crate::request::stub::RecordingClient;
+
+        let config = Config::new("openai".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        let client = RecordingClient::new(json!({"choices": [{"message": {"role": "assistant", "content": "hello"}, "finish_reason": "stop"}]}));
+        let mut chat = OpenAIChat::new(config, Box::new(client.clone()), vec![]);
+
+        // Inline system message without the stored prompt is a part of the conversation.
+        let messages = [Message::system("be brief".to_owned()), Message::text(Role::User, "hi".to_owned())];
+        chat.get_inference(&messages, ToolChoice::None).expect("receive response");
+        assert_eq!(chat.export_history()[0], json!({"role": "system", "content": "be brief"}));
+
+        chat.set_system_prompt("sys".to_owned());
+        chat.get_inference_with_system("other", &[Message::text(Role::User, "again".to_owned())], ToolChoice::None).expect("receive response");
+
+        let requests = client.requests();
+        assert_eq!(requests[1].payload["messages"][0], json!({"role": "system", "content": "other"}));
+        assert_eq!(requests[1].payload["messages"][1], json!({"role": "system", "content": "be brief"}));
+        assert_eq!(chat.export_history().len(), 5);
+    }
+
+    #[test]
+    fn test_system_override() {
+        use crate::request::stub::RecordingClient;
+
+        let config = Config::new("openai".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        let client = RecordingClient::new(json!({"choices": [{"message": {"role": "assistant", "content": "hello"}, "finish_reason": "stop"}]}));
+        let mut chat = OpenAIChat::new(config, Box::new(client.clone()), vec![]);
+        chat.set_system_prompt("sys".to_owned());
+
+        chat.get_inference_with_system("other", &[Message::text(Role::User, "hi".to_owned())], ToolChoice::None).expect("receive response");
+        chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None).expect("receive response");
+
+        let requests = client.requests();
+        assert_eq!(requests[0].payload["messages"][0], json!({"role": "system", "content": "other"}));
+        assert_eq!(requests[1].payload["messages"][0], json!({"role": "system", "content": "sys"}));
+        assert_eq!(chat.export_history().len(), 4);
+    }
+
+    fn tool_turn_client() -> SequenceClient {
+        let tool_call = json!({"role": "assistant", "content": null, "tool_calls": [
+            {"id": "call_1", "type": "function", "function": {"name": "SHELL", "arguments": "{\"command\": \"gcloud compute instances list\"}"}}
+        ]});
+        SequenceClient::new(vec![
+            json!({"choices": [{"message": tool_call, "finish_reason": "tool_calls"}]}),
+            json!({"choices": [{"message": {"role": "assistant", "content": "No instances."}, "finish_reason": "stop"}]}),
+        ])
+    }
+
+    #[test]
+    fn test_history_single_append() {
+        let config = Config::new("openai".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        let mut chat = OpenAIChat::new(config, Box::new(tool_turn_client()), vec![]);
+
+        chat.get_inference(&[Message::text(Role::User, "list vms".to_owned())], ToolChoice::Auto).expect("receive response");
+        assert_eq!(chat.export_history().len(), 2);
+
+        let result = Message::tool_result("call_1".to_owned(), "SHELL".to_owned(), "Listed 0 items.".to_owned());
+        chat.get_inference(&[result], ToolChoice::Auto).expect("receive response");
+
+        let roles: Vec<Value> = chat.export_history().iter().map(|entry| entry["role"].clone()).collect();
+        assert_eq!(roles, vec!["user", "assistant", "tool", "assistant"]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "tool result was already sent")]
+    fn test_resent_tool_result() {
+        let config = Config::new("openai".try_into().expect("determine model provider"),
+            "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+        let mut chat = OpenAIChat::new(config, Box::new(tool_turn_client()), vec![]);
+
+        let user = Message::text(Role::User, "list vms".to_owned());
+        chat.get_inference(std::slice::from_ref(&user), ToolChoice::Auto).expect("receive response");
+        let result = Message::tool_result("call_1".to_owned(), "SHELL".to_owned(), "Listed 0 items.".to_owned());
+        chat.get_inference(std::slice::from_ref(&result), ToolChoice::Auto).expect("receive response");
+
+        // The whole conversation is passed again instead of the new messages.
+        let _ = chat.get_inference(&[user, result, Message::text(Role::User, "thanks".to_owned())], ToolChoice::Auto);
+    }
+}
@@ -1,16 +1,17 @@
 use serde_json::{json, Number, Value};
-use crate::{config::ModelProvider, error::Error, tools::ToolParam};
-use super::Role;
+use crate::{config::{Config, ModelProvider}, error::Error, tools::ToolParam};
+use super::{Capabilities, Message, Role, StopReason, Text, TokenLogprob, Usage};
 
 /// Get model-specific role for the provider.
 pub fn role_to_llm(provider: ModelProvider, role: Role) -> &'static str {
     const ROLES_FOR_OPENAI: [&str; 3] = ["system", "assistant", "user"];
-    const ROLES_FOR_ANTHROPIC: [&str; 3] = ["", "assistant", "user"];
+    // Anthropic has no system role in messages, system guidance is sent as user notes.
+    const ROLES_FOR_ANTHROPIC: [&str; 3] = ["user", "assistant", "user"];
     const ROLES_FOR_GCP: [&str; 3] = ["system", "model", "user"];
 
     match provider {
-        ModelProvider::OpenAI => ROLES_FOR_OPENAI[role as usize],
-        ModelProvider::Anthropic => ROLES_FOR_ANTHROPIC[role as usize],
+        ModelProvider::OpenAI | ModelProvider::DeepSeek | ModelProvider::Groq | ModelProvider::OpenRouter | ModelProvider::Cohere | ModelProvider::Echo => ROLES_FOR_OPENAI[role as usize],
+        ModelProvider::Anthropic | ModelProvider::Bedrock => ROLES_FOR_ANTHROPIC[role as usize],
         ModelProvider::GCP => ROLES_FOR_GCP[role as usize],
     }
 }
@@ -25,6 +26,164 @@ pub fn llm_to_role(role: &str) -> Result<Role, Error> {
     }
 }
 
+/// Add the tool result entry to the history.
+/// Panics in debug builds if the entry is already there, i.e. already sent messages were passed to the inference again.
+pub fn push_tool_result(history: &mut Vec<Value>, entry: Value) {
+    debug_assert!(!history.contains(&entry), "tool result was already sent: {}", entry);
+    history.push(entry);
+}
+
+/// Input messages end with a model message, which the response must continue (prefill).
+pub fn has_prefill(messages: &[Message]) -> bool {
+    matches!(messages.last(), Some(Message::Text(Text {role: Role::Model, ..})))
+}
+
+/// Error for the providers that don't support prefill.
+pub fn prefill_error(provider: ModelProvider) -> Error {
+    Error::Error(format!("prefill (input messages ending with a model message) is not supported by {}.", provider))
+}
+
+/// Provider headers followed by `Config::extra_headers`.
+pub fn with_extra_headers<'a>(headers: &[(&'a str, &'a str)], config: &'a Config) -> Vec<(&'a str, &'a str)> {
+    headers.iter().copied()
+        .chain(config.extra_headers.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+        .collect()
+}
+
+/// Get logical stop reason by model stop reason.
+pub fn llm_to_stop_reason(provider: ModelProvider, reason: &str) -> StopReason {
+    match (provider, reason) {
+        (ModelProvider::OpenAI | ModelProvider::DeepSeek | ModelProvider::Groq | ModelProvider::OpenRouter, "stop") => StopReason::EndTurn,
+        (ModelProvider::OpenAI | ModelProvider::DeepSeek | ModelProvider::Groq | ModelProvider::OpenRouter, "length") => StopReason::MaxTokens,
+        (ModelProvider::OpenAI | ModelProvider::DeepSeek | ModelProvider::Groq | ModelProvider::OpenRouter, "tool_calls") => StopReason::ToolUse,
+        (ModelProvider::Anthropic | ModelProvider::Bedrock, "end_turn") => StopReason::EndTurn,
+        (ModelProvider::Anthropic | ModelProvider::Bedrock, "max_tokens") => StopReason::MaxTokens,
+        (ModelProvider::Anthropic | ModelProvider::Bedrock, "stop_sequence") => StopReason::StopSequence,
+        (ModelProvider::Anthropic | ModelProvider::Bedrock, "tool_use") => StopReason::ToolUse,
+        (ModelProvider::GCP, "STOP") => StopReason::EndTurn,
+        (ModelProvider::GCP, "MAX_TOKENS") => StopReason::MaxTokens,
+        (ModelProvider::Cohere, "COMPLETE") => StopReason::EndTurn,
+        (ModelProvider::Cohere, "MAX_TOKENS") => StopReason::MaxTokens,
+        (ModelProvider::Cohere, "STOP_SEQUENCE") => StopReason::StopSequence,
+        (ModelProvider::Cohere, "TOOL_CALL") => StopReason::ToolUse,
+        _ => StopReason::Other(reason.to_owned()),
+    }
+}
+
+/// Extract token usage from the provider response.
+pub fn llm_to_usage(provider: ModelProvider, response: &Value) -> Option<Usage> {
+    let count = |val: &Value| val.as_u64().unwrap_or(0);
+
+    match provider {
+        ModelProvider::OpenAI | ModelProvider::DeepSeek | ModelProvider::Groq | ModelProvider::OpenRouter => {
+            let usage = response.get("usage")?;
+            Some(Usage {
+                input_tokens: count(&usage["prompt_tokens"]),
+                output_tokens: count(&usage["completion_tokens"]),
+            })
+        },
+        ModelProvider::Anthropic | ModelProvider::Bedrock => {
+            let usage = response.get("usage")?;
+            Some(Usage {
+                input_tokens: count(&usage["input_tokens"]) + count(&usage["cache_creation_input_tokens"]) + count(&usage["cache_read_input_tokens"]),
+                output_tokens: count(&usage["output_tokens"]),
+            })
+        },
+        ModelProvider::GCP => {
+            let usage = response.get("usageMetadata")?;
+            Some(Usage {
+                input_tokens: count(&usage["promptTokenCount"]),
+                output_tokens: count(&usage["candidatesTokenCount"]) + count(&usage["thoughtsTokenCount"]),
+            })
+        },
+        ModelProvider::Cohere => {
+            // Billed units exclude the tokens of the prompt template.
+            let usage = response.get("usage")?.get("tokens")?;
+            Some(Usage {
+                input_tokens: count(&usage["input_tokens"]),
+                output_tokens: count(&usage["output_tokens"]),
+            })
+        },
+        ModelProvider::Echo => None,
+    }
+}
+
+/// Check if the model accepts reasoning parameters (reasoning effort, thinking budget).
+pub fn supports_reasoning(provider: ModelProvider, model: &str) -> bool {
+    match provider {
+        ModelProvider::OpenAI => ["o1", "o3", "o4", "gpt-5"].iter().any(|prefix| model.starts_with(prefix)),
+        ModelProvider::Anthropic => !model.starts_with("claude-3-") || model.starts_with("claude-3-7"),
+        ModelProvider::GCP => !model.starts_with("gemini-1") && !model.starts_with("gemini-2.0"),
+        // deepseek-reasoner always reasons and accepts no reasoning parameters.
+        ModelProvider::DeepSeek => false,
+        ModelProvider::Groq => model.starts_with("openai/gpt-oss"),
+        // OpenRouter has its own reasoning parameters.
+        ModelProvider::Cohere | ModelProvider::OpenRouter | ModelProvider::Echo => false,
+        // Model ids have a prefix, e.g. `us.anthropic.claude-sonnet-4-20250514-v1:0`.
+        ModelProvider::Bedrock => supports_reasoning(ModelProvider::Anthropic, model.rsplit("anthropic.").next().unwrap_or(model)),
+    }
+}
+
+/// Guess the model capabilities from the provider and the model name.
+pub fn model_capabilities(provider: ModelProvider, model: &str) -> Capabilities {
+    match provider {
+        ModelProvider::OpenAI => Capabilities {
+            supports_tools: !model.starts_with("o1-mini") && !model.starts_with("o1-preview"),
+            supports_vision: ["gpt-4o", "gpt-4.1", "gpt-4-turbo", "gpt-5", "o1", "o3", "o4"].iter().any(|prefix| model.starts_with(prefix))
+                && !model.starts_with("o1-mini") && !model.starts_with("o3-mini"),
+            supports_streaming: false,
+            max_context: if model.starts_with("gpt-4.1") {
+                Some(1_047_576)
+            } else if model.starts_with("gpt-5") {
+                Some(400_000)
+            } else if ["o1", "o3", "o4"].iter().any(|prefix| model.starts_with(prefix)) {
+                Some(200_000)
+            } else if model.starts_with("gpt-4o") || model.starts_with("gpt-4-turbo") {
+                Some(128_000)
+            } else {
+                None
+            },
+        },
+        ModelProvider::Anthropic | ModelProvider::Bedrock => {
+            let claude = model.contains("claude-");
+            Capabilities {
+                supports_tools: claude,
+                supports_vision: claude && !model.contains("claude-3-5-haiku"),
+                // Bedrock responses are not streamed.
+                supports_streaming: provider == ModelProvider::Anthropic,
+                max_context: if claude { Some(200_000) } else { None },
+            }
+        },
+        ModelProvider::GCP => {
+            let gemini = model.starts_with("gemini-") && !model.starts_with("gemini-1.0");
+            Capabilities {
+                supports_tools: true,
+                supports_vision: gemini,
+                supports_streaming: true,
+                max_context: if gemini { Some(1_048_576) } else { None },
+            }
+        },
+        ModelProvider::DeepSeek => Capabilities {
+            supports_tools: model != "deepseek-reasoner",
+            supports_vision: false,
+            supports_streaming: false,
+            max_context: Some(64_000),
+        },
+        ModelProvider::Groq | ModelProvider::Cohere | ModelProvider::OpenRouter => Capabilities {
+            supports_tools: true,
+            supports_vision: model.contains("vision"),
+            supports_streaming: false,
+            max_context: None,
+        },
+        ModelProvider::Echo => Capabilities {
+            supports_tools: false,
+            supports_vision: false,
+            supports_streaming: false,
+            max_context: None,
+        },
+    }
+}
+
 /// Interpret value as str
 #[macro_export(local_inner_macros)]
 macro_rules! val_as_str {
@@ -35,6 +194,111 @@ macro_rules! val_as_str {
     }
 }
 
+/// Get error from the provider's error object.
+/// Falls back to `Error::LLMErrorMessage` if the error has neither code nor type.
+pub fn llm_to_error(provider: ModelProvider, error: &Value) -> Result<Error, Error> {
+    let message = val_as_str!(error["message"], "error message").to_owned();
+
+    let (code, kind) = match provider {
+        ModelProvider::OpenAI | ModelProvider::DeepSeek | ModelProvider::Groq | ModelProvider::OpenRouter => (error["code"].as_str().map(str::to_owned), error["type"].as_str()),
+        ModelProvider::Anthropic | ModelProvider::Bedrock => (None, error["type"].as_str()),
+        ModelProvider::GCP => {
+            // The reason, e.g. API_KEY_INVALID, is more specific than the HTTP status code.
+            let reason = error["details"].as_array()
+                .and_then(|details| details.iter().find_map(|detail| detail["reason"].as_str()));
+            let code = reason.map(str::to_owned).or_else(|| error["code"].as_i64().map(|code| code.to_string()));
+            (code, error["status"].as_str())
+        },
+        ModelProvider::Cohere | ModelProvider::Echo => (None, None),
+    };
+
+    Ok(if code.is_none() && kind.is_none() {
+        Error::LLMErrorMessage(message)
+    } else {
+        Error::ProviderError { code, kind: kind.map(str::to_owned), message }
+    })
+}
+
+/// Candidate responses of the last inference in the provider-specific format.
+#[derive(Default)]
+pub struct Candidates {
+    entries: Vec<Vec<Value>>,
+    stop_reasons: Vec<Option<StopReason>>,
+    reasoning: Vec<Option<String>>,
+    logprobs: Vec<Option<Vec<TokenLogprob>>>,
+    usage: Option<Usage>,
+    selected: usize,
+}
+
+impl Candidates {
+
+    /// Store candidates and append the first one to the history.
+    pub fn keep(&mut self, history: &mut Vec<Value>, entries: Vec<Vec<Value>>, stop_reasons: Vec<Option<StopReason>>, reasoning: Vec<Option<String>>) {
+        if let Some(first) = entries.first() {
+            history.extend(first.iter().cloned());
+        }
+        self.entries = entries;
+        self.stop_reasons = stop_reasons;
+        self.reasoning = reasoning;
+        self.logprobs.clear();
+        self.selected = 0;
+    }
+
+    /// Set token log probabilities of the candidates.
+    pub fn set_logprobs(&mut self, logprobs: Vec<Option<Vec<TokenLogprob>>>) {
+        self.logprobs = logprobs;
+    }
+
+    /// Token log probabilities of the selected candidate.
+    pub fn logprobs(&self) -> Option<Vec<TokenLogprob>> {
+        self.logprobs.get(self.selected).cloned().flatten()
+    }
+
+    /// Set token usage of the inference.
+    pub fn set_usage(&mut self, usage: Option<Usage>) {
+        self.usage = usage;
+    }
+
+    /// Token usage of the inference.
+    pub fn usage(&self) -> Option<Usage> {
+        self.usage
+    }
+
+    /// Reasoning output of the selected candidate.
+    pub fn reasoning(&self) -> Option<String> {
+        self.reasoning.get(self.selected).cloned().flatten()
+    }
+
+    /// Stop reason of the selected candidate.
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reasons.get(self.selected).cloned().flatten()
+    }
+
+    /// Replace the selected candidate at the end of the history with the candidate `index`.
+    pub fn select(&mut self, history: &mut Vec<Value>, index: usize) -> Result<(), Error> {
+        let current = self.entries.get(self.selected)
+            .ok_or(Error::Error("there are no candidates to select from.".to_owned()))?;
+        let new = self.entries.get(index)
+            .ok_or_else(|| Error::Error(format!("candidate index {} is out of range.", index)))?;
+
+        history.truncate(history.len().saturating_sub(current.len()));
+        history.extend(new.iter().cloned());
+        self.selected = index;
+
+        Ok(())
+    }
+
+    /// Forget candidates.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.stop_reasons.clear();
+        self.reasoning.clear();
+        self.logprobs.clear();
+        self.usage = None;
+        self.selected = 0;
+    }
+}
+
 pub fn set_i64_param(payload: &mut Value, key: &str, val: &Option<i64>) {
     if let Some(v) = val {
         payload[key] = Value::Number(Number::from_i128(*v as i128).unwrap());
@@ -63,6 +327,25 @@ pub fn tool_params_to_value(params: &[ToolParam], provider: ModelProvider) -> Va
             "type": param.data_type,
             "description": param.description,
         });
+        let property = &mut result["properties"][&param.name];
+        if let Some(default) = &param.default {
+            property["default"] = default.clone();
+        }
+        if let Some(minimum) = param.minimum {
+            property["minimum"] = json!(minimum);
+        }
+        if let Some(maximum) = param.maximum {
+            property["maximum"] = json!(maximum);
+        }
+        if let Some(min_length) = param.min_length {
+            property["minLength"] = json!(min_length);
+        }
+        if let Some(max_length) = param.max_length {
+            property["maxLength"] = json!(max_length);
+        }
+        if let Some(pattern) = &param.pattern {
+            property["pattern"] = json!(pattern);
+        }
         if param.required {
             required.push(Value::String(param.name.clone()));
         }
@@ -70,14 +353,135 @@ pub fn tool_params_to_value(params: &[ToolParam], provider: ModelProvider) -> Va
 
     result["required"] = Value::Array(required);
     match provider {
-        ModelProvider::OpenAI => {
+        ModelProvider::OpenAI | ModelProvider::DeepSeek | ModelProvider::Groq | ModelProvider::OpenRouter => {
             result["additionalProperties"] = Value::Bool(false);
         }
-        ModelProvider::Anthropic => {
+        ModelProvider::Anthropic | ModelProvider::Bedrock => {
             result["additionalProperties"] = Value::Bool(false);
         }
-        ModelProvider::GCP => {}
+        ModelProvider::GCP | ModelProvider::Cohere | ModelProvider::Echo => {}
     }
 
     result
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_to_llm() {
+        for provider in [ModelProvider::OpenAI, ModelProvider::Anthropic, ModelProvider::GCP] {
+            for role in [Role::System, Role::Model, Role::User] {
+                assert!(!role_to_llm(provider, role).is_empty());
+            }
+        }
+        assert_eq!(role_to_llm(ModelProvider::Anthropic, Role::System), "user");
+    }
+
+    #[test]
+    fn test_llm_to_error() {
+        let error = json!({"code": "insufficient_quota", "message": "quota", "param": null, "type": "insufficient_quota"});
+        assert!(matches!(llm_to_error(ModelProvider::OpenAI, &error).expect("error"),
+            Error::ProviderError { code: Some(code), kind: Some(kind), message } if code == "insufficient_quota" && kind == "insufficient_quota" && message == "quota"));
+
+        let error = json!({"type": "authentication_error", "message": "invalid x-api-key"});
+        assert!(matches!(llm_to_error(ModelProvider::Anthropic, &error).expect("error"),
+            Error::ProviderError { code: None, kind: Some(kind), .. } if kind == "authentication_error"));
+
+        let error = json!({"code": 400, "message": "invalid key", "status": "INVALID_ARGUMENT",
+            "details": [{"@type": "type.googleapis.com/google.rpc.ErrorInfo", "reason": "API_KEY_INVALID"}]});
+        assert!(matches!(llm_to_error(ModelProvider::GCP, &error).expect("error"),
+            Error::ProviderError { code: Some(code), kind: Some(kind), .. } if code == "API_KEY_INVALID" && kind == "INVALID_ARGUMENT"));
+
+        let error = json!({"code": 503, "message": "overloaded", "status": "UNAVAILABLE"});
+        assert!(matches!(llm_to_error(ModelProvider::GCP, &error).expect("error"),
+            Error::ProviderError { code: Some(code), .. } if code == "503"));
+
+        let error = json!({"message": "something went wrong"});
+        assert!(matches!(llm_to_error(ModelProvider::OpenAI, &error).expect("error"),
+            Error::LLMErrorMessage(message) if message == "something went wrong"));
+
+        assert!(llm_to_error(ModelProvider::OpenAI, &json!({})).is_err());
+    }
+
+    #[test]
+    fn test_llm_to_usage() {
+        let response = json!({"usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}});
+        assert_eq!(llm_to_usage(ModelProvider::OpenAI, &response), Some(Usage { input_tokens: 10, output_tokens: 5 }));
+
+        let response = json!({"usage": {"input_tokens": 10, "cache_read_input_tokens": 100, "output_tokens": 5}});
+        assert_eq!(llm_to_usage(ModelProvider::Anthropic, &response), Some(Usage { input_tokens: 110, output_tokens: 5 }));
+
+        let response = json!({"usageMetadata": {"promptTokenCount": 10, "candidatesTokenCount": 5, "thoughtsTokenCount": 20}});
+        assert_eq!(llm_to_usage(ModelProvider::GCP, &response), Some(Usage { input_tokens: 10, output_tokens: 25 }));
+
+        assert_eq!(llm_to_usage(ModelProvider::GCP, &json!({})), None);
+    }
+
+    #[test]
+    fn test_supports_reasoning() {
+        assert!(supports_reasoning(ModelProvider::OpenAI, "o3-mini"));
+        assert!(!supports_reasoning(ModelProvider::OpenAI, "gpt-4o"));
+        assert!(supports_reasoning(ModelProvider::Anthropic, "claude-3-7-sonnet-20250219"));
+        assert!(supports_reasoning(ModelProvider::Anthropic, "claude-sonnet-4-20250514"));
+        assert!(!supports_reasoning(ModelProvider::Anthropic, "claude-3-5-sonnet-20241022"));
+        assert!(supports_reasoning(ModelProvider::Bedrock, "us.anthropic.claude-sonnet-4-20250514-v1:0"));
+        assert!(!supports_reasoning(ModelProvider::Bedrock, "anthropic.claude-3-5-sonnet-20240620-v1:0"));
+        assert!(supports_reasoning(ModelProvider::GCP, "gemini-2.5-pro"));
+        assert!(!supports_reasoning(ModelProvider::GCP, "gemini-1.5-pro-002"));
+    }
+
+    #[test]
+    fn test_tool_params_to_value() {
+        use crate::tools::ParamType;
+
+        let param = |name: &str, data_type: ParamType| ToolParam::new(name.to_owned(), format!("{} desc", name), data_type, false);
+
+        let mut command = param("command", ParamType::String);
+        command.required = true;
+        let timeout = param("timeout", ParamType::Integer).with_default(json!(30));
+        let params = vec![command, timeout];
+
+        let value = tool_params_to_value(&params, ModelProvider::GCP);
+        assert_eq!(value, json!({
+            "type": "object",
+            "properties": {
+                "command": {"type": "string", "description": "command desc"},
+                "timeout": {"type": "integer", "description": "timeout desc", "default": 30},
+            },
+            "required": ["command"],
+        }));
+
+        let count = param("count", ParamType::Integer).with_minimum(1.0).with_maximum(10.0);
+        let name = param("name", ParamType::String).with_min_length(1).with_max_length(64).with_pattern("^[a-z]+$".to_owned());
+        let params = vec![count, name];
+
+        for provider in [ModelProvider::OpenAI, ModelProvider::Anthropic, ModelProvider::GCP] {
+            let value = tool_params_to_value(&params, provider);
+            assert_eq!(value["properties"]["count"], json!({"type": "integer", "description": "count desc", "minimum": 1.0, "maximum": 10.0}));
+            assert_eq!(value["properties"]["name"],
+                json!({"type": "string", "description": "name desc", "minLength": 1, "maxLength": 64, "pattern": "^[a-z]+$"}));
+        }
+    }
+
+    #[test]
+    fn test_model_capabilities() {
+        let caps = model_capabilities(ModelProvider::OpenAI, "gpt-4o-mini");
+        assert!(caps.supports_tools && caps.supports_vision && !caps.supports_streaming);
+        assert_eq!(caps.max_context, Some(128_000));
+
+        let caps = model_capabilities(ModelProvider::OpenAI, "o1-mini");
+        assert!(!caps.supports_tools && !caps.supports_vision);
+
+        assert_eq!(model_capabilities(ModelProvider::OpenAI, "my-fine-tuned-model").max_context, None);
+
+        let caps = model_capabilities(ModelProvider::Anthropic, "claude-sonnet-4-20250514");
+        assert!(caps.supports_tools && caps.supports_vision && caps.supports_streaming);
+        assert!(!model_capabilities(ModelProvider::Bedrock, "us.anthropic.claude-sonnet-4-20250514-v1:0").supports_streaming);
+
+        assert!(model_capabilities(ModelProvider::GCP, "gemini-2.5-pro").supports_streaming);
+        assert!(!model_capabilities(ModelProvider::DeepSeek, "deepseek-reasoner").supports_tools);
+        assert!(!model_capabilities(ModelProvider::Echo, "echo").supports_tools);
+    }
+}
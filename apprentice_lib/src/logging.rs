@@ -0,0 +1,27 @@
+//! Logging through the `log` facade, enabled with the `log` feature.
+//! Without the feature the macros expand to dead code, so the arguments are type checked but never evaluated.
+
+macro_rules! log_debug {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        log::debug!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    }};
+}
+
+macro_rules! log_trace {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        log::trace!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    }};
+}
+
+pub(crate) use log_debug;
+pub(crate) use log_trace;
@@ -0,0 +1,184 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+use serde_json::Value;
+use crate::error::Error;
+use crate::request::client::Client;
+
+struct Cache {
+    entries: HashMap<String, Value>,
+    path: Option<PathBuf>,
+    enabled: bool,
+}
+
+impl Cache {
+    fn save(&self) -> Result<(), Error> {
+        if let Some(path) = &self.path {
+            let content = serde_json::to_string(&self.entries)?;
+            std::fs::write(path, content)
+                .map_err(|err| Error::Error(format!("failed to save cache file {}: {}", path.display(), err)))?;
+        }
+        Ok(())
+    }
+}
+
+/// Client that returns stored responses for the requests with the same URL and payload.
+/// Responses containing an error are not cached.
+/// Clones share the same cache, so a clone kept by the caller can disable or clear
+/// the cache of the client passed to a chat.
+#[derive(Clone)]
+pub struct CachingClient {
+    inner: Rc<dyn Client>,
+    cache: Rc<RefCell<Cache>>,
+}
+
+impl CachingClient {
+
+    /// Create in-memory cache over the client.
+    pub fn new(inner: Box<dyn Client>) -> Self {
+        CachingClient {
+            inner: Rc::from(inner),
+            cache: Rc::new(RefCell::new(Cache {
+                entries: HashMap::new(),
+                path: None,
+                enabled: true,
+            })),
+        }
+    }
+
+    /// Create cache over the client persisted to the JSON file.
+    /// Existing file content is loaded.
+    pub fn with_file(inner: Box<dyn Client>, path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+
+        let entries = if path.exists() {
+            let content = std::fs::read_to_string(&path)
+                .map_err(|err| Error::Error(format!("failed to load cache file {}: {}", path.display(), err)))?;
+            serde_json::from_str(&content)?
+        } else {
+            HashMap::new()
+        };
+
+        let client = CachingClient::new(inner);
+        {
+            let mut cache = client.cache.borrow_mut();
+            cache.entries = entries;
+            cache.path = Some(path);
+        }
+        Ok(client)
+    }
+
+    /// Enable or disable the cache. Disabled cache neither returns nor stores responses.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.cache.borrow_mut().enabled = enabled;
+    }
+
+    /// Remove all stored responses.
+    pub fn clear(&self) -> Result<(), Error> {
+        let mut cache = self.cache.borrow_mut();
+        cache.entries.clear();
+        cache.save()
+    }
+
+    // FNV-1a, stable between runs unlike the std hasher.
+    fn key(url: &str, payload: &Value) -> String {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in url.bytes().chain([0]).chain(payload.to_string().bytes()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        format!("{:016x}", hash)
+    }
+}
+
+impl Client for CachingClient {
+
+    fn make_json_request(&self, url: &str, payload: Value, headers: &[(&str, &str)], params: &[(&str, &str)]) -> Result<Value, Error> {
+        if !self.cache.borrow().enabled {
+            return self.inner.make_json_request(url, payload, headers, params);
+        }
+
+        let key = Self::key(url, &payload);
+
+        if let Some(response) = self.cache.borrow().entries.get(&key) {
+            return Ok(response.clone());
+        }
+
+        let response = self.inner.make_json_request(url, payload, headers, params)?;
+
+        if response.get("error").is_none() {
+            let mut cache = self.cache.borrow_mut();
+            cache.entries.insert(key, response.clone());
+            cache.save()?;
+        }
+
+        Ok(response)
+    }
+
+    // Streamed responses are not cached.
+    fn make_streaming_request(&self, url: &str, payload: Value, headers: &[(&str, &str)], params: &[(&str, &str)],
+        on_line: &mut dyn FnMut(&str) -> Result<(), Error>) -> Result<(), Error>
+    {
+        self.inner.make_streaming_request(url, payload, headers, params, on_line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use serde_json::json;
+
+    struct CountingClient {
+        calls: Rc<Cell<usize>>,
+    }
+
+    impl Client for CountingClient {
+        fn make_json_request(&self, _url: &str, payload: Value, _headers: &[(&str, &str)], _params: &[(&str, &str)]) -> Result<Value, Error> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(json!({"echo": payload}))
+        }
+    }
+
+    #[test]
+    fn test_caching_client() {
+        let calls = Rc::new(Cell::new(0));
+        let client = CachingClient::new(Box::new(CountingClient { calls: calls.clone() }));
+
+        let resp1 = client.make_json_request("<url>", json!({"a": 1}), &[], &[]).expect("response");
+        let resp2 = client.make_json_request("<url>", json!({"a": 1}), &[], &[]).expect("response");
+        assert_eq!(resp1, resp2);
+        assert_eq!(calls.get(), 1);
+
+        client.make_json_request("<url>", json!({"a": 2}), &[], &[]).expect("response");
+        assert_eq!(calls.get(), 2);
+
+        client.clone().set_enabled(false);
+        client.make_json_request("<url>", json!({"a": 1}), &[], &[]).expect("response");
+        assert_eq!(calls.get(), 3);
+
+        client.set_enabled(true);
+        client.clear().expect("clear cache");
+        client.make_json_request("<url>", json!({"a": 1}), &[], &[]).expect("response");
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[test]
+    fn test_caching_client_file() {
+        let path = std::env::temp_dir().join(format!("apprentice_cache_test_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let calls = Rc::new(Cell::new(0));
+
+        let client = CachingClient::with_file(Box::new(CountingClient { calls: calls.clone() }), &path).expect("create cache");
+        client.make_json_request("<url>", json!({"a": 1}), &[], &[]).expect("response");
+
+        let client = CachingClient::with_file(Box::new(CountingClient { calls: calls.clone() }), &path).expect("load cache");
+        client.make_json_request("<url>", json!({"a": 1}), &[], &[]).expect("response");
+
+        assert_eq!(calls.get(), 1);
+
+        std::fs::remove_file(path).expect("remove cache file");
+    }
+}
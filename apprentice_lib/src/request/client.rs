@@ -2,13 +2,30 @@ use serde_json::Value;
 use crate::error::Error;
 use super::reqwest::ReqwestClient;
 
+pub use super::reqwest::DEFAULT_USER_AGENT;
+
 /// Request client.
 pub trait Client {
     /// Send request and receive response.
     fn make_json_request(&self, url: &str, payload: Value, headers: &[(&str, &str)], params: &[(&str, &str)]) -> Result<Value, Error>;
+
+    /// Send request and pass each line of the response body to `on_line` as soon as it is received,
+    /// e.g. the events of a streamed response. Error responses are passed the same way.
+    /// An error returned by `on_line` stops reading and is returned.
+    /// Clients without streaming support return an error.
+    fn make_streaming_request(&self, _url: &str, _payload: Value, _headers: &[(&str, &str)], _params: &[(&str, &str)],
+        _on_line: &mut dyn FnMut(&str) -> Result<(), Error>) -> Result<(), Error>
+    {
+        Err(Error::Error("streaming is not supported by the client.".to_owned()))
+    }
 }
 
-/// Create reqwest client.
+/// Callback invoked after each request with the URL, payload, and response or error.
+/// Streamed responses are not kept, so they are passed as null.
+pub type Interceptor = Box<dyn Fn(&str, &Value, Result<&Value, &Error>)>;
+
+/// Create reqwest client with the default options,
+/// see [`ReqwestClient::builder`] for verbose output, retries, interceptor, user agent, cancellation, or a shared connection pool.
 pub fn get_reqwest_client() -> Result<Box<dyn Client>, Error> {
-    Ok(Box::new(ReqwestClient::new()))
-}
\ No newline at end of file
+    Ok(Box::new(ReqwestClient::builder().build()))
+}
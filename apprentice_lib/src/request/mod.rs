@@ -1,9 +1,19 @@
 //! API transport.
 mod client;
 mod reqwest;
+mod caching;
+mod cancellation;
+mod retry;
+pub(crate) mod sigv4;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "testing"))]
 pub mod stub;
 
 pub use client::Client;
-pub use client::get_reqwest_client;
\ No newline at end of file
+pub use client::get_reqwest_client;
+pub use client::Interceptor;
+pub use client::DEFAULT_USER_AGENT;
+pub use self::reqwest::{ReqwestClient, ReqwestClientBuilder};
+pub use caching::CachingClient;
+pub use cancellation::CancellationToken;
+pub use retry::RetryPolicy;
\ No newline at end of file
@@ -1,37 +1,588 @@
-use reqwest::blocking::Client as BlockingClient;
+use std::io::{BufRead, BufReader};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+use reqwest::blocking::{Client as BlockingClient, RequestBuilder};
+use reqwest::StatusCode;
 use serde_json::Value;
 use crate::error::Error;
-use crate::request::client::Client;
+use crate::request::client::{Client, Interceptor};
+use crate::request::cancellation::CancellationToken;
+use crate::request::retry::RetryPolicy;
+use crate::logging::{log_debug, log_trace};
 
+/// Header and query parameter names whose values are hidden in verbose output.
+const SECRET_NAMES: [&str; 4] = ["authorization", "x-api-key", "api-key", "key"];
+
+/// How often a cancellable client checks the token while waiting for the response.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// User agent sent unless the client is built with a custom one.
+pub const DEFAULT_USER_AGENT: &str = concat!("apprentice-lib/", env!("CARGO_PKG_VERSION"));
+
+/// Client over a blocking reqwest client, created with [`ReqwestClient::builder`].
 pub struct ReqwestClient {
     client: BlockingClient,
+    verbose: bool,
+    interceptor: Option<Interceptor>,
+    retry_policy: RetryPolicy,
+    user_agent: String,
+    cancellation: Option<CancellationToken>,
 }
 
-impl ReqwestClient {
+/// Builder of [`ReqwestClient`], the options can be combined.
+#[derive(Default)]
+pub struct ReqwestClientBuilder {
+    client: Option<BlockingClient>,
+    verbose: bool,
+    interceptor: Option<Interceptor>,
+    retry_policy: RetryPolicy,
+    user_agent: Option<String>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl ReqwestClientBuilder {
+
+    /// Send requests with an externally built reqwest client.
+    /// `reqwest::blocking::Client` clones share the connection pool, so passing clones of one client
+    /// to several chats reuses connections and TLS sessions between them.
+    pub fn client(mut self, client: BlockingClient) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Print request payloads and responses to stderr.
+    /// Secret headers and query parameters are redacted.
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
 
-    pub fn new() -> Self {
+    /// Call `interceptor` after each request, e.g. to log latency or record transcripts.
+    pub fn interceptor(mut self, interceptor: Interceptor) -> Self {
+        self.interceptor = Some(interceptor);
+        self
+    }
+
+    /// Repeat a request that fails with a connection error, a rate limit, or a server error
+    /// according to the `policy`. Requests are not repeated by default.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Send the custom `User-Agent` header instead of [`DEFAULT_USER_AGENT`],
+    /// e.g. to identify the application traffic in the provider dashboards.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Fail the requests with `Error::Cancelled` once the `token` is cancelled.
+    /// A streamed response stops at the next received line, and the connection is dropped;
+    /// other requests are abandoned in a background thread.
+    /// The input messages of a cancelled inference stay in the chat history without a response, as with other errors.
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Create the client.
+    pub fn build(self) -> ReqwestClient {
         ReqwestClient {
-            client: BlockingClient::new(),
+            client: self.client.unwrap_or_default(),
+            verbose: self.verbose,
+            interceptor: self.interceptor,
+            retry_policy: self.retry_policy,
+            user_agent: self.user_agent.unwrap_or_else(|| DEFAULT_USER_AGENT.to_owned()),
+            cancellation: self.cancellation,
         }
     }
 }
 
-impl Client for ReqwestClient {
+impl ReqwestClient {
 
-    fn make_json_request(&self, url: &str, payload: Value, headers: &[(&str, &str)], params: &[(&str, &str)]) -> Result<Value, Error> {
+    /// Builder with the default options.
+    pub fn builder() -> ReqwestClientBuilder {
+        ReqwestClientBuilder::default()
+    }
+
+    fn redact<'a>(name: &str, value: &'a str) -> &'a str {
+        if SECRET_NAMES.contains(&name.to_lowercase().as_str()) {
+            "<redacted>"
+        } else {
+            value
+        }
+    }
+
+    // Headers or query parameters with the secret values hidden.
+    fn redact_pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), Self::redact(k, v).to_owned())).collect()
+    }
+
+    // Copy of the payload with the secret values hidden at any depth.
+    fn redact_payload(payload: &Value) -> Value {
+        match payload {
+            Value::Object(map) => Value::Object(map.iter()
+                .map(|(k, v)| match v {
+                    Value::String(s) => (k.clone(), Value::String(Self::redact(k, s).to_owned())),
+                    _ => (k.clone(), Self::redact_payload(v)),
+                })
+                .collect()),
+            Value::Array(items) => Value::Array(items.iter().map(Self::redact_payload).collect()),
+            _ => payload.clone(),
+        }
+    }
+
+    fn log_request(url: &str, payload: &Value, headers: &[(&str, &str)], params: &[(&str, &str)]) {
+        eprintln!(">>> POST {}", url);
+        for (k, v) in headers {
+            eprintln!(">>> header {}: {}", k, Self::redact(k, v));
+        }
+        for (k, v) in params {
+            eprintln!(">>> param {}={}", k, Self::redact(k, v));
+        }
+        eprintln!(">>> {}", serde_json::to_string_pretty(payload).unwrap_or_default());
+    }
+
+    fn build_request(&self, url: &str, payload: &Value, headers: &[(&str, &str)], params: &[(&str, &str)]) -> RequestBuilder {
+
+        if self.verbose {
+            Self::log_request(url, payload, headers, params);
+        }
+
+        log_debug!("POST {}", url);
+        log_trace!("headers: {:?}, params: {:?}, payload: {}",
+            Self::redact_pairs(headers), Self::redact_pairs(params), Self::redact_payload(payload));
 
         let mut request = self.client
             .post(url)
             .query(params)
-            .json(&payload);
+            .header(reqwest::header::USER_AGENT, &self.user_agent)
+            .json(payload);
 
+        // `Accept-Encoding` is not set here: reqwest sets it for the enabled compression
+        // features, and decompresses the response only if the header is its own.
         for (k, v) in headers {
             request = request.header(*k, *v);
         }
 
-        let response = request.send()?;
+        request
+    }
+
+    // Cancellable clients run the blocking call in a background thread, and stop waiting for it
+    // when the token is cancelled; the abandoned call finishes on its own.
+    fn run<T: Send + 'static>(&self, call: impl FnOnce() -> Result<T, reqwest::Error> + Send + 'static) -> Result<T, Error> {
+        let Some(token) = &self.cancellation else {
+            return Ok(call()?);
+        };
+        if token.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || sender.send(call()));
+
+        loop {
+            match receiver.recv_timeout(CANCEL_POLL_INTERVAL) {
+                Ok(ret) => return Ok(ret?),
+                Err(RecvTimeoutError::Timeout) if token.is_cancelled() => return Err(Error::Cancelled),
+                Err(RecvTimeoutError::Timeout) => {},
+                Err(RecvTimeoutError::Disconnected) => return Err(Error::Error("request thread stopped without a response.".to_owned())),
+            }
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation.as_ref().is_some_and(CancellationToken::is_cancelled)
+    }
+
+    fn send(&self, url: &str, payload: &Value, headers: &[(&str, &str)], params: &[(&str, &str)]) -> Result<(StatusCode, String), Error> {
+        let request = self.build_request(url, payload, headers, params);
+        let started = std::time::Instant::now();
+        let (status, text) = self.run(move || {
+            let response = request.send()?;
+            Ok((response.status(), response.text()?))
+        }).inspect_err(|err| log_debug!("request to {} failed: {}", url, err))?;
+
+        if self.verbose {
+            eprintln!("<<< {}\n<<< {}", status, text);
+        }
+
+        log_debug!("{} from {} in {:?}", status, url, started.elapsed());
+        log_trace!("response: {}", text);
+
+        Ok((status, text))
+    }
+
+    // Rate limits, server errors, and connection failures are transient;
+    // other errors won't go away by repeating the same request.
+    fn should_retry(ret: &Result<(StatusCode, String), Error>) -> bool {
+        match ret {
+            Ok((status, _)) => Self::is_transient_status(*status),
+            Err(Error::LLMCallError(err)) => err.is_timeout() || err.is_connect(),
+            Err(_) => false,
+        }
+    }
 
-        let ret = response.json()?;
-        Ok(ret)
+    fn is_transient_status(status: StatusCode) -> bool {
+        status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
     }
-}
\ No newline at end of file
+
+    fn wait_before_retry(&self, url: &str, attempt: u32) {
+        let delay = self.retry_policy.delay(attempt);
+        log_debug!("retrying request to {} in {:?}, attempt {} of {}", url, delay, attempt + 1, self.retry_policy.max_retries);
+        std::thread::sleep(delay);
+    }
+
+    // Each attempt sends the same URL, payload, headers, and query parameters,
+    // so auth passed as a header or as a query parameter is preserved.
+    // A successful response with a malformed body, e.g. truncated by a network failure, is retried too;
+    // a malformed error response fails right away.
+    fn send_with_retries(&self, url: &str, payload: &Value, headers: &[(&str, &str)], params: &[(&str, &str)]) -> Result<Value, Error> {
+        let mut attempt = 0;
+        loop {
+            let ret = self.send(url, payload, headers, params);
+
+            if attempt < self.retry_policy.max_retries && Self::should_retry(&ret) {
+                self.wait_before_retry(url, attempt);
+                attempt += 1;
+                continue;
+            }
+
+            let (status, text) = ret?;
+            match serde_json::from_str(&text) {
+                Err(err) if status.is_success() && attempt < self.retry_policy.max_retries => {
+                    log_debug!("malformed response from {}: {}", url, err);
+                    self.wait_before_retry(url, attempt);
+                    attempt += 1;
+                }
+                ret => return Ok(ret?),
+            }
+        }
+    }
+
+    // Only the failures before the first line is passed on are retried,
+    // a broken stream can't be resumed without repeating the lines.
+    fn stream_with_retries(&self, url: &str, payload: &Value, headers: &[(&str, &str)], params: &[(&str, &str)],
+        on_line: &mut dyn FnMut(&str) -> Result<(), Error>) -> Result<(), Error>
+    {
+        let mut attempt = 0;
+        let response = loop {
+            let request = self.build_request(url, payload, headers, params);
+            let ret = self.run(move || request.send());
+
+            let retry = match &ret {
+                Ok(response) => Self::is_transient_status(response.status()),
+                Err(Error::LLMCallError(err)) => err.is_timeout() || err.is_connect(),
+                Err(_) => false,
+            };
+            if attempt < self.retry_policy.max_retries && retry {
+                self.wait_before_retry(url, attempt);
+                attempt += 1;
+                continue;
+            }
+
+            break ret.inspect_err(|err| log_debug!("request to {} failed: {}", url, err))?;
+        };
+
+        if self.verbose {
+            eprintln!("<<< {}", response.status());
+        }
+
+        log_debug!("{} from {}, streaming the response", response.status(), url);
+
+        for line in BufReader::new(response).lines() {
+            let line = line.map_err(|err| Error::Error(format!("failed to read the response stream: {}", err)))?;
+            if self.verbose {
+                eprintln!("<<< {}", line);
+            }
+            log_trace!("response line: {}", line);
+            // Returning drops the response, which closes the connection.
+            if self.is_cancelled() {
+                log_debug!("streaming from {} cancelled", url);
+                return Err(Error::Cancelled);
+            }
+            on_line(&line)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Client for ReqwestClient {
+
+    fn make_json_request(&self, url: &str, payload: Value, headers: &[(&str, &str)], params: &[(&str, &str)]) -> Result<Value, Error> {
+        let ret = self.send_with_retries(url, &payload, headers, params);
+
+        if let Some(interceptor) = &self.interceptor {
+            interceptor(url, &payload, ret.as_ref());
+        }
+
+        ret
+    }
+
+    fn make_streaming_request(&self, url: &str, payload: Value, headers: &[(&str, &str)], params: &[(&str, &str)],
+        on_line: &mut dyn FnMut(&str) -> Result<(), Error>) -> Result<(), Error>
+    {
+        let ret = self.stream_with_retries(url, &payload, headers, params, on_line);
+
+        if let Some(interceptor) = &self.interceptor {
+            let streamed = Value::Null;
+            interceptor(url, &payload, ret.as_ref().map(|_| &streamed));
+        }
+
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(ReqwestClient::redact("Authorization", "Bearer abc"), "<redacted>");
+        assert_eq!(ReqwestClient::redact("x-api-key", "abc"), "<redacted>");
+        assert_eq!(ReqwestClient::redact("key", "abc"), "<redacted>");
+        assert_eq!(ReqwestClient::redact("anthropic-version", "2023-06-01"), "2023-06-01");
+
+        let payload = serde_json::json!({"model": "gpt-4", "auth": [{"api-key": "abc"}], "metadata": {"key": "abc"}});
+        assert_eq!(ReqwestClient::redact_payload(&payload),
+            serde_json::json!({"model": "gpt-4", "auth": [{"api-key": "<redacted>"}], "metadata": {"key": "<redacted>"}}));
+        assert_eq!(ReqwestClient::redact_pairs(&[("key", "abc"), ("alt", "sse")]),
+            vec![("key".to_owned(), "<redacted>".to_owned()), ("alt".to_owned(), "sse".to_owned())]);
+    }
+
+    #[test]
+    fn test_interceptor() {
+        use std::rc::Rc;
+        use std::cell::RefCell;
+
+        let calls = Rc::new(RefCell::new(vec![]));
+        let recorded = calls.clone();
+
+        let client = ReqwestClient::builder().interceptor(Box::new(move |url, payload, result| {
+            recorded.borrow_mut().push((url.to_owned(), payload.clone(), result.is_err()));
+        })).build();
+
+        // Nothing listens on port 1, so the request fails without network access.
+        let ret = client.make_json_request("http://127.0.0.1:1", serde_json::json!({"a": 1}), &[], &[]);
+
+        assert!(ret.is_err());
+        assert_eq!(*calls.borrow(), vec![("http://127.0.0.1:1".to_owned(), serde_json::json!({"a": 1}), true)]);
+    }
+
+    // Serve the responses one per connection on a local port, returning the URL
+    // and a handle that yields the received request lines with headers.
+    fn serve(responses: Vec<(u16, &'static str)>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        serve_raw(responses.into_iter()
+            .map(|(status, body)| format!("HTTP/1.1 {} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status, body.len(), body).into_bytes())
+            .collect())
+    }
+
+    fn serve_raw(responses: Vec<Vec<u8>>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind local port");
+        let url = format!("http://{}/v1beta/models/gemini:generateContent", listener.local_addr().expect("local address"));
+
+        let handle = std::thread::spawn(move || {
+            let mut request_lines = vec![];
+            for response in responses {
+                let (stream, _) = listener.accept().expect("accept connection");
+                let mut reader = BufReader::new(stream);
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).expect("read request line");
+
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).expect("read header");
+                    if line.trim_end().is_empty() {
+                        break;
+                    }
+                    request_line.push_str(&line);
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().expect("content length");
+                        }
+                    }
+                }
+                let mut payload = vec![0; content_length];
+                reader.read_exact(&mut payload).expect("read payload");
+                request_lines.push(request_line.trim_end().to_owned());
+
+                reader.get_mut().write_all(&response).expect("write response");
+            }
+            request_lines
+        });
+
+        (url, handle)
+    }
+
+    #[test]
+    fn test_retry_preserves_key_param() {
+        use crate::{Config, ModelProvider};
+        use crate::llm::{get_llm_chat, Message, Role};
+        use crate::tools::ToolChoice;
+
+        let (url, handle) = serve(vec![
+            (503, r#"{"error": {"code": 503, "message": "The model is overloaded.", "status": "UNAVAILABLE"}}"#),
+            (200, r#"{"candidates": [{"content": {"parts": [{"text": "hi"}], "role": "model"}, "finishReason": "STOP"}]}"#),
+        ]);
+
+        let config = Config::new(ModelProvider::GCP, "gemini".to_owned(), "<api-key>".to_owned(), url);
+        let mut chat = get_llm_chat(config, Box::new(ReqwestClient::builder().retry_policy(RetryPolicy::new(1)).build()), vec![]).expect("create chat");
+
+        let response = chat.get_inference(&[Message::text(Role::User, "hello".to_owned())], ToolChoice::None).expect("receive response");
+        assert!(matches!(&response[0], Message::Text(text) if text.message == "hi"));
+
+        let request_lines = handle.join().expect("server thread");
+        assert_eq!(request_lines.len(), 2);
+        for line in request_lines {
+            assert!(line.contains("key=%3Capi-key%3E"), "key param is missing in {line}");
+        }
+    }
+
+    #[test]
+    fn test_shared_client() {
+        let (url, handle) = serve(vec![
+            (503, r#"{"error": "overloaded"}"#),
+            (200, r#"{"a": 1}"#),
+            (200, r#"{"a": 2}"#),
+        ]);
+
+        // Options combine: the shared pool is used with retries.
+        let shared = BlockingClient::new();
+        let client1 = ReqwestClient::builder().client(shared.clone())
+            .retry_policy(RetryPolicy { base_delay: Duration::from_millis(1), ..RetryPolicy::new(1) })
+            .build();
+        let client2 = ReqwestClient::builder().client(shared).build();
+
+        assert_eq!(client1.make_json_request(&url, serde_json::json!({}), &[], &[]).expect("receive response"), serde_json::json!({"a": 1}));
+        assert_eq!(client2.make_json_request(&url, serde_json::json!({}), &[], &[]).expect("receive response"), serde_json::json!({"a": 2}));
+        assert_eq!(handle.join().expect("server thread").len(), 3);
+    }
+
+    #[test]
+    fn test_gzip_response() {
+        // gzip of `{"a": 1}`
+        let body: [u8; 28] = [31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 171, 86, 74, 84, 178, 82, 48, 172, 5, 0, 151, 142, 161, 251, 8, 0, 0, 0];
+        let mut response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()).into_bytes();
+        response.extend_from_slice(&body);
+
+        let (url, handle) = serve_raw(vec![response]);
+
+        let client = ReqwestClient::builder().build();
+        assert_eq!(client.make_json_request(&url, serde_json::json!({}), &[], &[]).expect("receive response"), serde_json::json!({"a": 1}));
+
+        let request = handle.join().expect("server thread").remove(0).to_lowercase();
+        assert!(request.contains("accept-encoding: gzip, br, deflate"), "no accept-encoding in {request}");
+    }
+
+    #[test]
+    fn test_user_agent() {
+        let (url, handle) = serve(vec![
+            (200, r#"{"a": 1}"#),
+            (200, r#"{"a": 2}"#),
+        ]);
+
+        ReqwestClient::builder().build().make_json_request(&url, serde_json::json!({}), &[], &[]).expect("receive response");
+        ReqwestClient::builder().user_agent("my-agent/1.0").build().make_json_request(&url, serde_json::json!({}), &[], &[]).expect("receive response");
+
+        let requests = handle.join().expect("server thread");
+        assert!(requests[0].to_lowercase().contains(&format!("user-agent: {}", DEFAULT_USER_AGENT)), "no default user agent in {}", requests[0]);
+        assert!(requests[1].to_lowercase().contains("user-agent: my-agent/1.0"), "no custom user agent in {}", requests[1]);
+    }
+
+    #[test]
+    fn test_no_retry_on_client_error() {
+        let (url, handle) = serve(vec![
+            (400, r#"{"error": {"code": 400, "message": "API key not valid. Please pass a valid API key.", "status": "INVALID_ARGUMENT"}}"#),
+        ]);
+
+        let client = ReqwestClient::builder().retry_policy(RetryPolicy::new(3)).build();
+        let response = client.make_json_request(&url, serde_json::json!({}), &[], &[("key", "bad")]).expect("receive response");
+
+        assert_eq!(response["error"]["message"], "API key not valid. Please pass a valid API key.");
+        assert_eq!(handle.join().expect("server thread").len(), 1);
+    }
+
+    #[test]
+    fn test_retry_malformed_response() {
+        let (url, handle) = serve(vec![
+            (200, r#"{"candidates": [{"content": {"#),
+            (200, r#"{"a": 1}"#),
+            (400, r#"{"error": "#),
+        ]);
+
+        let client = ReqwestClient::builder().retry_policy(RetryPolicy::new(1)).build();
+        assert_eq!(client.make_json_request(&url, serde_json::json!({}), &[], &[]).expect("receive response"), serde_json::json!({"a": 1}));
+
+        // Malformed error response is not retried.
+        let ret = client.make_json_request(&url, serde_json::json!({}), &[], &[]);
+        assert!(matches!(ret, Err(Error::LLMJsonError(_))));
+        assert_eq!(handle.join().expect("server thread").len(), 3);
+    }
+
+    #[test]
+    fn test_streaming_request() {
+        let (url, handle) = serve(vec![
+            (200, "[{\"a\": 1}\n,\n{\"a\": 2}]"),
+        ]);
+
+        let mut lines = vec![];
+        ReqwestClient::builder().build().make_streaming_request(&url, serde_json::json!({}), &[], &[], &mut |line| {
+            lines.push(line.to_owned());
+            Ok(())
+        }).expect("receive response");
+
+        assert_eq!(lines, vec!["[{\"a\": 1}", ",", "{\"a\": 2}]"]);
+        assert_eq!(handle.join().expect("server thread").len(), 1);
+    }
+
+    #[test]
+    fn test_cancellation() {
+        // Connections are queued by the listener that never responds, so the request waits until cancelled.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind local port");
+        let silent_url = format!("http://{}/", listener.local_addr().expect("local address"));
+
+        let token = CancellationToken::new();
+        let client = ReqwestClient::builder().cancellation(token.clone()).build();
+
+        let canceller = token.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            canceller.cancel();
+        });
+        let started = std::time::Instant::now();
+        let ret = client.make_json_request(&silent_url, serde_json::json!({}), &[], &[]);
+        assert!(matches!(ret, Err(Error::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // Cancelled token fails the requests until reset.
+        let (url, handle) = serve(vec![
+            (200, "[{\"a\": 1}\n,\n{\"a\": 2}]"),
+            (200, r#"{"a": 1}"#),
+        ]);
+        assert!(matches!(client.make_json_request(&url, serde_json::json!({}), &[], &[]), Err(Error::Cancelled)));
+        token.reset();
+
+        // Streaming stops at the next line after the cancellation.
+        let mut lines = vec![];
+        let ret = client.make_streaming_request(&url, serde_json::json!({}), &[], &[], &mut |line| {
+            lines.push(line.to_owned());
+            token.cancel();
+            Ok(())
+        });
+        assert!(matches!(ret, Err(Error::Cancelled)));
+        assert_eq!(lines, vec!["[{\"a\": 1}"]);
+
+        token.reset();
+        assert_eq!(client.make_json_request(&url, serde_json::json!({}), &[], &[]).expect("receive response"), serde_json::json!({"a": 1}));
+        assert_eq!(handle.join().expect("server thread").len(), 2);
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::Duration;
+
+/// How a client built with [`ReqwestClientBuilder::retry_policy`](super::ReqwestClientBuilder::retry_policy) repeats failed requests.
+/// The delay before the retry `n` (counting from 0) is `base_delay * 2^n`, capped at `max_delay`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of retries after the first attempt.
+    pub max_retries: u32,
+    /// Delay before the first retry.
+    pub base_delay: Duration,
+    /// Upper limit of each delay.
+    pub max_delay: Duration,
+    /// Wait a random time between half and the full delay,
+    /// so the clients that failed together don't retry together.
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+
+    /// Policy with up to `max_retries` retries, 500ms base delay capped at 30s, and no jitter.
+    pub fn new(max_retries: u32) -> Self {
+        RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: false,
+        }
+    }
+
+    /// Delay before the retry `attempt`, counting from 0.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay.saturating_mul(2u32.saturating_pow(attempt)).min(self.max_delay);
+        if self.jitter {
+            // Randomly seeded hasher is a good enough random source for spreading the retries.
+            let random = RandomState::new().hash_one(attempt) as f64 / u64::MAX as f64;
+            delay.mul_f64(0.5 + random / 2.0)
+        } else {
+            delay
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(2),
+            jitter: false,
+        };
+
+        let delays: Vec<Duration> = (0..40).map(|attempt| policy.delay(attempt)).collect();
+        assert_eq!(delays[..3], [Duration::from_millis(100), Duration::from_millis(200), Duration::from_millis(400)]);
+        assert!(delays.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(delays.iter().all(|delay| *delay <= policy.max_delay));
+        assert_eq!(delays[39], policy.max_delay);
+
+        let jittered = RetryPolicy { jitter: true, ..policy };
+        for attempt in 0..40 {
+            let delay = jittered.delay(attempt);
+            assert!(delay >= policy.delay(attempt) / 2 && delay <= policy.delay(attempt));
+        }
+    }
+}
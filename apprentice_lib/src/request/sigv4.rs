@@ -0,0 +1,134 @@
+//! AWS Signature Version 4 request signing.
+use std::time::{SystemTime, UNIX_EPOCH};
+use ring::{digest, hmac};
+use crate::config::AwsCredentials;
+use crate::error::Error;
+
+/// Return the headers that authenticate a POST request of the JSON `body` to `url` for the AWS `service`.
+/// Only `host` and `x-amz-date` (and `x-amz-security-token` with temporary credentials) are signed,
+/// so the headers added by the transport, e.g. `content-type`, don't affect the signature.
+pub fn sign(credentials: &AwsCredentials, service: &str, url: &str, body: &[u8], time: SystemTime) -> Result<Vec<(String, String)>, Error> {
+    let url = reqwest::Url::parse(url)
+        .map_err(|err| Error::Error(format!("invalid request url {}: {}", url, err)))?;
+    let host = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_owned(),
+        (None, _) => return Err(Error::Error(format!("request url {} has no host.", url))),
+    };
+
+    let (date, timestamp) = format_time(time);
+
+    let mut headers = vec![("host", host), ("x-amz-date", timestamp.clone())];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", token.clone()));
+    }
+
+    let signed_headers = headers.iter().map(|(k, _)| *k).collect::<Vec<_>>().join(";");
+    let canonical_headers: String = headers.iter().map(|(k, v)| format!("{}:{}\n", k, v.trim())).collect();
+
+    // Path segments are already encoded in the URL, and are encoded once more for all services except S3.
+    let canonical_uri = url.path().split('/').map(uri_encode).collect::<Vec<_>>().join("/");
+
+    let mut query: Vec<(String, String)> = url.query_pairs()
+        .map(|(k, v)| (uri_encode(&k), uri_encode(&v)))
+        .collect();
+    query.sort();
+    let canonical_query = query.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&");
+
+    let canonical_request = format!("POST\n{}\n{}\n{}\n{}\n{}",
+        canonical_uri, canonical_query, canonical_headers, signed_headers, hex(digest::digest(&digest::SHA256, body).as_ref()));
+
+    let scope = format!("{}/{}/{}/aws4_request", date, credentials.region, service);
+    let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        timestamp, scope, hex(digest::digest(&digest::SHA256, canonical_request.as_bytes()).as_ref()));
+
+    let mut key = format!("AWS4{}", credentials.secret_access_key).into_bytes();
+    for part in [date.as_str(), credentials.region.as_str(), service, "aws4_request"] {
+        key = hmac_sha256(&key, part.as_bytes());
+    }
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+
+    let mut result = vec![
+        ("Authorization".to_owned(), format!("AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            credentials.access_key_id, scope, signed_headers, signature)),
+        ("x-amz-date".to_owned(), timestamp),
+    ];
+    if let Some(token) = &credentials.session_token {
+        result.push(("x-amz-security-token".to_owned(), token.clone()));
+    }
+
+    Ok(result)
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data).as_ref().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Percent-encode everything except the unreserved characters.
+fn uri_encode(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            result.push(byte as char);
+        } else {
+            result.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    result
+}
+
+// Date (`YYYYMMDD`) and timestamp (`YYYYMMDDTHHMMSSZ`) in UTC.
+fn format_time(time: SystemTime) -> (String, String) {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+
+    // Civil date from the number of days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let date = format!("{:04}{:02}{:02}", year, month, day);
+    let timestamp = format!("{}T{:02}{:02}{:02}Z", date, rem / 3600, rem % 3600 / 60, rem % 60);
+    (date, timestamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn credentials() -> AwsCredentials {
+        AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_owned(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_owned(),
+            session_token: None,
+            region: "us-east-1".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_sign() {
+        // `post-vanilla` case of the AWS SigV4 test suite, 2015-08-30T12:36:00Z.
+        let time = UNIX_EPOCH + Duration::from_secs(1440938160);
+        assert_eq!(format_time(time), ("20150830".to_owned(), "20150830T123600Z".to_owned()));
+
+        let headers = sign(&credentials(), "service", "https://example.amazonaws.com/", b"", time).expect("sign request");
+        assert_eq!(headers, vec![
+            ("Authorization".to_owned(), "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+                SignedHeaders=host;x-amz-date, Signature=5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b".to_owned()),
+            ("x-amz-date".to_owned(), "20150830T123600Z".to_owned()),
+        ]);
+
+        assert_eq!(uri_encode("anthropic.claude-v2%3A1"), "anthropic.claude-v2%253A1");
+    }
+}
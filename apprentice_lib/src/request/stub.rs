@@ -1,5 +1,8 @@
-//! Only for tests.
+//! Clients for tests, available with the `testing` feature.
 
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
 use serde_json::Value;
 use crate::error::Error;
 use crate::request::client::Client;
@@ -8,13 +11,14 @@ use crate::request::client::Client;
 pub struct StubClient {
     expected_headers: Vec<(String, String)>,
     expected_params: Vec<(String, String)>,
-    expected_payload: Value,
+    expected_payload: Option<Value>,
     response_body: Value,
 }
 
 impl StubClient {
 
-    /// Create client.
+    /// Create client that checks the request headers, params, and payload,
+    /// and returns the response body.
     pub fn new(expected_headers: Vec<(String, String)>,
         expected_params: Vec<(String, String)>,
         expected_payload: Value, 
@@ -23,7 +27,17 @@ impl StubClient {
         StubClient {
             expected_headers,
             expected_params,
-            expected_payload,
+            expected_payload: Some(expected_payload),
+            response_body,
+        }
+    }
+
+    /// Create client that returns the response body for any request.
+    pub fn with_response(response_body: Value) -> Self {
+        StubClient {
+            expected_headers: vec![],
+            expected_params: vec![],
+            expected_payload: None,
             response_body,
         }
     }
@@ -42,8 +56,139 @@ impl Client for StubClient {
             assert_eq!(expected.1, actual.1, "params values");
         }
 
-        assert_eq!(payload, self.expected_payload);
+        if let Some(expected_payload) = &self.expected_payload {
+            assert_eq!(&payload, expected_payload);
+        }
+
+        Ok(self.response_body.clone())
+    }
+}
+
+/// Client that returns scripted responses in order, one per request.
+pub struct SequenceClient {
+    responses: RefCell<VecDeque<Value>>,
+}
+
+impl SequenceClient {
+
+    /// Create client.
+    pub fn new(responses: Vec<Value>) -> Self {
+        SequenceClient {
+            responses: RefCell::new(responses.into()),
+        }
+    }
+}
+
+impl Client for SequenceClient {
+
+    fn make_json_request(&self, _url: &str, _payload: Value, _headers: &[(&str, &str)], _params: &[(&str, &str)]) -> Result<Value, Error> {
+        self.responses.borrow_mut()
+            .pop_front()
+            .ok_or(Error::Error("no more scripted responses.".to_owned()))
+    }
+}
+
+/// Request received by `RecordingClient`.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    /// URL.
+    pub url: String,
+    /// Payload.
+    pub payload: Value,
+    /// Headers.
+    pub headers: Vec<(String, String)>,
+    /// Query parameters.
+    pub params: Vec<(String, String)>,
+}
+
+/// Client that records the requests and returns the response body.
+/// Clones share the records, so a clone kept by the test can inspect the requests of the client passed to a chat.
+#[derive(Clone)]
+pub struct RecordingClient {
+    requests: Rc<RefCell<Vec<RecordedRequest>>>,
+    response_body: Value,
+    stream_body: String,
+}
+
+impl RecordingClient {
+
+    /// Create client.
+    pub fn new(response_body: Value) -> Self {
+        RecordingClient {
+            requests: Rc::new(RefCell::new(vec![])),
+            response_body,
+            stream_body: String::new(),
+        }
+    }
+
+    /// Create client that streams the lines of `stream_body` in response to the streaming requests.
+    pub fn streaming(stream_body: &str) -> Self {
+        RecordingClient {
+            requests: Rc::new(RefCell::new(vec![])),
+            response_body: Value::Null,
+            stream_body: stream_body.to_owned(),
+        }
+    }
+
+    fn record(&self, url: &str, payload: Value, headers: &[(&str, &str)], params: &[(&str, &str)]) {
+        let to_owned = |pairs: &[(&str, &str)]| pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        self.requests.borrow_mut().push(RecordedRequest {
+            url: url.to_owned(),
+            payload,
+            headers: to_owned(headers),
+            params: to_owned(params),
+        });
+    }
+
+    /// Requests received so far.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.borrow().clone()
+    }
+}
+
+impl Client for RecordingClient {
 
+    fn make_json_request(&self, url: &str, payload: Value, headers: &[(&str, &str)], params: &[(&str, &str)]) -> Result<Value, Error> {
+        self.record(url, payload, headers, params);
         Ok(self.response_body.clone())
     }
-}
\ No newline at end of file
+
+    fn make_streaming_request(&self, url: &str, payload: Value, headers: &[(&str, &str)], params: &[(&str, &str)],
+        on_line: &mut dyn FnMut(&str) -> Result<(), Error>) -> Result<(), Error>
+    {
+        self.record(url, payload, headers, params);
+        self.stream_body.lines().try_for_each(on_line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::llm::{get_llm_chat, Message, Role};
+    use crate::tools::ToolChoice;
+    use crate::{Config, ModelProvider};
+
+    #[test]
+    fn test_sequence_client() {
+        let config = Config::new(ModelProvider::OpenAI, "<model-name>".to_owned(), "<api-key>".to_owned(), "<api-uri>".to_owned());
+
+        let client = Box::new(SequenceClient::new(vec![
+            json!({"choices": [{"message": {"role": "assistant", "tool_calls": [
+                {"id": "call_1", "type": "function", "function": {"name": "tool1", "arguments": "{}"}}
+            ]}, "finish_reason": "tool_calls"}]}),
+            json!({"choices": [{"message": {"role": "assistant", "content": "done"}, "finish_reason": "stop"}]}),
+        ]));
+
+        let mut chat = get_llm_chat(config, client, vec![]).expect("chat created");
+
+        let response = chat.get_inference(&[Message::text(Role::User, "hi".to_owned())], ToolChoice::None).expect("response");
+        assert!(matches!(&response[0], Message::ToolCall(call) if call.name == "tool1"));
+
+        let result = Message::tool_result("call_1".to_owned(), "tool1".to_owned(), "ok".to_owned());
+        let response = chat.get_inference(&[result], ToolChoice::None).expect("response");
+        assert!(matches!(&response[0], Message::Text(txt) if txt.message == "done"));
+
+        assert!(chat.get_inference(&[], ToolChoice::None).is_err());
+    }
+}
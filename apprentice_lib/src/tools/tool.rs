@@ -1,4 +1,7 @@
+use std::collections::HashSet;
 use serde::Serialize;
+use serde_json::Value;
+use crate::error::Error;
 
 /// Tool parameter data types.
 pub enum ParamType {
@@ -12,15 +15,31 @@ pub enum ParamType {
     Boolean,
 }
 
+impl ParamType {
+    fn as_str(&self) -> &'static str {
+        match &self {
+            ParamType::String => "string",
+            ParamType::Integer => "integer",
+            ParamType::Number => "number",
+            ParamType::Boolean => "boolean",
+        }
+    }
+
+    /// Whether the JSON value is of this type.
+    fn matches(&self, value: &Value) -> bool {
+        match &self {
+            ParamType::String => value.is_string(),
+            ParamType::Integer => value.is_i64() || value.is_u64(),
+            ParamType::Number => value.is_number(),
+            ParamType::Boolean => value.is_boolean(),
+        }
+    }
+}
+
 impl Serialize for ParamType {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where S: serde::Serializer {
-        match &self {
-            ParamType::String => serializer.serialize_str("string"),
-            ParamType::Integer => serializer.serialize_str("integer"),
-            ParamType::Number => serializer.serialize_str("number"),
-            ParamType::Boolean => serializer.serialize_str("boolean"),
-        }
+        serializer.serialize_str(self.as_str())
     }
 }
 
@@ -34,6 +53,92 @@ pub struct ToolParam {
     pub data_type: ParamType,
     /// Value is required.
     pub required: bool,
+    pub(crate) default: Option<Value>,
+    pub(crate) minimum: Option<f64>,
+    pub(crate) maximum: Option<f64>,
+    pub(crate) min_length: Option<u64>,
+    pub(crate) max_length: Option<u64>,
+    pub(crate) pattern: Option<String>,
+}
+
+impl ToolParam {
+
+    /// Create parameter without default value and constraints.
+    pub fn new(name: String, description: String, data_type: ParamType, required: bool) -> Self {
+        ToolParam {
+            name,
+            description,
+            data_type,
+            required,
+            default: None,
+            minimum: None,
+            maximum: None,
+            min_length: None,
+            max_length: None,
+            pattern: None,
+        }
+    }
+
+    /// Set the value assumed when the model omits the parameter, the caller fills it in.
+    /// Only optional parameters can have a default, and it must be of `data_type`.
+    pub fn with_default(mut self, default: Value) -> Self {
+        self.default = Some(default);
+        self
+    }
+
+    /// Set the minimum value (`Integer` and `Number` only).
+    pub fn with_minimum(mut self, minimum: f64) -> Self {
+        self.minimum = Some(minimum);
+        self
+    }
+
+    /// Set the maximum value (`Integer` and `Number` only).
+    pub fn with_maximum(mut self, maximum: f64) -> Self {
+        self.maximum = Some(maximum);
+        self
+    }
+
+    /// Set the minimum length (`String` only).
+    pub fn with_min_length(mut self, min_length: u64) -> Self {
+        self.min_length = Some(min_length);
+        self
+    }
+
+    /// Set the maximum length (`String` only).
+    pub fn with_max_length(mut self, max_length: u64) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Set the regular expression the value must match (`String` only).
+    pub fn with_pattern(mut self, pattern: String) -> Self {
+        self.pattern = Some(pattern);
+        self
+    }
+
+    /// Value assumed when the model omits the parameter.
+    pub fn default(&self) -> Option<&Value> {
+        self.default.as_ref()
+    }
+
+    fn validate_constraints(&self, tool: &str) -> Result<(), Error> {
+        let numeric = matches!(self.data_type, ParamType::Integer | ParamType::Number);
+        if !numeric && (self.minimum.is_some() || self.maximum.is_some()) {
+            return Err(Error::Error(format!("parameter {} of tool {} is not a number and can't have minimum or maximum.", self.name, tool)));
+        }
+
+        let string = matches!(self.data_type, ParamType::String);
+        if !string && (self.min_length.is_some() || self.max_length.is_some() || self.pattern.is_some()) {
+            return Err(Error::Error(format!("parameter {} of tool {} is not a string and can't have length or pattern.", self.name, tool)));
+        }
+
+        if self.minimum.zip(self.maximum).is_some_and(|(min, max)| min > max)
+            || self.min_length.zip(self.max_length).is_some_and(|(min, max)| min > max) {
+            return Err(Error::Error(format!("parameter {} of tool {} has minimum greater than maximum.", self.name, tool)));
+        }
+
+        Ok(())
+    }
 }
 
 /// Tool specification.
@@ -46,6 +151,40 @@ pub struct ToolSpec {
     pub params: Vec<ToolParam>,
 }
 
+impl ToolSpec {
+
+    /// Check that the tool and its parameters have names, the parameter names are unique,
+    /// the defaults are set only for optional parameters and match their types,
+    /// and the constraints are set only for the types they apply to.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.name.is_empty() {
+            return Err(Error::Error("tool name is empty.".to_owned()));
+        }
+
+        let mut names = HashSet::with_capacity(self.params.len());
+        for param in self.params.iter() {
+            param.validate_constraints(&self.name)?;
+            if param.name.is_empty() {
+                return Err(Error::Error(format!("tool {} has a parameter with empty name.", self.name)));
+            }
+            if !names.insert(param.name.as_str()) {
+                return Err(Error::Error(format!("tool {} has duplicate parameter {}.", self.name, param.name)));
+            }
+            if let Some(default) = &param.default {
+                if param.required {
+                    return Err(Error::Error(format!("required parameter {} of tool {} can't have a default value.", param.name, self.name)));
+                }
+                if !param.data_type.matches(default) {
+                    return Err(Error::Error(format!("default value {} of parameter {} of tool {} is not {}.",
+                        default, param.name, self.name, param.data_type.as_str())));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
 /// Tool choice settings.
 pub enum ToolChoice {
     /// Do not use tools.
@@ -55,5 +194,79 @@ pub enum ToolChoice {
     /// LLM must use any one of the provided tools.
     CallOne,
     /// LLM must call specified tool (name).
-    Force(String)
-}
\ No newline at end of file
+    Force(String),
+    /// Same as `Auto`, but the named tools are not provided to LLM.
+    Except(Vec<String>),
+    /// Same as `Auto`, but only the named tools are provided to LLM,
+    /// e.g. to save tokens with a large tool set.
+    Subset(Vec<String>),
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(name: &str) -> ToolParam {
+        ToolParam::new(name.to_owned(), "param desc".to_owned(), ParamType::String, true)
+    }
+
+    fn spec(name: &str, params: Vec<ToolParam>) -> ToolSpec {
+        ToolSpec {
+            name: name.to_owned(),
+            description: "tool desc".to_owned(),
+            params,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(spec("tool1", vec![param("param1"), param("param2")]).validate().is_ok());
+        assert!(spec("tool1", vec![]).validate().is_ok());
+
+        let err = spec("", vec![param("param1")]).validate().expect_err("empty tool name");
+        assert_eq!(err.to_string(), "tool name is empty.");
+
+        let err = spec("tool1", vec![param("param1"), param("")]).validate().expect_err("empty param name");
+        assert_eq!(err.to_string(), "tool tool1 has a parameter with empty name.");
+
+        let err = spec("tool1", vec![param("param1"), param("param1")]).validate().expect_err("duplicate param");
+        assert_eq!(err.to_string(), "tool tool1 has duplicate parameter param1.");
+    }
+
+    #[test]
+    fn test_validate_default() {
+        let optional = |data_type: ParamType, default: Value|
+            ToolParam::new("param1".to_owned(), "param desc".to_owned(), data_type, false).with_default(default);
+
+        assert!(spec("tool1", vec![optional(ParamType::Integer, Value::from(10))]).validate().is_ok());
+        assert!(spec("tool1", vec![optional(ParamType::Number, Value::from(10))]).validate().is_ok());
+        assert!(spec("tool1", vec![optional(ParamType::Boolean, Value::Bool(false))]).validate().is_ok());
+
+        let err = spec("tool1", vec![optional(ParamType::Integer, Value::from(0.5))]).validate().expect_err("type mismatch");
+        assert_eq!(err.to_string(), "default value 0.5 of parameter param1 of tool tool1 is not integer.");
+
+        let required = param("param1").with_default(Value::from("ls"));
+        let err = spec("tool1", vec![required]).validate().expect_err("required param with default");
+        assert_eq!(err.to_string(), "required parameter param1 of tool tool1 can't have a default value.");
+    }
+
+    #[test]
+    fn test_validate_constraints() {
+        let mut count = param("count").with_minimum(1.0).with_maximum(10.0);
+        count.data_type = ParamType::Integer;
+        let name = param("name").with_max_length(64).with_pattern("^[a-z]+$".to_owned());
+        assert!(spec("tool1", vec![count, name]).validate().is_ok());
+
+        let name = param("name").with_minimum(1.0);
+        let err = spec("tool1", vec![name]).validate().expect_err("minimum of string");
+        assert_eq!(err.to_string(), "parameter name of tool tool1 is not a number and can't have minimum or maximum.");
+
+        let mut count = param("count").with_pattern("^[0-9]+$".to_owned());
+        count.data_type = ParamType::Integer;
+        let err = spec("tool1", vec![count]).validate().expect_err("pattern of integer");
+        assert_eq!(err.to_string(), "parameter count of tool tool1 is not a string and can't have length or pattern.");
+
+        let name = param("name").with_min_length(10).with_max_length(5);
+        let err = spec("tool1", vec![name]).validate().expect_err("min length greater than max length");
+        assert_eq!(err.to_string(), "parameter name of tool tool1 has minimum greater than maximum.");
+    }
+}